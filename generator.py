@@ -14,6 +14,16 @@ REGISTRIES = {
     "data/minecraft/worldgen/biome": "minecraft:worldgen/biome",
     "data/minecraft/dimension_type": "minecraft:dimension_type",
     "data/minecraft/damage_type": "minecraft:damage_type",
+    "data/minecraft/chat_type": "minecraft:chat_type",
+}
+
+BLOCKS_REPORT = "reports/blocks.json"
+REGISTRIES_REPORT = "reports/registries.json"
+
+# registries whose entries are only needed in the order of their IDs
+REGISTRY_LISTS = {
+    "minecraft:entity_type": "entity_types.json",
+    "minecraft:block_entity_type": "block_entity_types.json",
 }
 
 
@@ -54,8 +64,79 @@ def build_registries_dict(registries: dict[str, str]) -> dict:
     return result
 
 
+def build_blocks_list(report_path: str) -> list[dict[str, Any]]:
+    with open(report_path, "r", encoding="UTF-8") as report_file:
+        report = json.load(report_file)
+
+    blocks = []
+    for name, block in report.items():
+        properties = [
+            {"name": property_name, "values": values}
+            for property_name, values in block.get("properties", {}).items()
+        ]
+        states = block["states"]
+        min_state_id = min(state["id"] for state in states)
+        default_state_id = next(state["id"] for state in states if state.get("default"))
+
+        # the server computes state IDs from the property values, where the
+        # last property changes the fastest - make sure it holds
+        for index, state in enumerate(sorted(states, key=lambda state: state["id"])):
+            assert state["id"] == min_state_id + index, f"{name} states aren't contiguous"
+            expected_index = 0
+            for block_property in properties:
+                value = state["properties"][block_property["name"]]
+                expected_index = expected_index * len(block_property["values"]) + block_property["values"].index(value)
+            assert expected_index == index, f"{name} states aren't in the expected order"
+
+        blocks.append({
+            "name": name,
+            "min_state_id": min_state_id,
+            "default_state_id": default_state_id,
+            "properties": properties,
+        })
+
+    blocks.sort(key=lambda block: block["min_state_id"])
+
+    # the client knows every state ID, so the registry can't have any gaps
+    next_state_id = 0
+    for block in blocks:
+        assert block["min_state_id"] == next_state_id, f"states before {block['name']} are missing"
+        next_state_id += len(report[block["name"]]["states"])
+    print(f"* Collected {len(blocks)} blocks with {next_state_id} states")
+    return blocks
+
+
+def build_registry_list(report_path: str, registry: str) -> list[str]:
+    with open(report_path, "r", encoding="UTF-8") as report_file:
+        report = json.load(report_file)
+
+    entries = report[registry]["entries"]
+    names = sorted(entries, key=lambda name: entries[name]["protocol_id"])
+    for index, name in enumerate(names):
+        assert entries[name]["protocol_id"] == index, f"{name} has an unexpected ID"
+    print(f"* Collected {len(names)} entries of {registry}")
+    return names
+
+
 if __name__ == "__main__":
     registries = build_registries_dict(REGISTRIES)
     with open("new_registry.json", "w+", encoding="UTF-8") as output_file:
         json.dump(registries, output_file, indent=2)
     print(f"Done. Wrote `new_registry.json` with {len(registries)} entries")
+
+    if os.path.isfile(BLOCKS_REPORT):
+        blocks = build_blocks_list(BLOCKS_REPORT)
+        with open("blocks.json", "w+", encoding="UTF-8") as output_file:
+            json.dump(blocks, output_file, indent=2)
+        print(f"Done. Wrote `blocks.json` with {len(blocks)} blocks")
+    else:
+        print(f"Warning: {BLOCKS_REPORT} doesn't exist, skipping blocks...")
+
+    if os.path.isfile(REGISTRIES_REPORT):
+        for registry, output_path in REGISTRY_LISTS.items():
+            names = build_registry_list(REGISTRIES_REPORT, registry)
+            with open(output_path, "w+", encoding="UTF-8") as output_file:
+                json.dump(names, output_file, indent=2)
+            print(f"Done. Wrote `{output_path}` with {len(names)} entries")
+    else:
+        print(f"Warning: {REGISTRIES_REPORT} doesn't exist, skipping registry lists...")
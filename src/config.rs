@@ -0,0 +1,319 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rsa::RsaPublicKey;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    chat::{self, ChatError},
+    player::GameMode,
+    registry::RegistryDimensionType,
+    world::schematic::{Mirror, Rotation},
+};
+
+/// Path of the configuration file, relative to the working directory.
+pub const CONFIG_PATH: &str = "config.json";
+
+/// Preset of the default superflat world, in the vanilla format.
+pub const DEFAULT_FLAT_PRESET: &str =
+    "minecraft:bedrock,2*minecraft:dirt,minecraft:grass_block;minecraft:plains";
+
+/// Errors that can occur while loading the configuration.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    /// Indicates that the configuration file couldn't be read or written.
+    #[error("I/O error has occurred: {0}")]
+    IoError(#[from] io::Error),
+    /// Indicates that the configuration file is malformed.
+    #[error("failed to parse the configuration: {0}")]
+    JsonError(#[from] serde_json::Error),
+    /// Indicates that the spawn dimension isn't one of the configured ones.
+    #[error("spawn dimension {0} isn't configured")]
+    UnknownSpawnDimension(String),
+    /// Indicates that the secure chat is enforced, but there are no keys of
+    /// Mojang's services to verify the chat sessions of the players with.
+    #[error("secure chat can't be enforced without the services public keys")]
+    MissingServicesKeys,
+    /// Indicates that a public key of Mojang's services is malformed.
+    #[error("invalid services public key: {0}")]
+    InvalidServicesKey(#[from] ChatError),
+}
+
+/// Configuration of the chunk generator of a dimension.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GeneratorConfig {
+    /// Superflat world made of the layers from the preset, i.e.
+    /// `minecraft:bedrock,2*minecraft:dirt,minecraft:grass_block;minecraft:plains`.
+    Flat {
+        #[serde(default = "default_flat_preset")]
+        preset: String,
+    },
+    /// Natural terrain shaped by the noise, which depends on the seed.
+    Noise,
+    /// Empty world with a single square platform around the spawn.
+    Void {
+        #[serde(default = "default_platform_block")]
+        platform_block: String,
+        #[serde(default = "default_platform_y")]
+        platform_y: i32,
+        /// Distance from the center of the platform to its edges.
+        #[serde(default = "default_platform_radius")]
+        platform_radius: i32,
+    },
+}
+
+fn default_flat_preset() -> String {
+    DEFAULT_FLAT_PRESET.to_string()
+}
+
+fn default_platform_block() -> String {
+    String::from("minecraft:stone")
+}
+
+fn default_platform_y() -> i32 {
+    64
+}
+
+fn default_platform_radius() -> i32 {
+    4
+}
+
+fn default_sea_level() -> i32 {
+    63
+}
+
+fn default_autosave_interval() -> u64 {
+    300
+}
+
+fn default_view_distance() -> u8 {
+    10
+}
+
+/// Returns the seed for the new configurations, which is based on the current
+/// time.
+fn random_seed() -> i64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    now.as_nanos() as i64
+}
+
+/// Configuration of a single dimension (world) of the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DimensionConfig {
+    /// Name of the dimension type from the `minecraft:dimension_type`
+    /// registry, which defines i.e. the height of the world.
+    pub dimension_type: String,
+    pub generator: GeneratorConfig,
+    /// The level water fills the terrain up to (exclusively).
+    #[serde(default = "default_sea_level")]
+    pub sea_level: i32,
+    /// Directory of the dimension in the vanilla (Anvil) format, which
+    /// contains the `region` directory, i.e. `world` for the overworld or
+    /// `world/DIM-1` for the nether. Chunks that aren't stored there are
+    /// produced by the generator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub world_directory: Option<String>,
+    /// Schematics that are pasted into the world when the server starts, i.e.
+    /// the lobby buildings. Each one is pasted into a world with a directory
+    /// only once, as the world keeps it afterwards.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schematics: Vec<SchematicConfig>,
+}
+
+/// A schematic pasted into the world on startup.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchematicConfig {
+    /// Path to the Sponge (`.schem`) or structure (`.nbt`) file.
+    pub path: String,
+    /// Coordinates of the first corner of the schematic.
+    pub position: [i32; 3],
+    #[serde(default)]
+    pub rotation: Rotation,
+    #[serde(default)]
+    pub mirror: Mirror,
+}
+
+/// Configuration of the whole server, loaded from `CONFIG_PATH`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    /// Dimensions hosted by the server, by their names.
+    pub dimensions: BTreeMap<String, DimensionConfig>,
+    /// Dimension types added to the vanilla ones, by their names, which the
+    /// dimensions may use. Types with the vanilla names replace them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dimension_types: BTreeMap<String, RegistryDimensionType>,
+    /// Name of the dimension the players join into.
+    pub spawn_dimension: String,
+    /// Seed of the worlds, which makes the generation deterministic.
+    #[serde(default = "random_seed")]
+    pub seed: i64,
+    /// Interval between the saves of the changed chunks, in seconds. Zero
+    /// disables the autosave, so the chunks are saved only on shutdown.
+    #[serde(default = "default_autosave_interval")]
+    pub autosave_interval: u64,
+    /// The largest radius (in chunks) of the area around the players that is
+    /// sent to them. Clients may request a smaller one.
+    #[serde(default = "default_view_distance")]
+    pub view_distance: u8,
+    /// Game mode of the players that join the server.
+    #[serde(default)]
+    pub game_mode: GameMode,
+    /// Whether the chat messages have to be signed. Players without a chat
+    /// session are disconnected when they chat then.
+    #[serde(default)]
+    pub enforce_secure_chat: bool,
+    /// Public keys of Mojang's services, in base64 DER, as the
+    /// `playerCertificateKeys` from `https://api.minecraftservices.com/publickeys`.
+    /// The chat sessions of the players have to be signed by any of them.
+    /// Required when the secure chat is enforced.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub services_public_keys: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let overworld = DimensionConfig {
+            dimension_type: String::from("minecraft:overworld"),
+            generator: GeneratorConfig::Flat {
+                preset: default_flat_preset(),
+            },
+            sea_level: default_sea_level(),
+            world_directory: Some(String::from("world")),
+            schematics: vec![],
+        };
+        Self {
+            dimensions: BTreeMap::from([(String::from("minecraft:overworld"), overworld)]),
+            dimension_types: BTreeMap::new(),
+            spawn_dimension: String::from("minecraft:overworld"),
+            seed: random_seed(),
+            autosave_interval: default_autosave_interval(),
+            view_distance: default_view_distance(),
+            game_mode: GameMode::default(),
+            enforce_secure_chat: false,
+            services_public_keys: vec![],
+        }
+    }
+}
+
+impl Config {
+    /// Loads the configuration from the provided path. If there is no such
+    /// file, the default configuration is written there and returned.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let config = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(path)?)?
+        } else {
+            let config = Self::default();
+            fs::write(path, serde_json::to_string_pretty(&config)?)?;
+            config
+        };
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Returns the configuration of the dimension the players join into.
+    pub fn spawn_dimension(&self) -> &DimensionConfig {
+        &self.dimensions[&self.spawn_dimension]
+    }
+
+    /// Parses the public keys of Mojang's services.
+    pub fn services_keys(&self) -> Result<Vec<RsaPublicKey>, ConfigError> {
+        let keys = self
+            .services_public_keys
+            .iter()
+            .map(|key| chat::parse_services_key(key))
+            .collect::<Result<_, _>>()?;
+        Ok(keys)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if !self.dimensions.contains_key(&self.spawn_dimension) {
+            return Err(ConfigError::UnknownSpawnDimension(
+                self.spawn_dimension.clone(),
+            ));
+        }
+        if self.enforce_secure_chat && self.services_public_keys.is_empty() {
+            return Err(ConfigError::MissingServicesKeys);
+        }
+        self.services_keys()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl DimensionConfig {
+    /// Returns the configuration of an overworld made of the layers of the
+    /// superflat preset, which isn't stored anywhere.
+    pub fn test_flat(preset: &str) -> Self {
+        Self {
+            dimension_type: String::from("minecraft:overworld"),
+            generator: GeneratorConfig::Flat {
+                preset: preset.to_string(),
+            },
+            sea_level: default_sea_level(),
+            world_directory: None,
+            schematics: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generator_defaults() {
+        let config: Config = serde_json::from_str(
+            r#"{
+                "dimensions": {
+                    "lobby": {
+                        "dimension_type": "minecraft:overworld",
+                        "generator": { "type": "void", "platform_y": 100 }
+                    }
+                },
+                "spawn_dimension": "lobby",
+                "seed": 42
+            }"#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.spawn_dimension().sea_level, 63);
+
+        match &config.spawn_dimension().generator {
+            GeneratorConfig::Void {
+                platform_block,
+                platform_y,
+                platform_radius,
+            } => {
+                assert_eq!(platform_block, "minecraft:stone");
+                assert_eq!(*platform_y, 100);
+                assert_eq!(*platform_radius, 4);
+            }
+            generator => panic!("unexpected generator: {generator:?}"),
+        }
+    }
+
+    #[test]
+    fn test_secure_chat_requires_services_keys() {
+        let mut config = Config {
+            enforce_secure_chat: true,
+            ..Config::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::MissingServicesKeys)
+        ));
+        config.services_public_keys = vec![String::from("not a key")];
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::InvalidServicesKey(_))
+        ));
+    }
+}
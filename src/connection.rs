@@ -1,5 +1,5 @@
 use std::{
-    io::{Error, Read, Write},
+    io::{Error, Read},
     net::TcpStream,
     sync::Arc,
 };
@@ -7,13 +7,17 @@ use std::{
 use thiserror::Error;
 
 use crate::{
-    network::{BUFFER_CAPACITY, PacketReader, PacketReaderError},
+    network::{BUFFER_CAPACITY, PacketReader, PacketReaderError, PacketWriter},
+    player::Player,
     protocol::{
-        ProtocolState, ReadError, Writeable,
-        packets::Packet,
+        ProtocolState, ReadError,
+        packets::{Packet, play::ClientboundDisconnectPacket},
         registry::{HandlersRegistry, PacketsRegistry},
+        text::TextComponent,
     },
-    varint::{VarInt, VarIntError},
+    server::Server,
+    varint::VarIntError,
+    world::{World, tracker::ChunkTracker},
 };
 
 /// Errors that can occur with the client-server connection.
@@ -35,7 +39,18 @@ pub enum ConnectionError {
 pub struct Connection {
     stream: TcpStream,
     reader: PacketReader,
+    /// Writer of the packets to the client, which is shared with the worlds
+    /// to broadcast their changes.
+    pub writer: Arc<PacketWriter>,
     pub state: ProtocolState,
+    /// State of the server shared between all connections.
+    pub server: Arc<Server>,
+    /// The player of the client, once it has started logging in.
+    pub player: Option<Arc<Player>>,
+    /// The world the client plays in.
+    pub world: Arc<World>,
+    /// Chunks of the world the client has loaded.
+    pub chunk_tracker: ChunkTracker,
 
     // registries
     registry: Arc<PacketsRegistry>,
@@ -44,20 +59,46 @@ pub struct Connection {
 
 impl Connection {
     /// Creates a new instance of the `Connection` with the provided underlying
-    /// stream, packet and handler registries.
+    /// stream, server, packet and handler registries. The client joins into
+    /// the spawn world.
     pub fn new(
         stream: TcpStream,
+        server: Arc<Server>,
         registry: Arc<PacketsRegistry>,
         handler_registry: Arc<HandlersRegistry>,
-    ) -> Self {
-        Self {
+    ) -> Result<Self, ConnectionError> {
+        let writer = PacketWriter::from_tcp_stream(&stream).map_err(ConnectionError::IoError)?;
+        Ok(Self {
             stream,
             reader: PacketReader::default(),
+            writer: Arc::new(writer),
             state: ProtocolState::Handshake,
+            chunk_tracker: ChunkTracker::new(server.config.view_distance as i32),
+            world: server.worlds.spawn_world().clone(),
+            server,
+            player: None,
             registry,
             handler_registry,
-            // client_information: None,
-        }
+        })
+    }
+
+    /// Returns the view distance of the client, which is the smaller one of
+    /// the server and the client ones.
+    pub fn view_distance(&self) -> i32 {
+        let server_view_distance = self.server.config.view_distance;
+        self.player
+            .as_ref()
+            .map_or(server_view_distance as i32, |player| {
+                player.view_distance(server_view_distance)
+            })
+    }
+
+    /// Returns the player of the client. The player exists since the login
+    /// start, so it's always there in the configuration and play states.
+    pub fn player(&self) -> &Arc<Player> {
+        self.player
+            .as_ref()
+            .expect("the client hasn't started logging in")
     }
 
     /// Updates the protocol state of the client to the provided.
@@ -65,21 +106,40 @@ impl Connection {
         self.state = state;
     }
 
+    /// Queues the packet to the client. Failures are ignored, as the writer
+    /// closes the connection then, which stops serving it.
     pub fn write_packet(&mut self, packet: Box<dyn Packet>) {
-        let mut body_buffer = vec![];
-        body_buffer.extend_from_slice(&packet.id().write().unwrap()); // TODO: no unwrap
-        body_buffer.extend_from_slice(&packet.write().unwrap());
-
-        let mut buffer = vec![];
-        buffer.extend_from_slice(&VarInt(body_buffer.len() as i32).write().unwrap());
-        buffer.extend(body_buffer);
+        let _ = self.writer.write_packet(packet.as_ref());
+    }
 
-        self.stream.write_all(&buffer).unwrap();
+    /// Disconnects the client in the play state, which shows the reason. The
+    /// connection is closed once the packet is written, so it stops being
+    /// served.
+    pub fn disconnect(&mut self, reason: TextComponent) {
+        let _ = self
+            .writer
+            .write_packet(&ClientboundDisconnectPacket { reason });
+        self.writer.close();
     }
 
     /// Performs the handling of the connection in a loop with stack error
-    /// propagation.
+    /// propagation. Chunks the client had loaded are released afterwards, and
+    /// the player leaves the server on the next tick.
     pub fn serve(mut self) -> Result<(), ConnectionError> {
+        let result = self.handle_packets();
+        let sent_chunks = self.chunk_tracker.sent_chunks();
+        self.world.chunks.release(&sent_chunks, &self.writer);
+        if let Some(player) = self.player.take() {
+            self.server.submit(move |server| {
+                if server.remove_player(&player) {
+                    println!("{} has left the server", player.name);
+                }
+            });
+        }
+        result
+    }
+
+    fn handle_packets(&mut self) -> Result<(), ConnectionError> {
         let mut buffer = [0u8; BUFFER_CAPACITY];
         let mut observed_unknown_packets = vec![];
 
@@ -114,7 +174,7 @@ impl Connection {
                 };
 
                 if let Some(handler) = self.handler_registry.get(self.state, id) {
-                    handler(&mut self, &packet);
+                    handler(self, &packet);
                 }
             }
         }
@@ -0,0 +1,602 @@
+use std::{array, ops::Deref, sync::Arc};
+
+use bitflags::bitflags;
+use uuid::Uuid;
+
+use crate::{
+    entity::{Entity, EntityType, equipment::ItemStack, metadata::MetadataValue},
+    player::Location,
+    protocol::text::TextComponent,
+    server::Server,
+    world::block::BlockState,
+};
+
+// Indices of the metadata shared by all display entities.
+/// Ticks until the interpolation of the transformation starts.
+pub const INTERPOLATION_DELAY: u8 = 8;
+pub const TRANSFORMATION_INTERPOLATION_DURATION: u8 = 9;
+pub const TELEPORT_DURATION: u8 = 10;
+pub const TRANSLATION: u8 = 11;
+pub const SCALE: u8 = 12;
+pub const LEFT_ROTATION: u8 = 13;
+pub const RIGHT_ROTATION: u8 = 14;
+pub const BILLBOARD: u8 = 15;
+pub const BRIGHTNESS_OVERRIDE: u8 = 16;
+pub const VIEW_RANGE: u8 = 17;
+pub const SHADOW_RADIUS: u8 = 18;
+pub const SHADOW_STRENGTH: u8 = 19;
+pub const WIDTH: u8 = 20;
+pub const HEIGHT: u8 = 21;
+pub const GLOW_COLOR_OVERRIDE: u8 = 22;
+
+// Indices of the metadata of the specific display entities.
+pub const BLOCK_DISPLAY_BLOCK: u8 = 23;
+pub const ITEM_DISPLAY_ITEM: u8 = 23;
+pub const ITEM_DISPLAY_CONTEXT: u8 = 24;
+pub const TEXT_DISPLAY_TEXT: u8 = 23;
+pub const TEXT_DISPLAY_LINE_WIDTH: u8 = 24;
+pub const TEXT_DISPLAY_BACKGROUND_COLOR: u8 = 25;
+pub const TEXT_DISPLAY_TEXT_OPACITY: u8 = 26;
+pub const TEXT_DISPLAY_FLAGS: u8 = 27;
+
+/// How the display turns to face the player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i8)]
+pub enum Billboard {
+    /// Keeps the rotation of the entity.
+    Fixed = 0,
+    /// Turns around the vertical axis only.
+    Vertical = 1,
+    /// Tilts around the horizontal axis only.
+    Horizontal = 2,
+    /// Always faces the player.
+    Center = 3,
+}
+
+/// How the item of an item display is rendered, the same as the item in the
+/// corresponding place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i8)]
+pub enum ItemDisplayContext {
+    None = 0,
+    ThirdPersonLeftHand = 1,
+    ThirdPersonRightHand = 2,
+    FirstPersonLeftHand = 3,
+    FirstPersonRightHand = 4,
+    Head = 5,
+    Gui = 6,
+    Ground = 7,
+    Fixed = 8,
+}
+
+bitflags! {
+    /// Flags of the text display at the `TEXT_DISPLAY_FLAGS` index.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct TextDisplayFlags: i8 {
+        const SHADOW = 0x01;
+        /// Renders the text through the blocks.
+        const SEE_THROUGH = 0x02;
+        /// Uses the background of the chat instead of the background color.
+        const DEFAULT_BACKGROUND = 0x04;
+        const ALIGN_LEFT = 0x08;
+        const ALIGN_RIGHT = 0x10;
+    }
+}
+
+/// Transformation of the display relative to its position: the translation,
+/// then the left rotation, the scale and the right rotation, which are
+/// applied in the reverse order. Rotations are quaternions in the `[x, y, z,
+/// w]` order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transformation {
+    pub translation: [f32; 3],
+    pub left_rotation: [f32; 4],
+    pub scale: [f32; 3],
+    pub right_rotation: [f32; 4],
+}
+
+impl Default for Transformation {
+    fn default() -> Self {
+        Self {
+            translation: [0.0; 3],
+            left_rotation: [0.0, 0.0, 0.0, 1.0],
+            scale: [1.0; 3],
+            right_rotation: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+}
+
+type Matrix3 = [[f64; 3]; 3];
+
+fn multiply(a: &Matrix3, b: &Matrix3) -> Matrix3 {
+    let mut result = [[0.0; 3]; 3];
+    for (row, result_row) in result.iter_mut().enumerate() {
+        for (column, value) in result_row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[row][k] * b[k][column]).sum();
+        }
+    }
+    result
+}
+
+fn transpose(m: &Matrix3) -> Matrix3 {
+    let mut result = [[0.0; 3]; 3];
+    for (row, result_row) in result.iter_mut().enumerate() {
+        for (column, value) in result_row.iter_mut().enumerate() {
+            *value = m[column][row];
+        }
+    }
+    result
+}
+
+fn determinant(m: &Matrix3) -> f64 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+/// Returns the eigenvalues of the symmetric matrix and the eigenvectors as
+/// the columns of a matrix, with the Jacobi eigenvalue algorithm.
+fn symmetric_eigen(mut m: Matrix3) -> ([f64; 3], Matrix3) {
+    let mut vectors = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    for _ in 0..32 {
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if m[p][q].abs() < 1e-12 {
+                continue;
+            }
+            let theta = (m[q][q] - m[p][p]) / (2.0 * m[p][q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let c = 1.0 / (t * t + 1.0).sqrt();
+            let s = t * c;
+            for row in m.iter_mut().chain(vectors.iter_mut()) {
+                let (a, b) = (row[p], row[q]);
+                row[p] = c * a - s * b;
+                row[q] = s * a + c * b;
+            }
+            let (row_p, row_q) = (m[p], m[q]);
+            m[p] = array::from_fn(|k| c * row_p[k] - s * row_q[k]);
+            m[q] = array::from_fn(|k| s * row_p[k] + c * row_q[k]);
+        }
+    }
+    ([m[0][0], m[1][1], m[2][2]], vectors)
+}
+
+/// Converts the rotation matrix into a quaternion.
+fn to_quaternion(m: &Matrix3) -> [f32; 4] {
+    let trace = m[0][0] + m[1][1] + m[2][2];
+    let (x, y, z, w) = if trace > 0.0 {
+        let s = (trace + 1.0).sqrt() * 2.0;
+        (
+            (m[2][1] - m[1][2]) / s,
+            (m[0][2] - m[2][0]) / s,
+            (m[1][0] - m[0][1]) / s,
+            0.25 * s,
+        )
+    } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+        let s = (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.0;
+        (
+            0.25 * s,
+            (m[0][1] + m[1][0]) / s,
+            (m[0][2] + m[2][0]) / s,
+            (m[2][1] - m[1][2]) / s,
+        )
+    } else if m[1][1] > m[2][2] {
+        let s = (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.0;
+        (
+            (m[0][1] + m[1][0]) / s,
+            0.25 * s,
+            (m[1][2] + m[2][1]) / s,
+            (m[0][2] - m[2][0]) / s,
+        )
+    } else {
+        let s = (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.0;
+        (
+            (m[0][2] + m[2][0]) / s,
+            (m[1][2] + m[2][1]) / s,
+            0.25 * s,
+            (m[1][0] - m[0][1]) / s,
+        )
+    };
+    [x as f32, y as f32, z as f32, w as f32]
+}
+
+/// Converts the quaternion into a rotation matrix.
+fn from_quaternion([x, y, z, w]: [f32; 4]) -> Matrix3 {
+    let (x, y, z, w) = (x as f64, y as f64, z as f64, w as f64);
+    [
+        [
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y - z * w),
+            2.0 * (x * z + y * w),
+        ],
+        [
+            2.0 * (x * y + z * w),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z - x * w),
+        ],
+        [
+            2.0 * (x * z - y * w),
+            2.0 * (y * z + x * w),
+            1.0 - 2.0 * (x * x + y * y),
+        ],
+    ]
+}
+
+impl Transformation {
+    /// Decomposes the affine transformation matrix, where the rows are the
+    /// outer arrays and the translation is the last column. The linear part
+    /// is split into the rotations and the scale with the singular value
+    /// decomposition, the same way as the vanilla server does.
+    pub fn from_matrix(matrix: [[f32; 4]; 4]) -> Self {
+        let mut linear = [[0.0; 3]; 3];
+        for (row, linear_row) in linear.iter_mut().enumerate() {
+            for (column, value) in linear_row.iter_mut().enumerate() {
+                *value = matrix[row][column] as f64;
+            }
+        }
+
+        // A = U * S * V^T, where V and S come from the eigenvectors and the
+        // eigenvalues of A^T * A
+        let (eigenvalues, mut v) = symmetric_eigen(multiply(&transpose(&linear), &linear));
+        if determinant(&v) < 0.0 {
+            for row in &mut v {
+                row[2] = -row[2];
+            }
+        }
+        let mut scale = eigenvalues.map(|value| value.max(0.0).sqrt());
+        let mut u = multiply(&linear, &v);
+        for (column, &value) in scale.iter().enumerate() {
+            if value > 1e-9 {
+                for row in &mut u {
+                    row[column] /= value;
+                }
+            }
+        }
+        // rotations can't mirror, so it's left to the scale
+        if determinant(&u) < 0.0 {
+            for row in &mut u {
+                row[2] = -row[2];
+            }
+            scale[2] = -scale[2];
+        }
+
+        Self {
+            translation: [matrix[0][3], matrix[1][3], matrix[2][3]],
+            left_rotation: to_quaternion(&u),
+            scale: scale.map(|value| value as f32),
+            right_rotation: to_quaternion(&transpose(&v)),
+        }
+    }
+
+    /// Returns the affine transformation matrix, the same as the one
+    /// `from_matrix` takes.
+    pub fn to_matrix(&self) -> [[f32; 4]; 4] {
+        let scale = [
+            [self.scale[0] as f64, 0.0, 0.0],
+            [0.0, self.scale[1] as f64, 0.0],
+            [0.0, 0.0, self.scale[2] as f64],
+        ];
+        let linear = multiply(
+            &multiply(&from_quaternion(self.left_rotation), &scale),
+            &from_quaternion(self.right_rotation),
+        );
+        let mut matrix = [[0.0; 4]; 4];
+        for row in 0..3 {
+            for column in 0..3 {
+                matrix[row][column] = linear[row][column] as f32;
+            }
+            matrix[row][3] = self.translation[row];
+        }
+        matrix[3][3] = 1.0;
+        matrix
+    }
+}
+
+/// A display entity, which renders a block, an item or a text without any
+/// interaction. The metadata shared by all display types is set through it.
+pub struct Display {
+    pub entity: Arc<Entity>,
+}
+
+impl Display {
+    /// Spawns the display of the type at the location, with the metadata set
+    /// by `setup` before the players see it.
+    fn spawn(
+        server: &Server,
+        entity_type: &str,
+        location: Location,
+        setup: impl FnOnce(&Entity),
+    ) -> Self {
+        let entity_type = EntityType::new(entity_type).unwrap();
+        let entity = Entity::new(server.allocate_entity_id(), entity_type, location);
+        setup(&entity);
+        Self {
+            entity: server.add_entity(entity),
+        }
+    }
+
+    /// Sets the transformation, which the clients interpolate to over the
+    /// interpolation duration.
+    pub fn set_transformation(&self, transformation: &Transformation) {
+        let [x, y, z] = transformation.translation;
+        self.entity
+            .set_metadata(TRANSLATION, MetadataValue::Vector3(x, y, z));
+        let [x, y, z] = transformation.scale;
+        self.entity
+            .set_metadata(SCALE, MetadataValue::Vector3(x, y, z));
+        let [x, y, z, w] = transformation.left_rotation;
+        self.entity
+            .set_metadata(LEFT_ROTATION, MetadataValue::Quaternion(x, y, z, w));
+        let [x, y, z, w] = transformation.right_rotation;
+        self.entity
+            .set_metadata(RIGHT_ROTATION, MetadataValue::Quaternion(x, y, z, w));
+
+        // the interpolation restarts only when the delay is sent again
+        self.entity
+            .set_metadata(INTERPOLATION_DELAY, MetadataValue::VarInt(0));
+        self.entity.touch_metadata(INTERPOLATION_DELAY);
+    }
+
+    /// Sets the duration of the interpolation of the transformation, in
+    /// ticks.
+    pub fn set_interpolation_duration(&self, ticks: i32) {
+        self.entity.set_metadata(
+            TRANSFORMATION_INTERPOLATION_DURATION,
+            MetadataValue::VarInt(ticks),
+        );
+    }
+
+    /// Sets the duration of the interpolation of the movement, in ticks.
+    pub fn set_teleport_duration(&self, ticks: i32) {
+        self.entity
+            .set_metadata(TELEPORT_DURATION, MetadataValue::VarInt(ticks));
+    }
+
+    pub fn set_billboard(&self, billboard: Billboard) {
+        self.entity
+            .set_metadata(BILLBOARD, MetadataValue::Byte(billboard as i8));
+    }
+
+    /// Overrides the block and the sky light levels the display is rendered
+    /// with, which are taken from the world otherwise.
+    pub fn set_brightness(&self, brightness: Option<(u8, u8)>) {
+        let value = brightness.map_or(-1, |(block, sky)| (block as i32) << 4 | (sky as i32) << 20);
+        self.entity
+            .set_metadata(BRIGHTNESS_OVERRIDE, MetadataValue::VarInt(value));
+    }
+
+    /// Sets the distance the display is rendered within, as a multiplier of
+    /// 64 blocks.
+    pub fn set_view_range(&self, view_range: f32) {
+        self.entity
+            .set_metadata(VIEW_RANGE, MetadataValue::Float(view_range));
+    }
+
+    pub fn set_shadow(&self, radius: f32, strength: f32) {
+        self.entity
+            .set_metadata(SHADOW_RADIUS, MetadataValue::Float(radius));
+        self.entity
+            .set_metadata(SHADOW_STRENGTH, MetadataValue::Float(strength));
+    }
+
+    /// Sets the color of the outline when the display is glowing, as RGB.
+    pub fn set_glow_color(&self, color: Option<u32>) {
+        let value = color.map_or(-1, |color| color as i32);
+        self.entity
+            .set_metadata(GLOW_COLOR_OVERRIDE, MetadataValue::VarInt(value));
+    }
+}
+
+/// A display that renders a text, i.e. a hologram.
+pub struct TextDisplay(Display);
+
+impl TextDisplay {
+    pub fn spawn(server: &Server, location: Location, text: TextComponent) -> Self {
+        Self(Display::spawn(server, "text_display", location, |entity| {
+            entity.set_metadata(TEXT_DISPLAY_TEXT, MetadataValue::Text(text));
+        }))
+    }
+
+    pub fn set_text(&self, text: TextComponent) {
+        self.entity
+            .set_metadata(TEXT_DISPLAY_TEXT, MetadataValue::Text(text));
+    }
+
+    /// Sets the text that only the player sees instead of the shared one.
+    pub fn set_text_for(&self, viewer: Uuid, text: TextComponent) {
+        self.entity
+            .set_metadata_for(viewer, TEXT_DISPLAY_TEXT, MetadataValue::Text(text));
+    }
+
+    /// Shows the shared text to the player again.
+    pub fn clear_text_for(&self, viewer: &Uuid) {
+        self.entity.clear_metadata_for(viewer);
+    }
+
+    /// Sets the width in pixels the lines are wrapped at.
+    pub fn set_line_width(&self, width: i32) {
+        self.entity
+            .set_metadata(TEXT_DISPLAY_LINE_WIDTH, MetadataValue::VarInt(width));
+    }
+
+    /// Sets the color of the background as ARGB.
+    pub fn set_background_color(&self, color: u32) {
+        self.entity.set_metadata(
+            TEXT_DISPLAY_BACKGROUND_COLOR,
+            MetadataValue::VarInt(color as i32),
+        );
+    }
+
+    /// Sets the opacity of the text, where 255 is opaque.
+    pub fn set_text_opacity(&self, opacity: u8) {
+        self.entity.set_metadata(
+            TEXT_DISPLAY_TEXT_OPACITY,
+            MetadataValue::Byte(opacity as i8),
+        );
+    }
+
+    pub fn set_flags(&self, flags: TextDisplayFlags) {
+        self.entity
+            .set_metadata(TEXT_DISPLAY_FLAGS, MetadataValue::Byte(flags.bits()));
+    }
+}
+
+impl Deref for TextDisplay {
+    type Target = Display;
+
+    fn deref(&self) -> &Display {
+        &self.0
+    }
+}
+
+/// A display that renders an item.
+pub struct ItemDisplay(Display);
+
+impl ItemDisplay {
+    pub fn spawn(server: &Server, location: Location, item: ItemStack) -> Self {
+        Self(Display::spawn(server, "item_display", location, |entity| {
+            entity.set_metadata(ITEM_DISPLAY_ITEM, MetadataValue::Item(item));
+        }))
+    }
+
+    pub fn set_item(&self, item: ItemStack) {
+        self.entity
+            .set_metadata(ITEM_DISPLAY_ITEM, MetadataValue::Item(item));
+    }
+
+    pub fn set_context(&self, context: ItemDisplayContext) {
+        self.entity
+            .set_metadata(ITEM_DISPLAY_CONTEXT, MetadataValue::Byte(context as i8));
+    }
+}
+
+impl Deref for ItemDisplay {
+    type Target = Display;
+
+    fn deref(&self) -> &Display {
+        &self.0
+    }
+}
+
+/// A display that renders a block state.
+pub struct BlockDisplay(Display);
+
+impl BlockDisplay {
+    pub fn spawn(server: &Server, location: Location, block: BlockState) -> Self {
+        Self(Display::spawn(
+            server,
+            "block_display",
+            location,
+            |entity| {
+                entity.set_metadata(BLOCK_DISPLAY_BLOCK, MetadataValue::BlockState(block));
+            },
+        ))
+    }
+
+    pub fn set_block(&self, block: BlockState) {
+        self.entity
+            .set_metadata(BLOCK_DISPLAY_BLOCK, MetadataValue::BlockState(block));
+    }
+}
+
+impl Deref for BlockDisplay {
+    type Target = Display;
+
+    fn deref(&self) -> &Display {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, player::Position, world::Worlds};
+
+    #[test]
+    fn test_text_for_viewer() {
+        let mut config = Config::default();
+        for dimension in config.dimensions.values_mut() {
+            dimension.world_directory = None;
+        }
+        let worlds = Arc::new(Worlds::from_config(&config).unwrap());
+        let server = Server::new(config, worlds, Arc::default());
+        let (steve, alex) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let location = Location {
+            world: server.worlds.spawn_world().clone(),
+            position: Position::default(),
+        };
+        let hologram = TextDisplay::spawn(&server, location, TextComponent::text("Welcome"));
+        hologram.set_billboard(Billboard::Center);
+        hologram.set_text_for(steve, TextComponent::text("Welcome, Steve"));
+
+        let text = |viewer: &Uuid| {
+            hologram
+                .entity
+                .metadata_for(viewer)
+                .get(TEXT_DISPLAY_TEXT)
+                .cloned()
+        };
+        assert_eq!(
+            text(&steve),
+            Some(MetadataValue::Text(TextComponent::text("Welcome, Steve")))
+        );
+        assert_eq!(
+            text(&alex),
+            Some(MetadataValue::Text(TextComponent::text("Welcome")))
+        );
+
+        // the shared text doesn't replace the one of the player
+        hologram.set_text(TextComponent::text("Hello"));
+        let changes = hologram.entity.take_metadata_changes(&[steve, alex]);
+        assert_eq!(
+            changes[&steve].get(TEXT_DISPLAY_TEXT),
+            Some(&MetadataValue::Text(TextComponent::text("Welcome, Steve")))
+        );
+        assert_eq!(
+            changes[&alex].get(TEXT_DISPLAY_TEXT),
+            Some(&MetadataValue::Text(TextComponent::text("Hello")))
+        );
+
+        hologram.clear_text_for(&steve);
+        let changes = hologram.entity.take_metadata_changes(&[steve, alex]);
+        assert_eq!(
+            changes[&steve].get(TEXT_DISPLAY_TEXT),
+            Some(&MetadataValue::Text(TextComponent::text("Hello")))
+        );
+        assert!(!changes.contains_key(&alex));
+    }
+
+    #[test]
+    fn test_transformation_matrix() {
+        // translated, rotated by 90 degrees around Z and scaled
+        let matrix = [
+            [0.0, -3.0, 0.0, 1.0],
+            [2.0, 0.0, 0.0, 2.0],
+            [0.0, 0.0, 4.0, 3.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        let transformation = Transformation::from_matrix(matrix);
+        assert_eq!(transformation.translation, [1.0, 2.0, 3.0]);
+        let mut scale = transformation.scale.map(f32::abs);
+        scale.sort_by(f32::total_cmp);
+        assert!(
+            scale
+                .iter()
+                .zip([2.0, 3.0, 4.0])
+                .all(|(a, b)| (a - b).abs() < 1e-4)
+        );
+
+        let restored = transformation.to_matrix();
+        for (row, restored_row) in matrix.iter().zip(restored) {
+            for (value, restored_value) in row.iter().zip(restored_row) {
+                assert!(
+                    (value - restored_value).abs() < 1e-4,
+                    "{matrix:?} != {restored:?}"
+                );
+            }
+        }
+        assert_eq!(
+            Transformation::from_matrix(Transformation::default().to_matrix()).to_matrix(),
+            Transformation::default().to_matrix()
+        );
+    }
+}
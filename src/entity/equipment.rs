@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+
+use bytes::{Bytes, BytesMut};
+
+use crate::{
+    protocol::{WriteError, Writeable},
+    varint::VarInt,
+};
+
+/// Marks that more equipment entries follow the current one.
+const HAS_NEXT_FLAG: u8 = 0x80;
+
+/// A stack of items without any data components.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ItemStack {
+    /// ID of the item in the item registry.
+    pub item_id: i32,
+    /// The amount of items, where 0 is the empty stack.
+    pub count: i32,
+}
+
+impl ItemStack {
+    pub const EMPTY: ItemStack = ItemStack {
+        item_id: 0,
+        count: 0,
+    };
+
+    pub fn new(item_id: i32, count: i32) -> Self {
+        Self { item_id, count }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count <= 0
+    }
+}
+
+impl Writeable for ItemStack {
+    fn write(&self) -> Result<Bytes, WriteError> {
+        let mut buffer = BytesMut::new();
+        if self.is_empty() {
+            buffer.extend_from_slice(&VarInt(0).write()?);
+            return Ok(buffer.freeze());
+        }
+        buffer.extend_from_slice(&VarInt(self.count).write()?);
+        buffer.extend_from_slice(&VarInt(self.item_id).write()?);
+        // no components are added or removed
+        buffer.extend_from_slice(&VarInt(0).write()?);
+        buffer.extend_from_slice(&VarInt(0).write()?);
+        Ok(buffer.freeze())
+    }
+}
+
+/// Slot of the equipment that is shown on the entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum EquipmentSlot {
+    MainHand = 0,
+    OffHand = 1,
+    Feet = 2,
+    Legs = 3,
+    Chest = 4,
+    Head = 5,
+    /// Armor of the animals, i.e. of horses and wolves.
+    Body = 6,
+    Saddle = 7,
+}
+
+/// Items of the entity by their slots. Slots that aren't there are empty.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Equipment(pub BTreeMap<EquipmentSlot, ItemStack>);
+
+impl Equipment {
+    pub fn get(&self, slot: EquipmentSlot) -> ItemStack {
+        self.0.get(&slot).copied().unwrap_or(ItemStack::EMPTY)
+    }
+
+    /// Puts the item into the slot. Returns `false` if it was already there.
+    pub fn set(&mut self, slot: EquipmentSlot, item: ItemStack) -> bool {
+        if self.get(slot) == item {
+            return false;
+        }
+        self.0.insert(slot, item);
+        true
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Writeable for Equipment {
+    fn write(&self) -> Result<Bytes, WriteError> {
+        let mut buffer = BytesMut::new();
+        let mut entries = self.0.iter().peekable();
+        while let Some((slot, item)) = entries.next() {
+            let mut slot = *slot as u8;
+            if entries.peek().is_some() {
+                slot |= HAS_NEXT_FLAG;
+            }
+            buffer.extend_from_slice(&slot.write()?);
+            buffer.extend_from_slice(&item.write()?);
+        }
+        Ok(buffer.freeze())
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::BTreeMap;
+
+use bitflags::bitflags;
+use bytes::{Bytes, BytesMut};
+use uuid::Uuid;
+
+use crate::{
+    define_varint_enum,
+    entity::equipment::ItemStack,
+    protocol::{WriteError, Writeable, nbt::NbtTag, text::TextComponent},
+    varint::{VarInt, VarLong},
+    world::{block::BlockState, position::BlockPos},
+};
+
+/// Marks the end of the metadata entries.
+const END_OF_METADATA: u8 = 0xFF;
+
+// Indices of the metadata shared by all entities.
+/// Flags of the entity, see `EntityFlags`.
+pub const FLAGS: u8 = 0;
+/// Ticks of the air supply left, as a VarInt.
+pub const AIR_TICKS: u8 = 1;
+/// Custom name of the entity, as an optional text component.
+pub const CUSTOM_NAME: u8 = 2;
+/// Whether the custom name is always shown, as a boolean.
+pub const CUSTOM_NAME_VISIBLE: u8 = 3;
+pub const SILENT: u8 = 4;
+pub const NO_GRAVITY: u8 = 5;
+pub const POSE: u8 = 6;
+/// Ticks the entity has spent in powder snow, as a VarInt.
+pub const TICKS_FROZEN: u8 = 7;
+
+// Indices of the metadata of armor stands.
+/// Flags of the armor stand, see `ArmorStandFlags`.
+pub const ARMOR_STAND_FLAGS: u8 = 15;
+pub const ARMOR_STAND_HEAD_ROTATION: u8 = 16;
+pub const ARMOR_STAND_BODY_ROTATION: u8 = 17;
+pub const ARMOR_STAND_LEFT_ARM_ROTATION: u8 = 18;
+pub const ARMOR_STAND_RIGHT_ARM_ROTATION: u8 = 19;
+pub const ARMOR_STAND_LEFT_LEG_ROTATION: u8 = 20;
+pub const ARMOR_STAND_RIGHT_LEG_ROTATION: u8 = 21;
+
+bitflags! {
+    /// Flags of the entity at the `FLAGS` index.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct EntityFlags: i8 {
+        const ON_FIRE = 0x01;
+        const SNEAKING = 0x02;
+        const SPRINTING = 0x08;
+        const SWIMMING = 0x10;
+        const INVISIBLE = 0x20;
+        const GLOWING = 0x40;
+        const FALL_FLYING = -0x80;
+    }
+}
+
+bitflags! {
+    /// Flags of the armor stand at the `ARMOR_STAND_FLAGS` index.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct ArmorStandFlags: i8 {
+        const SMALL = 0x01;
+        const SHOW_ARMS = 0x04;
+        const NO_BASEPLATE = 0x08;
+        /// Makes the box of the armor stand empty, so it can't be hit.
+        const MARKER = 0x10;
+    }
+}
+
+define_varint_enum!(Direction, {
+    Down = 0,
+    Up = 1,
+    North = 2,
+    South = 3,
+    West = 4,
+    East = 5,
+});
+
+define_varint_enum!(Pose, {
+    Standing = 0,
+    FallFlying = 1,
+    Sleeping = 2,
+    Swimming = 3,
+    SpinAttack = 4,
+    Sneaking = 5,
+    LongJumping = 6,
+    Dying = 7,
+    Croaking = 8,
+    UsingTongue = 9,
+    Sitting = 10,
+    Roaring = 11,
+    Sniffing = 12,
+    Emerging = 13,
+    Digging = 14,
+    Sliding = 15,
+    Shooting = 16,
+    Inhaling = 17,
+});
+
+/// Value of a single metadata entry. Each kind is sent with its own
+/// serializer, which the index of the entry has to expect.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetadataValue {
+    Byte(i8),
+    VarInt(i32),
+    VarLong(i64),
+    Float(f32),
+    String(String),
+    Text(TextComponent),
+    OptionalText(Option<TextComponent>),
+    Item(ItemStack),
+    Boolean(bool),
+    /// Rotation around the X, Y and Z axes in degrees.
+    Rotations(f32, f32, f32),
+    BlockPos(BlockPos),
+    OptionalBlockPos(Option<BlockPos>),
+    Direction(Direction),
+    /// UUID of an entity, i.e. the owner of a tamed animal.
+    OptionalUuid(Option<Uuid>),
+    BlockState(BlockState),
+    /// Block state, where `None` is air.
+    OptionalBlockState(Option<BlockState>),
+    Nbt(NbtTag),
+    OptionalVarInt(Option<i32>),
+    Pose(Pose),
+    Vector3(f32, f32, f32),
+    /// Rotation as the X, Y, Z and W components of a quaternion.
+    Quaternion(f32, f32, f32, f32),
+}
+
+impl MetadataValue {
+    /// Returns the ID of the serializer of the value.
+    pub fn serializer_id(&self) -> i32 {
+        match self {
+            MetadataValue::Byte(_) => 0,
+            MetadataValue::VarInt(_) => 1,
+            MetadataValue::VarLong(_) => 2,
+            MetadataValue::Float(_) => 3,
+            MetadataValue::String(_) => 4,
+            MetadataValue::Text(_) => 5,
+            MetadataValue::OptionalText(_) => 6,
+            MetadataValue::Item(_) => 7,
+            MetadataValue::Boolean(_) => 8,
+            MetadataValue::Rotations(..) => 9,
+            MetadataValue::BlockPos(_) => 10,
+            MetadataValue::OptionalBlockPos(_) => 11,
+            MetadataValue::Direction(_) => 12,
+            MetadataValue::OptionalUuid(_) => 13,
+            MetadataValue::BlockState(_) => 14,
+            MetadataValue::OptionalBlockState(_) => 15,
+            MetadataValue::Nbt(_) => 16,
+            MetadataValue::OptionalVarInt(_) => 20,
+            MetadataValue::Pose(_) => 21,
+            MetadataValue::Vector3(..) => 33,
+            MetadataValue::Quaternion(..) => 34,
+        }
+    }
+}
+
+/// Writes the optional value with a boolean prefix.
+fn write_optional<T: Writeable>(value: &Option<T>) -> Result<Bytes, WriteError> {
+    let mut buffer = BytesMut::new();
+    buffer.extend_from_slice(&value.is_some().write()?);
+    buffer.extend_from_slice(&value.write()?);
+    Ok(buffer.freeze())
+}
+
+fn write_floats(values: &[f32]) -> Result<Bytes, WriteError> {
+    let mut buffer = BytesMut::with_capacity(values.len() * 4);
+    for value in values {
+        buffer.extend_from_slice(&value.write()?);
+    }
+    Ok(buffer.freeze())
+}
+
+impl Writeable for MetadataValue {
+    fn write(&self) -> Result<Bytes, WriteError> {
+        match self {
+            MetadataValue::Byte(value) => value.write(),
+            MetadataValue::VarInt(value) => VarInt(*value).write(),
+            MetadataValue::VarLong(value) => VarLong(*value).write(),
+            MetadataValue::Float(value) => value.write(),
+            MetadataValue::String(value) => value.write(),
+            MetadataValue::Text(value) => value.write(),
+            MetadataValue::OptionalText(value) => write_optional(value),
+            MetadataValue::Item(value) => value.write(),
+            MetadataValue::Boolean(value) => value.write(),
+            MetadataValue::Rotations(x, y, z) | MetadataValue::Vector3(x, y, z) => {
+                write_floats(&[*x, *y, *z])
+            }
+            MetadataValue::BlockPos(value) => value.write(),
+            MetadataValue::OptionalBlockPos(value) => write_optional(value),
+            MetadataValue::Direction(value) => value.write(),
+            MetadataValue::OptionalUuid(value) => write_optional(value),
+            MetadataValue::BlockState(value) => value.write(),
+            // air is the absent value, which is the state 0
+            MetadataValue::OptionalBlockState(value) => value.unwrap_or_default().write(),
+            MetadataValue::Nbt(value) => Ok(Bytes::from(value.to_nameless_bytes())),
+            // 0 is the absent value, the others are shifted by one
+            MetadataValue::OptionalVarInt(value) => {
+                VarInt(value.map_or(0, |value| value + 1)).write()
+            }
+            MetadataValue::Pose(value) => value.write(),
+            MetadataValue::Quaternion(x, y, z, w) => write_floats(&[*x, *y, *z, *w]),
+        }
+    }
+}
+
+/// Metadata of an entity: the values by their indices, which depend on the
+/// type of the entity.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Metadata(pub BTreeMap<u8, MetadataValue>);
+
+impl Metadata {
+    pub fn get(&self, index: u8) -> Option<&MetadataValue> {
+        self.0.get(&index)
+    }
+
+    /// Sets the value at the index. Returns `false` if it was already set.
+    pub fn set(&mut self, index: u8, value: MetadataValue) -> bool {
+        if self.0.get(&index) == Some(&value) {
+            return false;
+        }
+        self.0.insert(index, value);
+        true
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Writeable for Metadata {
+    fn write(&self) -> Result<Bytes, WriteError> {
+        let mut buffer = BytesMut::new();
+        for (index, value) in &self.0 {
+            buffer.extend_from_slice(&index.write()?);
+            buffer.extend_from_slice(&VarInt(value.serializer_id()).write()?);
+            buffer.extend_from_slice(&value.write()?);
+        }
+        buffer.extend_from_slice(&END_OF_METADATA.write()?);
+        Ok(buffer.freeze())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata() {
+        let mut metadata = Metadata::default();
+        assert!(metadata.set(FLAGS, MetadataValue::Byte(EntityFlags::GLOWING.bits())));
+        assert!(!metadata.set(FLAGS, MetadataValue::Byte(EntityFlags::GLOWING.bits())));
+        metadata.set(CUSTOM_NAME_VISIBLE, MetadataValue::Boolean(true));
+        metadata.set(POSE, MetadataValue::Pose(Pose::Sneaking));
+        metadata.set(AIR_TICKS, MetadataValue::OptionalVarInt(None));
+
+        assert_eq!(
+            metadata.write().unwrap().as_ref(),
+            [0, 0, 0x40, 1, 20, 0, 3, 8, 1, 6, 21, 5, 0xFF]
+        );
+    }
+}
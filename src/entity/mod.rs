@@ -0,0 +1,433 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    hash::{BuildHasher, RandomState},
+    sync::{Arc, LazyLock, Mutex, MutexGuard},
+};
+
+use thiserror::Error;
+use uuid::{Builder, Uuid};
+
+use crate::{
+    entity::{
+        equipment::{Equipment, EquipmentSlot, ItemStack},
+        metadata::{Metadata, MetadataValue},
+    },
+    player::{Location, Position},
+    world::World,
+};
+
+pub mod display;
+pub mod equipment;
+pub mod metadata;
+
+/// The global entity type registry, generated from the vanilla
+/// `registries.json` report (see `generator.py`).
+pub static ENTITY_TYPES: LazyLock<EntityTypeRegistry> = LazyLock::new(|| {
+    let raw_entity_types_json = include_str!("../../entity_types.json");
+    EntityTypeRegistry::from_json(raw_entity_types_json).unwrap()
+});
+
+/// Namespace that is used for the entity type names that were provided
+/// without one.
+const DEFAULT_NAMESPACE: &str = "minecraft";
+
+/// Errors that can occur while working with entities.
+#[derive(Debug, Error)]
+pub enum EntityError {
+    /// Indicates that there is no entity type with the provided name.
+    #[error("unknown entity type: {0}")]
+    UnknownEntityType(String),
+    /// Indicates that the provided registry JSON couldn't be parsed.
+    #[error("failed to parse the entity type registry: {0}")]
+    JsonError(#[from] serde_json::Error),
+}
+
+/// Registry of the entity types, where the ID of each type is its index.
+pub struct EntityTypeRegistry {
+    names: Vec<String>,
+    by_name: HashMap<String, i32>,
+}
+
+impl EntityTypeRegistry {
+    /// Parses the registry from the JSON generated by `generator.py`.
+    pub fn from_json(json: &str) -> Result<Self, EntityError> {
+        let names: Vec<String> = serde_json::from_str(json)?;
+        let by_name = names
+            .iter()
+            .enumerate()
+            .map(|(id, name)| (name.clone(), id as i32))
+            .collect();
+        Ok(Self { names, by_name })
+    }
+
+    /// Returns the ID of the entity type, with the name with or without the
+    /// namespace.
+    pub fn id(&self, name: &str) -> Option<i32> {
+        if name.contains(':') {
+            return self.by_name.get(name).copied();
+        }
+        self.by_name
+            .get(&format!("{DEFAULT_NAMESPACE}:{name}"))
+            .copied()
+    }
+
+    pub fn name(&self, id: i32) -> Option<&str> {
+        self.names
+            .get(usize::try_from(id).ok()?)
+            .map(String::as_str)
+    }
+}
+
+/// Type of an entity, which is the ID of it in the registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntityType(i32);
+
+impl EntityType {
+    /// Finds the entity type by its name, i.e. `minecraft:armor_stand`.
+    pub fn new(name: &str) -> Result<Self, EntityError> {
+        ENTITY_TYPES
+            .id(name)
+            .map(Self)
+            .ok_or_else(|| EntityError::UnknownEntityType(name.to_string()))
+    }
+
+    pub fn id(self) -> i32 {
+        self.0
+    }
+
+    pub fn name(self) -> &'static str {
+        ENTITY_TYPES.name(self.0).unwrap()
+    }
+}
+
+/// Returns a random (version 4) UUID. The keys of `RandomState` are random
+/// for each instance, which is enough for the UUIDs of the entities.
+fn random_uuid() -> Uuid {
+    let state = RandomState::new();
+    let bits = (state.hash_one(0u8) as u128) << 64 | state.hash_one(1u8) as u128;
+    Builder::from_random_bytes(bits.to_be_bytes()).into_uuid()
+}
+
+/// Velocity of an entity in blocks per tick.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Velocity {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Velocity {
+    /// Returns the velocity in the units of the protocol, which are 1/8000
+    /// of a block per tick. The client caps it to 3.9 blocks per tick.
+    pub fn to_protocol(self) -> (i16, i16, i16) {
+        let convert = |value: f64| (value.clamp(-3.9, 3.9) * 8000.0) as i16;
+        (convert(self.x), convert(self.y), convert(self.z))
+    }
+}
+
+/// State of the entity that changes while it lives.
+struct EntityState {
+    world: Arc<World>,
+    position: Position,
+    velocity: Velocity,
+    on_ground: bool,
+    /// Extra data of the spawn packet, which depends on the type, i.e. the
+    /// block state of a falling block.
+    data: i32,
+    metadata: Metadata,
+    /// Metadata entries that replace the ones of `metadata` for specific
+    /// players, by their UUIDs.
+    viewer_metadata: HashMap<Uuid, Metadata>,
+    equipment: Equipment,
+    /// Whether the players see the entity, unless they're in `exceptions`.
+    visible_by_default: bool,
+    /// Players for which `visible_by_default` is inverted.
+    exceptions: HashSet<Uuid>,
+    /// The position the viewers have last been sent.
+    synced_position: Option<Position>,
+    velocity_changed: bool,
+    /// Indices of the metadata entries that the viewers haven't been sent.
+    changed_metadata: BTreeSet<u8>,
+    /// Indices of the entries of `viewer_metadata` that the players haven't
+    /// been sent.
+    changed_viewer_metadata: HashMap<Uuid, BTreeSet<u8>>,
+    changed_equipment: BTreeSet<EquipmentSlot>,
+}
+
+impl EntityState {
+    /// Returns the metadata entry the player sees.
+    fn metadata_value(&self, viewer: &Uuid, index: u8) -> Option<&MetadataValue> {
+        self.viewer_metadata
+            .get(viewer)
+            .and_then(|metadata| metadata.get(index))
+            .or_else(|| self.metadata.get(index))
+    }
+}
+
+/// An entity other than a player, i.e. an armor stand or a mob without AI.
+/// It's spawned for the players nearby the same way as the other players.
+pub struct Entity {
+    /// ID of the entity, unique within the server.
+    pub entity_id: i32,
+    pub uuid: Uuid,
+    pub entity_type: EntityType,
+    state: Mutex<EntityState>,
+}
+
+impl Entity {
+    pub fn new(entity_id: i32, entity_type: EntityType, location: Location) -> Self {
+        Self {
+            entity_id,
+            uuid: random_uuid(),
+            entity_type,
+            state: Mutex::new(EntityState {
+                world: location.world,
+                position: location.position,
+                velocity: Velocity::default(),
+                on_ground: false,
+                data: 0,
+                metadata: Metadata::default(),
+                viewer_metadata: HashMap::new(),
+                equipment: Equipment::default(),
+                visible_by_default: true,
+                exceptions: HashSet::new(),
+                synced_position: None,
+                velocity_changed: false,
+                changed_metadata: BTreeSet::new(),
+                changed_viewer_metadata: HashMap::new(),
+                changed_equipment: BTreeSet::new(),
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, EntityState> {
+        self.state.lock().unwrap()
+    }
+
+    pub fn world(&self) -> Arc<World> {
+        self.lock().world.clone()
+    }
+
+    pub fn position(&self) -> Position {
+        self.lock().position
+    }
+
+    /// Moves the entity to the position in its world.
+    pub fn set_position(&self, position: Position) {
+        self.lock().position = position;
+    }
+
+    /// Moves the entity to the location, possibly in another world.
+    pub fn set_location(&self, location: Location) {
+        let mut state = self.lock();
+        state.world = location.world;
+        state.position = location.position;
+    }
+
+    pub fn is_on_ground(&self) -> bool {
+        self.lock().on_ground
+    }
+
+    pub fn set_on_ground(&self, on_ground: bool) {
+        self.lock().on_ground = on_ground;
+    }
+
+    pub fn velocity(&self) -> Velocity {
+        self.lock().velocity
+    }
+
+    pub fn set_velocity(&self, velocity: Velocity) {
+        let mut state = self.lock();
+        state.velocity_changed |= state.velocity != velocity;
+        state.velocity = velocity;
+    }
+
+    pub fn data(&self) -> i32 {
+        self.lock().data
+    }
+
+    /// Sets the extra data of the spawn packet. It's only sent to the
+    /// viewers that spawn the entity afterwards.
+    pub fn set_data(&self, data: i32) {
+        self.lock().data = data;
+    }
+
+    /// Returns all metadata of the entity.
+    pub fn metadata(&self) -> Metadata {
+        self.lock().metadata.clone()
+    }
+
+    /// Returns all metadata of the entity the player sees, with its own
+    /// entries in place of the shared ones.
+    pub fn metadata_for(&self, viewer: &Uuid) -> Metadata {
+        let state = self.lock();
+        let mut metadata = state.metadata.clone();
+        if let Some(viewer_metadata) = state.viewer_metadata.get(viewer) {
+            metadata.0.extend(viewer_metadata.0.clone());
+        }
+        metadata
+    }
+
+    /// Sets the metadata entry, which is sent to the viewers on the next
+    /// tick. Players with their own entry at the index keep it.
+    pub fn set_metadata(&self, index: u8, value: MetadataValue) {
+        let mut state = self.lock();
+        if state.metadata.set(index, value) {
+            state.changed_metadata.insert(index);
+        }
+    }
+
+    /// Sets the metadata entry that only the player sees, i.e. the text of a
+    /// hologram with its name.
+    pub fn set_metadata_for(&self, viewer: Uuid, index: u8, value: MetadataValue) {
+        let mut state = self.lock();
+        if state
+            .viewer_metadata
+            .entry(viewer)
+            .or_default()
+            .set(index, value)
+        {
+            state
+                .changed_viewer_metadata
+                .entry(viewer)
+                .or_default()
+                .insert(index);
+        }
+    }
+
+    /// Removes the metadata entries of the player, so it sees the shared
+    /// ones again.
+    pub fn clear_metadata_for(&self, viewer: &Uuid) {
+        let mut state = self.lock();
+        let Some(metadata) = state.viewer_metadata.remove(viewer) else {
+            return;
+        };
+        state
+            .changed_viewer_metadata
+            .entry(*viewer)
+            .or_default()
+            .extend(metadata.0.into_keys());
+    }
+
+    /// Marks the metadata entry as changed, so it's sent again even if the
+    /// value is the same, i.e. to restart the interpolation of a display.
+    pub fn touch_metadata(&self, index: u8) {
+        let mut state = self.lock();
+        if state.metadata.get(index).is_some() {
+            state.changed_metadata.insert(index);
+        }
+    }
+
+    /// Returns all equipment of the entity.
+    pub fn equipment(&self) -> Equipment {
+        self.lock().equipment.clone()
+    }
+
+    /// Puts the item into the slot, which is sent to the viewers on the next
+    /// tick.
+    pub fn set_equipment(&self, slot: EquipmentSlot, item: ItemStack) {
+        let mut state = self.lock();
+        if state.equipment.set(slot, item) {
+            state.changed_equipment.insert(slot);
+        }
+    }
+
+    /// Returns whether the player may see the entity.
+    pub fn is_visible_to(&self, viewer: &Uuid) -> bool {
+        let state = self.lock();
+        state.visible_by_default != state.exceptions.contains(viewer)
+    }
+
+    /// Sets whether the players see the entity, which resets the ones shown
+    /// or hidden individually.
+    pub fn set_visible_by_default(&self, visible: bool) {
+        let mut state = self.lock();
+        state.visible_by_default = visible;
+        state.exceptions.clear();
+    }
+
+    /// Shows the entity to the player from the next tick.
+    pub fn show_to(&self, viewer: Uuid) {
+        let mut state = self.lock();
+        if state.visible_by_default {
+            state.exceptions.remove(&viewer);
+        } else {
+            state.exceptions.insert(viewer);
+        }
+    }
+
+    /// Hides the entity from the player from the next tick.
+    pub fn hide_from(&self, viewer: Uuid) {
+        let mut state = self.lock();
+        if state.visible_by_default {
+            state.exceptions.insert(viewer);
+        } else {
+            state.exceptions.remove(&viewer);
+        }
+    }
+
+    /// Returns the position the viewers have last been sent, if any.
+    pub fn synced_position(&self) -> Option<Position> {
+        self.lock().synced_position
+    }
+
+    /// Marks the current position as sent to the viewers, returning the one
+    /// sent before.
+    pub fn sync_position(&self) -> Option<Position> {
+        let mut state = self.lock();
+        let current = state.position;
+        state.synced_position.replace(current)
+    }
+
+    /// Returns the velocity if it has changed since the last call.
+    pub fn take_velocity_change(&self) -> Option<Velocity> {
+        let mut state = self.lock();
+        if !state.velocity_changed {
+            return None;
+        }
+        state.velocity_changed = false;
+        Some(state.velocity)
+    }
+
+    /// Returns the metadata entries that have changed since the last call
+    /// for each of the viewers. Entries of the players that aren't viewers
+    /// are dropped, as they're sent the whole metadata when they spawn it.
+    pub fn take_metadata_changes(&self, viewers: &[Uuid]) -> HashMap<Uuid, Metadata> {
+        let mut state = self.lock();
+        let changed = std::mem::take(&mut state.changed_metadata);
+        let mut changed_viewer_metadata = std::mem::take(&mut state.changed_viewer_metadata);
+        viewers
+            .iter()
+            .filter_map(|viewer| {
+                let overridden = state.viewer_metadata.get(viewer);
+                let mut indices: BTreeSet<u8> = changed
+                    .iter()
+                    .filter(|&&index| {
+                        overridden.is_none_or(|metadata| metadata.get(index).is_none())
+                    })
+                    .copied()
+                    .collect();
+                indices.extend(changed_viewer_metadata.remove(viewer).unwrap_or_default());
+                let entries: BTreeMap<u8, MetadataValue> = indices
+                    .into_iter()
+                    .filter_map(|index| Some((index, state.metadata_value(viewer, index)?.clone())))
+                    .collect();
+                (!entries.is_empty()).then_some((*viewer, Metadata(entries)))
+            })
+            .collect()
+    }
+
+    /// Returns the equipment slots that have changed since the last call.
+    pub fn take_equipment_changes(&self) -> Option<Equipment> {
+        let mut state = self.lock();
+        if state.changed_equipment.is_empty() {
+            return None;
+        }
+        let changed = std::mem::take(&mut state.changed_equipment);
+        let slots = changed
+            .into_iter()
+            .map(|slot| (slot, state.equipment.get(slot)))
+            .collect();
+        Some(Equipment(slots))
+    }
+}
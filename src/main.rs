@@ -1,21 +1,93 @@
-use std::{net::TcpListener, sync::Arc};
+use std::{
+    net::TcpListener,
+    process,
+    sync::{
+        Arc,
+        mpsc::{self, Receiver},
+    },
+    thread,
+};
 
 use crate::protocol::{
     handlers::{self},
     packets::{self, Packet},
     registry::{HandlersRegistry, PacketsRegistry},
 };
+use crate::{
+    config::{CONFIG_PATH, Config},
+    server::Server,
+    tick::{Scheduler, TICKS_PER_SECOND, TickLoop},
+    world::{Chunk, World, Worlds},
+};
 
 use crate::connection::Connection;
 
+pub mod chat;
+pub mod config;
 pub mod connection;
+pub mod entity;
 pub mod network;
+pub mod player;
 pub mod protocol;
 pub mod registry;
+pub mod server;
+pub mod tick;
 pub mod varint;
 pub mod world;
 
 fn main() {
+    let config = Config::load(CONFIG_PATH).unwrap();
+    // custom dimension types have to be known before the worlds are created
+    registry::set_custom_dimension_types(config.dimension_types.clone());
+    let worlds = Arc::new(Worlds::from_config(&config).unwrap());
+
+    let scheduler = Arc::new(Scheduler::default());
+    // only the changed chunks are copied on the tick, and they're saved on a
+    // separate thread: the ones no one views anymore every second, and all
+    // of them with the autosave
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || save_chunks(receiver));
+    let evicted_worlds = worlds.clone();
+    let evicted_sender = sender.clone();
+    scheduler.run_every(TICKS_PER_SECOND, TICKS_PER_SECOND, move || {
+        for world in evicted_worlds.iter() {
+            let chunks = world.take_evicted_chunks();
+            if !chunks.is_empty() {
+                let _ = evicted_sender.send(SaveJob {
+                    world: world.clone(),
+                    chunks,
+                    is_autosave: false,
+                });
+            }
+        }
+    });
+    if config.autosave_interval > 0 {
+        let worlds = worlds.clone();
+        let interval = config.autosave_interval * TICKS_PER_SECOND;
+        scheduler.run_every(interval, interval, move || {
+            for world in worlds.iter() {
+                let chunks = world.take_dirty_chunks();
+                if !chunks.is_empty() {
+                    let _ = sender.send(SaveJob {
+                        world: world.clone(),
+                        chunks,
+                        is_autosave: true,
+                    });
+                }
+            }
+        });
+    }
+    let server = Arc::new(Server::new(config, worlds.clone(), scheduler));
+    TickLoop::new(server.clone()).spawn();
+
+    let shutdown_worlds = worlds.clone();
+    ctrlc::set_handler(move || {
+        println!("Saving the worlds before shutting down");
+        save_worlds(&shutdown_worlds);
+        process::exit(0);
+    })
+    .unwrap();
+
     let listener = TcpListener::bind("0.0.0.0:25565").unwrap();
 
     let mut registry = PacketsRegistry::default();
@@ -45,8 +117,48 @@ fn main() {
         };
 
         println!("New client from {addr}");
-        Connection::new(stream, packet_registry.clone(), handler_registry.clone())
-            .serve()
-            .unwrap();
+        let server = server.clone();
+        let packet_registry = packet_registry.clone();
+        let handler_registry = handler_registry.clone();
+        thread::spawn(move || {
+            let result = Connection::new(stream, server, packet_registry, handler_registry)
+                .and_then(Connection::serve);
+            if let Err(e) = result {
+                eprintln!("Connection with {addr} has failed: {e}");
+            }
+        });
+    }
+}
+
+/// Chunks copied from a world on the tick, which are saved on the save
+/// thread.
+struct SaveJob {
+    world: Arc<World>,
+    chunks: Vec<Chunk>,
+    /// Whether the chunks were copied by the autosave, whose saves are
+    /// reported. The chunks dropped from the worlds are saved quietly.
+    is_autosave: bool,
+}
+
+/// Saves the chunks copied from the worlds until the senders are dropped,
+/// reporting the errors.
+fn save_chunks(receiver: Receiver<SaveJob>) {
+    for job in receiver {
+        match job.world.save_chunks(job.chunks) {
+            Ok(saved) if job.is_autosave => println!("Saved {saved} chunks of {}", job.world.name),
+            Ok(_) => {}
+            Err(e) => eprintln!("Failed to save {}: {e}", job.world.name),
+        }
+    }
+}
+
+/// Saves the changed chunks of all worlds, reporting the errors.
+fn save_worlds(worlds: &Worlds) {
+    for world in worlds.iter() {
+        match world.save() {
+            Ok(0) => {}
+            Ok(saved) => println!("Saved {saved} chunks of {}", world.name),
+            Err(e) => eprintln!("Failed to save {}: {e}", world.name),
+        }
     }
 }
@@ -1,8 +1,20 @@
+use std::{
+    io,
+    io::Write,
+    net::{Shutdown, TcpStream},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Sender},
+    },
+    thread,
+};
+
 use bytes::{Buf, BytesMut};
 use thiserror::Error;
 
 use crate::{
-    protocol::{ReadError, Readable},
+    protocol::{ReadError, Readable, Writeable, packets::Packet},
     varint::{VarInt, VarIntError},
 };
 
@@ -88,6 +100,142 @@ impl PacketReader {
     }
 }
 
+/// The largest amount of bytes queued for a single client. Clients that
+/// don't receive the packets fast enough are disconnected once it's exceeded.
+pub const MAX_QUEUED_BYTES: usize = 32 * 1024 * 1024;
+
+/// Messages to the thread writing the packets of a client.
+enum WriterMessage {
+    /// Packet with its length and ID, ready to be written.
+    Packet(Vec<u8>),
+    /// Request to flush the stream, answered once all packets queued before
+    /// it are written.
+    Flush(Sender<()>),
+}
+
+/// Writer of the packets to a single client, which can be shared between
+/// threads, i.e. to broadcast the packets to the clients that aren't handled
+/// by the current thread. Packets are queued and written by a separate
+/// thread in the order they were queued, so slow clients don't block the
+/// others.
+pub struct PacketWriter {
+    /// Queue of the writing thread. Dropped once the writer is closed.
+    queue: Mutex<Option<Sender<WriterMessage>>>,
+    /// Amount of the queued bytes that aren't written yet.
+    queued_bytes: Arc<AtomicUsize>,
+    /// Closes the underlying connection immediately.
+    shutdown: Arc<dyn Fn() + Send + Sync>,
+}
+
+impl PacketWriter {
+    /// Creates a new `PacketWriter` over the provided stream.
+    pub fn new(stream: impl Write + Send + 'static) -> Self {
+        Self::with_shutdown(stream, || {})
+    }
+
+    /// Creates a new `PacketWriter` over the client's `TcpStream`, which is
+    /// shut down once the writer is closed.
+    pub fn from_tcp_stream(stream: &TcpStream) -> io::Result<Self> {
+        let connection = stream.try_clone()?;
+        Ok(Self::with_shutdown(stream.try_clone()?, move || {
+            let _ = connection.shutdown(Shutdown::Both);
+        }))
+    }
+
+    fn with_shutdown(
+        mut stream: impl Write + Send + 'static,
+        shutdown: impl Fn() + Send + Sync + 'static,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let queued_bytes = Arc::new(AtomicUsize::new(0));
+        let shutdown: Arc<dyn Fn() + Send + Sync> = Arc::new(shutdown);
+
+        let thread_queued_bytes = queued_bytes.clone();
+        let thread_shutdown = shutdown.clone();
+        thread::spawn(move || {
+            for message in receiver {
+                match message {
+                    WriterMessage::Packet(packet) => {
+                        let result = stream.write_all(&packet);
+                        thread_queued_bytes.fetch_sub(packet.len(), Ordering::SeqCst);
+                        if result.is_err() {
+                            break;
+                        }
+                    }
+                    WriterMessage::Flush(done) => {
+                        let _ = stream.flush();
+                        let _ = done.send(());
+                    }
+                }
+            }
+            // the writer was closed or the client can't be written to anymore
+            thread_shutdown();
+        });
+
+        Self {
+            queue: Mutex::new(Some(sender)),
+            queued_bytes,
+            shutdown,
+        }
+    }
+
+    pub fn write_packet(&self, packet: &dyn Packet) -> io::Result<()> {
+        let body = packet.write().map_err(io::Error::other)?;
+        self.write_raw_packet(packet.id(), &body)
+    }
+
+    /// Queues the packet with the provided ID and already encoded body, i.e.
+    /// the one that is shared between multiple clients. Fails if the writer
+    /// is closed, or if too many bytes are queued, which closes it.
+    pub fn write_raw_packet(&self, id: VarInt, body: &[u8]) -> io::Result<()> {
+        let id = id.write().unwrap();
+        let length = VarInt((id.len() + body.len()) as i32).write().unwrap();
+
+        let mut buffer = Vec::with_capacity(length.len() + id.len() + body.len());
+        buffer.extend_from_slice(&length);
+        buffer.extend_from_slice(&id);
+        buffer.extend_from_slice(body);
+
+        let queued_bytes = self.queued_bytes.fetch_add(buffer.len(), Ordering::SeqCst);
+        if queued_bytes + buffer.len() > MAX_QUEUED_BYTES {
+            self.queue.lock().unwrap().take();
+            (self.shutdown)();
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "the client doesn't receive the packets",
+            ));
+        }
+        self.send(WriterMessage::Packet(buffer))
+    }
+
+    /// Waits until all queued packets are written and the stream is flushed.
+    pub fn flush(&self) -> io::Result<()> {
+        let (sender, receiver) = mpsc::channel();
+        self.send(WriterMessage::Flush(sender))?;
+        receiver.recv().map_err(|_| closed_error())
+    }
+
+    /// Closes the writer. Packets that are already queued are still written,
+    /// and the connection is closed afterwards.
+    pub fn close(&self) {
+        self.queue.lock().unwrap().take();
+    }
+
+    fn send(&self, message: WriterMessage) -> io::Result<()> {
+        self.queue
+            .lock()
+            .unwrap()
+            .as_ref()
+            .ok_or_else(closed_error)?
+            .send(message)
+            .map_err(|_| closed_error())
+    }
+}
+
+fn closed_error() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "the writer is closed")
+}
+
 pub struct BufferReader<'a> {
     buffer: &'a [u8],
     offset: usize,
@@ -0,0 +1,618 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use rsa::RsaPublicKey;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    chat::{
+        ChatError, ChatSessionData, ChatState, LAST_SEEN_BYTES, LastSeenMessages,
+        PlayerChatMessage, current_millis,
+    },
+    network::PacketWriter,
+    protocol::{
+        PrefixedArray, PrefixedOption,
+        packets::{
+            Packet,
+            configuration::{ClientInformationChatMode, ServerboundClientInformationPacket},
+            login::Property,
+            play::{
+                ClientboundDisconnectPacket, ClientboundPlayerChatPacket,
+                ClientboundSystemChatPacket, PlayerInfoEntry, TeleportFlags,
+            },
+        },
+        text::TextComponent,
+    },
+    varint::VarInt,
+    world::World,
+};
+
+/// Width of the box of the player.
+pub const PLAYER_WIDTH: f64 = 0.6;
+
+/// Height of the box of the player, when it's standing.
+pub const PLAYER_HEIGHT: f64 = 1.8;
+
+/// The largest squared distance the player may move by with a single packet,
+/// the same as vanilla allows.
+const MAX_MOVE_DISTANCE_SQUARED: f64 = 100.0;
+
+/// The largest horizontal coordinate the player may move to.
+const MAX_COORDINATE: f64 = 3.0e7;
+
+/// The longest time the client may take to answer a keep-alive before it's
+/// disconnected, the same as vanilla allows.
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Reasons the movement of the player is rejected for.
+#[derive(Debug, Error)]
+pub enum InvalidMove {
+    /// Indicates that the coordinates aren't finite or are out of the world.
+    #[error("has sent invalid coordinates")]
+    InvalidCoordinates,
+    /// Indicates that the player has moved further than it could.
+    #[error("has moved too quickly ({0:.2} blocks)")]
+    TooFast(f64),
+    /// Indicates that the player has moved into a solid block.
+    #[error("has moved into a solid block")]
+    IntoBlock,
+}
+
+/// Game mode of a player, with the values used by the protocol.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u8)]
+pub enum GameMode {
+    #[default]
+    Survival = 0,
+    Creative = 1,
+    Adventure = 2,
+    Spectator = 3,
+}
+
+/// Position and rotation of a player in its world.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub x: f64,
+    /// Y coordinate of the feet.
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl Position {
+    /// Returns the position after the teleport to the other one, where the
+    /// fields marked by the flags are offsets from the ones of this position.
+    pub fn teleported(self, to: Position, flags: TeleportFlags) -> Position {
+        let apply = |flag, current, target| {
+            if flags.contains(flag) {
+                current + target
+            } else {
+                target
+            }
+        };
+        Position {
+            x: apply(TeleportFlags::X, self.x, to.x),
+            y: apply(TeleportFlags::Y, self.y, to.y),
+            z: apply(TeleportFlags::Z, self.z, to.z),
+            yaw: apply(TeleportFlags::YAW, self.yaw as f64, to.yaw as f64) as f32,
+            pitch: apply(TeleportFlags::PITCH, self.pitch as f64, to.pitch as f64) as f32,
+        }
+    }
+}
+
+/// Position in a specific world, i.e. the destination of a teleport.
+#[derive(Clone)]
+pub struct Location {
+    pub world: Arc<World>,
+    pub position: Position,
+}
+
+/// State of the player that changes while it plays.
+struct PlayerState {
+    /// The world the player is in.
+    world: Arc<World>,
+    position: Position,
+    on_ground: bool,
+    game_mode: GameMode,
+    /// Settings of the client, if they were received.
+    client_information: Option<ServerboundClientInformationPacket>,
+    /// ID of the teleport the client hasn't confirmed yet.
+    pending_teleport: Option<i32>,
+    next_teleport_id: i32,
+    /// Latency of the client in milliseconds, measured with the keep-alives.
+    latency: i32,
+    /// ID of the keep-alive the client hasn't answered yet, with the time it
+    /// was sent at.
+    pending_keep_alive: Option<(i64, Instant)>,
+    /// Entity IDs of the players that were spawned for the client.
+    tracked_entities: HashSet<i32>,
+    /// The position the other players have last been sent, with the
+    /// on-ground flag.
+    synced_position: Option<(Position, bool)>,
+    /// State of the secure chat: the chat session and the messages sent and
+    /// seen by the client.
+    chat: ChatState,
+}
+
+/// A player that has logged into the server. It's shared between its
+/// connection and the server, so the other players can reach it.
+pub struct Player {
+    pub uuid: Uuid,
+    pub name: String,
+    /// ID of the entity of the player, unique within the server.
+    pub entity_id: i32,
+    /// Writer of the packets to the client of the player.
+    pub writer: Arc<PacketWriter>,
+    /// Properties of the profile, i.e. the skin. They're shown to the other
+    /// players.
+    pub properties: Vec<Property>,
+    state: Mutex<PlayerState>,
+}
+
+impl Player {
+    pub fn new(
+        uuid: Uuid,
+        name: String,
+        entity_id: i32,
+        game_mode: GameMode,
+        world: Arc<World>,
+        writer: Arc<PacketWriter>,
+    ) -> Self {
+        Self {
+            uuid,
+            name,
+            entity_id,
+            writer,
+            properties: vec![],
+            state: Mutex::new(PlayerState {
+                world,
+                position: Position::default(),
+                on_ground: false,
+                game_mode,
+                client_information: None,
+                pending_teleport: None,
+                next_teleport_id: 0,
+                latency: 0,
+                pending_keep_alive: None,
+                tracked_entities: HashSet::new(),
+                synced_position: None,
+                chat: ChatState::default(),
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PlayerState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Sends the packet to the client. Errors are ignored, as the connection
+    /// of the client notices them itself.
+    pub fn send_packet(&self, packet: &dyn Packet) {
+        let _ = self.writer.write_packet(packet);
+    }
+
+    /// Disconnects the client, which shows the reason. The connection is
+    /// closed once the packet is written, so it stops being served.
+    pub fn disconnect(&self, reason: TextComponent) {
+        self.send_packet(&ClientboundDisconnectPacket { reason });
+        self.writer.close();
+    }
+
+    pub fn world(&self) -> Arc<World> {
+        self.lock().world.clone()
+    }
+
+    /// Moves the player into the world. The client drops all entities then,
+    /// so they're no longer tracked.
+    pub fn set_world(&self, world: Arc<World>) {
+        let mut state = self.lock();
+        state.world = world;
+        state.tracked_entities.clear();
+    }
+
+    pub fn position(&self) -> Position {
+        self.lock().position
+    }
+
+    pub fn set_position(&self, position: Position) {
+        self.lock().position = position;
+    }
+
+    /// Checks whether the player could move from its current position to the
+    /// provided one in the world. Players may move out of the blocks they're
+    /// stuck in, but not into the new ones, and spectators may move through
+    /// the blocks at all.
+    pub fn check_move(&self, world: &World, to: Position) -> Result<(), InvalidMove> {
+        if ![to.x, to.y, to.z]
+            .iter()
+            .all(|coordinate| coordinate.is_finite())
+            || to.x.abs() > MAX_COORDINATE
+            || to.z.abs() > MAX_COORDINATE
+        {
+            return Err(InvalidMove::InvalidCoordinates);
+        }
+
+        let from = self.position();
+        let distance_squared =
+            (to.x - from.x).powi(2) + (to.y - from.y).powi(2) + (to.z - from.z).powi(2);
+        if distance_squared > MAX_MOVE_DISTANCE_SQUARED {
+            return Err(InvalidMove::TooFast(distance_squared.sqrt()));
+        }
+
+        let collides = |position: Position| {
+            world.collides(
+                position.x,
+                position.y,
+                position.z,
+                PLAYER_WIDTH,
+                PLAYER_HEIGHT,
+            )
+        };
+        if self.game_mode() != GameMode::Spectator && collides(to) && !collides(from) {
+            return Err(InvalidMove::IntoBlock);
+        }
+        Ok(())
+    }
+
+    /// Moves the player to the position on the server side, and returns the
+    /// ID of the teleport that the client has to confirm. Movement of the
+    /// client is ignored until then, as it doesn't know about the teleport.
+    pub fn start_teleport(&self, position: Position) -> i32 {
+        let mut state = self.lock();
+        let id = state.next_teleport_id;
+        state.next_teleport_id = id.wrapping_add(1);
+        state.position = position;
+        state.pending_teleport = Some(id);
+        id
+    }
+
+    /// Confirms the pending teleport. Returns `false` if the ID isn't the one
+    /// of the last teleport, which is still pending then.
+    pub fn confirm_teleport(&self, id: i32) -> bool {
+        let mut state = self.lock();
+        if state.pending_teleport != Some(id) {
+            return false;
+        }
+        state.pending_teleport = None;
+        true
+    }
+
+    /// Returns whether the client hasn't confirmed the last teleport yet.
+    pub fn is_teleporting(&self) -> bool {
+        self.lock().pending_teleport.is_some()
+    }
+
+    pub fn is_on_ground(&self) -> bool {
+        self.lock().on_ground
+    }
+
+    pub fn set_on_ground(&self, on_ground: bool) {
+        self.lock().on_ground = on_ground;
+    }
+
+    pub fn game_mode(&self) -> GameMode {
+        self.lock().game_mode
+    }
+
+    pub fn set_game_mode(&self, game_mode: GameMode) {
+        self.lock().game_mode = game_mode;
+    }
+
+    /// Returns the settings of the client, if they were received.
+    pub fn client_information(&self) -> Option<ServerboundClientInformationPacket> {
+        self.lock().client_information.clone()
+    }
+
+    pub fn set_client_information(&self, information: ServerboundClientInformationPacket) {
+        self.lock().client_information = Some(information);
+    }
+
+    /// Returns the view distance of the client, which is the smaller one of
+    /// the server and the client ones.
+    pub fn view_distance(&self, server_view_distance: u8) -> i32 {
+        let client_view_distance = self
+            .client_information()
+            .map_or(server_view_distance, |information| {
+                information.view_distance
+            });
+        client_view_distance.min(server_view_distance) as i32
+    }
+
+    /// Returns the chat mode of the client. The chat is enabled until the
+    /// settings are received.
+    pub fn chat_mode(&self) -> ClientInformationChatMode {
+        self.client_information()
+            .map_or(ClientInformationChatMode::Enabled, |information| {
+                information.chat_mode
+            })
+    }
+
+    /// Returns whether the client shows the messages of the other players.
+    pub fn accepts_chat_messages(&self) -> bool {
+        self.chat_mode() == ClientInformationChatMode::Enabled
+    }
+
+    /// Sends the system message to the chat, unless the client hides it.
+    /// Clients showing only the commands still see system messages.
+    pub fn send_message(&self, message: TextComponent) {
+        if self.chat_mode() == ClientInformationChatMode::Hidden {
+            return;
+        }
+        self.send_packet(&ClientboundSystemChatPacket {
+            content: message,
+            overlay: false,
+        });
+    }
+
+    /// Shows the message above the hotbar. It's shown even if the client
+    /// hides the chat, the same as in vanilla.
+    pub fn send_action_bar(&self, message: TextComponent) {
+        self.send_packet(&ClientboundSystemChatPacket {
+            content: message,
+            overlay: true,
+        });
+    }
+
+    /// Sends the chat message of a player, which is decorated with the chat
+    /// type. Signed messages are tracked, so the client may acknowledge them.
+    pub fn send_chat_message(
+        &self,
+        message: &PlayerChatMessage,
+        chat_type: VarInt,
+        sender_name: TextComponent,
+    ) {
+        // sent under the lock, so the client receives the messages in the
+        // order of their global indices
+        let mut state = self.lock();
+        let global_index = state.chat.track_sent(message.signature);
+        self.send_packet(&ClientboundPlayerChatPacket {
+            global_index: VarInt(global_index),
+            sender: message.sender,
+            index: VarInt(message.index),
+            signature: PrefixedOption(message.signature),
+            message: message.message.clone(),
+            timestamp: message.timestamp,
+            salt: message.salt,
+            last_seen: message.last_seen.clone(),
+            unsigned_content: PrefixedOption(None),
+            filter_type: VarInt(0),
+            chat_type,
+            sender_name,
+            target_name: PrefixedOption(None),
+        });
+    }
+
+    /// Returns the chat session of the player, if the client has sent one.
+    pub fn chat_session(&self) -> Option<ChatSessionData> {
+        let state = self.lock();
+        state.chat.session().map(|session| session.data.clone())
+    }
+
+    /// Starts the chat session of the player. Returns `false` if it's the
+    /// current session.
+    pub fn set_chat_session(
+        &self,
+        data: ChatSessionData,
+        services_keys: &[RsaPublicKey],
+    ) -> Result<bool, ChatError> {
+        self.lock()
+            .chat
+            .set_session(data, self.uuid, services_keys, current_millis())
+    }
+
+    /// Validates the message of the player and places it into the chain of
+    /// its chat session.
+    pub fn validate_chat_message(
+        &self,
+        message: &mut PlayerChatMessage,
+        enforce_secure_chat: bool,
+    ) -> Result<(), ChatError> {
+        let mut state = self.lock();
+        state
+            .chat
+            .validate_message(message, enforce_secure_chat, current_millis())
+    }
+
+    /// Applies the acknowledgements of the messages sent along with a
+    /// message. Returns the messages the client has seen.
+    pub fn apply_last_seen(
+        &self,
+        offset: i32,
+        acknowledged: [u8; LAST_SEEN_BYTES],
+        checksum: u8,
+    ) -> Result<LastSeenMessages, ChatError> {
+        self.lock()
+            .chat
+            .apply_last_seen(offset, acknowledged, checksum)
+    }
+
+    /// Drops the messages the client no longer acknowledges.
+    pub fn acknowledge_messages(&self, offset: i32) -> Result<(), ChatError> {
+        self.lock().chat.apply_offset(offset)
+    }
+
+    /// Returns the latency of the client in milliseconds.
+    pub fn latency(&self) -> i32 {
+        self.lock().latency
+    }
+
+    /// Remembers the keep-alive sent to the client. The previous one is kept
+    /// if it hasn't been answered, so the latency accounts for the wait.
+    pub fn start_keep_alive(&self, id: i64) {
+        let mut state = self.lock();
+        if state.pending_keep_alive.is_none() {
+            state.pending_keep_alive = Some((id, Instant::now()));
+        }
+    }
+
+    /// Returns whether the client hasn't answered the pending keep-alive in
+    /// time, so it's considered to be gone.
+    pub fn is_keep_alive_overdue(&self, now: Instant) -> bool {
+        self.lock()
+            .pending_keep_alive
+            .is_some_and(|(_, sent_at)| now.saturating_duration_since(sent_at) > KEEP_ALIVE_TIMEOUT)
+    }
+
+    /// Updates the latency with the answer to the pending keep-alive. Returns
+    /// `false` if the ID isn't the one of the keep-alive.
+    pub fn confirm_keep_alive(&self, id: i64) -> bool {
+        let mut state = self.lock();
+        let Some((pending_id, sent_at)) = state.pending_keep_alive else {
+            return false;
+        };
+        if pending_id != id {
+            return false;
+        }
+        state.pending_keep_alive = None;
+        // the same smoothing as vanilla does
+        let latency = sent_at.elapsed().as_millis() as i32;
+        state.latency = (state.latency * 3 + latency) / 4;
+        true
+    }
+
+    /// Replaces the entities tracked by the client with the provided ones.
+    /// Returns the ones that have to be spawned and removed for the client.
+    pub fn update_tracked_entities(&self, visible: HashSet<i32>) -> (Vec<i32>, Vec<i32>) {
+        let mut state = self.lock();
+        let spawned = visible
+            .difference(&state.tracked_entities)
+            .copied()
+            .collect();
+        let removed = state
+            .tracked_entities
+            .difference(&visible)
+            .copied()
+            .collect();
+        state.tracked_entities = visible;
+        (spawned, removed)
+    }
+
+    /// Returns whether the entity was spawned for the client.
+    pub fn is_tracking(&self, entity_id: i32) -> bool {
+        self.lock().tracked_entities.contains(&entity_id)
+    }
+
+    /// Returns the position the other players have last been sent, if any.
+    pub fn synced_position(&self) -> Option<(Position, bool)> {
+        self.lock().synced_position
+    }
+
+    /// Marks the current position as sent to the other players, returning
+    /// the one sent before.
+    pub fn sync_position(&self) -> Option<(Position, bool)> {
+        let mut state = self.lock();
+        let current = (state.position, state.on_ground);
+        state.synced_position.replace(current)
+    }
+
+    /// Returns the entry of the player in the tab list.
+    pub fn info_entry(&self) -> PlayerInfoEntry {
+        PlayerInfoEntry {
+            uuid: self.uuid,
+            name: self.name.clone(),
+            properties: PrefixedArray(self.properties.clone()),
+            game_mode: VarInt(self.game_mode() as i32),
+            listed: true,
+            latency: VarInt(self.latency()),
+            list_priority: VarInt(0),
+            show_hat: true,
+            chat_session: PrefixedOption(self.chat_session()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+    use crate::{
+        config::DimensionConfig,
+        world::{block::BlockState, position::BlockPos},
+    };
+
+    #[test]
+    fn test_relative_teleport() {
+        let from = Position {
+            x: 10.0,
+            y: 64.0,
+            z: -10.0,
+            yaw: 90.0,
+            pitch: 10.0,
+        };
+        let offset = Position {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+            yaw: 45.0,
+            pitch: -20.0,
+        };
+        assert_eq!(from.teleported(offset, TeleportFlags::empty()), offset);
+        assert_eq!(
+            from.teleported(offset, TeleportFlags::POSITION | TeleportFlags::YAW),
+            Position {
+                x: 11.0,
+                y: 66.0,
+                z: -7.0,
+                yaw: 135.0,
+                pitch: -20.0,
+            }
+        );
+    }
+
+    fn at(x: f64, y: f64, z: f64) -> Position {
+        Position {
+            x,
+            y,
+            z,
+            ..Position::default()
+        }
+    }
+
+    #[test]
+    fn test_check_move() {
+        let config = DimensionConfig::test_flat("minecraft:stone");
+        let world = Arc::new(World::from_config("minecraft:overworld", &config, 0).unwrap());
+        let writer = Arc::new(PacketWriter::new(io::sink()));
+        world.chunks.acquire(&[(0, 0)], &writer);
+        world.set_block(BlockPos::new(8, -62, 10), BlockState::new("stone").unwrap());
+
+        let player = Player::new(
+            Uuid::nil(),
+            String::from("Steve"),
+            1,
+            GameMode::Survival,
+            world.clone(),
+            writer,
+        );
+        let teleport_id = player.start_teleport(at(8.5, -63.0, 8.5));
+        assert!(player.is_teleporting());
+        assert!(!player.confirm_teleport(teleport_id + 1));
+        assert!(player.confirm_teleport(teleport_id));
+        assert!(!player.is_teleporting());
+
+        assert!(player.check_move(&world, at(8.5, -63.0, 9.5)).is_ok());
+        assert!(matches!(
+            player.check_move(&world, at(8.5, -63.0, 10.5)),
+            Err(InvalidMove::IntoBlock)
+        ));
+        assert!(matches!(
+            player.check_move(&world, at(8.5, -63.0, 30.0)),
+            Err(InvalidMove::TooFast(_))
+        ));
+        assert!(matches!(
+            player.check_move(&world, at(f64::NAN, -63.0, 8.5)),
+            Err(InvalidMove::InvalidCoordinates)
+        ));
+
+        player.set_game_mode(GameMode::Spectator);
+        assert!(player.check_move(&world, at(8.5, -63.0, 10.5)).is_ok());
+    }
+}
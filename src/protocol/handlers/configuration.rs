@@ -4,6 +4,7 @@ use crate::{
     handler_adapter,
     protocol::{
         PrefixedArray, ProtocolState, Writeable,
+        handlers::play,
         identifier::Identifier,
         packets::{
             configuration::{
@@ -11,9 +12,10 @@ use crate::{
                 ClientboundRegistryDataPacket, KnownPack, ServerboundAcknowledgeFinishPacket,
                 ServerboundClientInformationPacket, ServerboundKnownPacksPacket,
             },
-            play::{ClientboundPlayPacket, ClientboundSynchronizePlayerPositionPacket},
+            play::ClientboundPlayPacket,
         },
         registry::HandlersRegistry,
+        text::TextComponent,
     },
     registry::build_registries_data,
     varint::VarInt,
@@ -50,6 +52,7 @@ pub fn handle_client_information(
     packet: &ServerboundClientInformationPacket,
 ) {
     println!("Received client information: {packet:?}");
+    connection.player().set_client_information(packet.clone());
     let core_pack = KnownPack {
         namespace: Identifier::minecraft("core"),
         id: String::from("wtf"),
@@ -82,44 +85,48 @@ pub fn handle_acknowledge_finish_configuration(
 ) {
     println!("State -> Play");
     connection.set_state(ProtocolState::Play);
-    let dimension_names = vec![Identifier::minecraft("overworld")];
+    let world = connection.world.clone();
+    let player = connection.player().clone();
+    let dimension_names: Vec<Identifier> = connection
+        .server
+        .worlds
+        .names()
+        .map(Identifier::parse)
+        .collect();
     let play_packet = ClientboundPlayPacket {
-        entity_id: 0_i32,
+        entity_id: player.entity_id,
         is_hardcore: false,
-        dimension_names: PrefixedArray(dimension_names.clone()),
+        dimension_names: PrefixedArray(dimension_names),
         max_players: VarInt(1337),
-        view_distance: VarInt(16),
+        view_distance: VarInt(connection.server.config.view_distance as i32),
         simulation_distance: VarInt(12),
         reduced_debug_info: false,
         enable_respawn_screen: true,
         do_limited_crafting: false,
-        dimension_type: VarInt(0),
-        dimension_name: dimension_names[0].clone(),
-        hashed_seed: 0_i64,
-        game_mode: 0_u8,
-        previous_game_mode: 0_i8,
-        is_debug: true,
-        is_flat: false,
+        dimension_type: world.dimension_type_id,
+        dimension_name: Identifier::parse(&world.name),
+        hashed_seed: world.hashed_seed(),
+        game_mode: player.game_mode() as u8,
+        previous_game_mode: -1_i8,
+        is_debug: false,
+        is_flat: world.is_flat,
         has_death_location: false,
         death_dimension_name: None,
         death_location: None,
         portal_cooldown: VarInt(20),
-        sea_level: VarInt(100),
-        enforces_secure_chat: false,
+        sea_level: VarInt(world.sea_level),
+        enforces_secure_chat: connection.server.config.enforce_secure_chat,
     };
     connection.write_packet(Box::new(play_packet));
-
-    let synchronize_player_position_packet = ClientboundSynchronizePlayerPositionPacket {
-        teleport_id: VarInt(0),
-        x: 0.0,
-        y: 0.0,
-        z: 0.0,
-        velocity_x: 0.0,
-        velocity_y: 0.0,
-        velocity_z: 0.0,
-        yaw: 0.0,
-        pitch: 0.0,
-        flags: 0,
-    };
-    connection.write_packet(Box::new(synchronize_player_position_packet));
+    connection.server.submit(move |server| {
+        if let Some(previous) = server.add_player(player.clone()) {
+            // the same account can't play from two clients at once
+            previous.disconnect(TextComponent::translatable(
+                "multiplayer.disconnect.duplicate_login",
+                vec![],
+            ));
+        }
+        println!("{} has joined the server", player.name);
+    });
+    play::teleport_to_spawn(connection);
 }
@@ -1,7 +1,10 @@
+use std::sync::Arc;
+
 use crate::{
     Packet,
     connection::Connection,
     handler_adapter,
+    player::Player,
     protocol::{
         PrefixedArray, ProtocolState,
         packets::login::{
@@ -32,11 +35,21 @@ pub fn setup_registry(registry: &mut HandlersRegistry) {
 
 /// Handles the incoming `LoginStart` packet.
 pub fn handle_login_start(connection: &mut Connection, packet: &ServerboundLoginStartPacket) {
+    let server = connection.server.clone();
+    connection.player = Some(Arc::new(Player::new(
+        packet.id,
+        packet.name.clone(),
+        server.allocate_entity_id(),
+        server.config.game_mode,
+        connection.world.clone(),
+        connection.writer.clone(),
+    )));
+
     // for now, just send the whole LoginSuccess packet
     let packet = ClientboundLoginSuccessPacket {
         id: packet.id,
         name: packet.name.to_owned(),
-        properties: PrefixedArray(vec![]),
+        properties: PrefixedArray(connection.player().properties.clone()),
     };
     connection.write_packet(Box::new(packet));
 }
@@ -1,23 +1,42 @@
-// ClientboundSynchronizePlayerPositionPacket
-
-use bytes::{BufMut, BytesMut};
+use std::sync::Arc;
 
 use crate::{
     Packet,
+    chat::{ChatError, PlayerChatMessage, current_millis},
     connection::Connection,
     handler_adapter,
+    player::{Location, Position},
     protocol::{
-        BitSet, PrefixedArray, ProtocolState,
-        packets::play::{
-            ChunkData, ClientboundChunkDataAndLightPacket, ClientboundGameEventPacket, LightData,
-            ServerboundConfirmTeleportationPacket,
+        ProtocolState,
+        identifier::Identifier,
+        packets::{
+            configuration::ClientInformationChatMode,
+            play::{
+                ClientboundChunkBatchFinishedPacket, ClientboundChunkBatchStartPacket,
+                ClientboundGameEventPacket, ClientboundRespawnPacket,
+                ClientboundSetCenterChunkPacket, ClientboundSynchronizePlayerPositionPacket,
+                ClientboundSystemChatPacket, ClientboundUnloadChunkPacket,
+                ServerboundChatMessagePacket, ServerboundChunkBatchReceivedPacket,
+                ServerboundConfirmTeleportationPacket, ServerboundKeepAlivePacket,
+                ServerboundMessageAcknowledgmentPacket, ServerboundPlayClientInformationPacket,
+                ServerboundPlayerSessionPacket, ServerboundSetPlayerMovementFlagsPacket,
+                ServerboundSetPlayerPositionAndRotationPacket, ServerboundSetPlayerPositionPacket,
+                ServerboundSetPlayerRotationPacket, TeleportFlags,
+            },
         },
         registry::HandlersRegistry,
+        text::{Color, NamedColor, TextComponent},
     },
     varint::VarInt,
-    world::{Chunk, Heightmap},
+    world::{World, position::ChunkPos},
 };
 
+/// Flag of the movement packets that is set when the player is on the ground.
+const ON_GROUND_FLAG: u8 = 0x01;
+
+/// The longest message the client is allowed to send to the chat.
+const MAX_CHAT_MESSAGE_LENGTH: usize = 256;
+
 /// Setups the registry for this handlers set and protocol state. Only handlers
 /// for serverbound packets are registered, through.
 pub fn setup_registry(registry: &mut HandlersRegistry) {
@@ -29,84 +48,446 @@ pub fn setup_registry(registry: &mut HandlersRegistry) {
             handle_confirm_teleportation
         ),
     );
+    registry.register(
+        ProtocolState::Play,
+        ServerboundChunkBatchReceivedPacket::PACKET_ID,
+        handler_adapter!(
+            ServerboundChunkBatchReceivedPacket,
+            handle_chunk_batch_received
+        ),
+    );
+    registry.register(
+        ProtocolState::Play,
+        ServerboundPlayClientInformationPacket::PACKET_ID,
+        handler_adapter!(
+            ServerboundPlayClientInformationPacket,
+            handle_client_information
+        ),
+    );
+    registry.register(
+        ProtocolState::Play,
+        ServerboundSetPlayerPositionPacket::PACKET_ID,
+        handler_adapter!(ServerboundSetPlayerPositionPacket, handle_set_position),
+    );
+    registry.register(
+        ProtocolState::Play,
+        ServerboundSetPlayerPositionAndRotationPacket::PACKET_ID,
+        handler_adapter!(
+            ServerboundSetPlayerPositionAndRotationPacket,
+            handle_set_position_and_rotation
+        ),
+    );
+    registry.register(
+        ProtocolState::Play,
+        ServerboundSetPlayerRotationPacket::PACKET_ID,
+        handler_adapter!(ServerboundSetPlayerRotationPacket, handle_set_rotation),
+    );
+    registry.register(
+        ProtocolState::Play,
+        ServerboundSetPlayerMovementFlagsPacket::PACKET_ID,
+        handler_adapter!(
+            ServerboundSetPlayerMovementFlagsPacket,
+            handle_set_movement_flags
+        ),
+    );
+    registry.register(
+        ProtocolState::Play,
+        ServerboundKeepAlivePacket::PACKET_ID,
+        handler_adapter!(ServerboundKeepAlivePacket, handle_keep_alive),
+    );
+    registry.register(
+        ProtocolState::Play,
+        ServerboundMessageAcknowledgmentPacket::PACKET_ID,
+        handler_adapter!(
+            ServerboundMessageAcknowledgmentPacket,
+            handle_message_acknowledgment
+        ),
+    );
+    registry.register(
+        ProtocolState::Play,
+        ServerboundChatMessagePacket::PACKET_ID,
+        handler_adapter!(ServerboundChatMessagePacket, handle_chat_message),
+    );
+    registry.register(
+        ProtocolState::Play,
+        ServerboundPlayerSessionPacket::PACKET_ID,
+        handler_adapter!(ServerboundPlayerSessionPacket, handle_player_session),
+    );
 }
 
-fn encode_all_air_section() -> Vec<u8> {
-    let mut buf = BytesMut::new();
+pub fn handle_confirm_teleportation(
+    connection: &mut Connection,
+    packet: &ServerboundConfirmTeleportationPacket,
+) {
+    println!("{:?}", packet);
+    if !connection.player().confirm_teleport(packet.teleport_id.0) {
+        return;
+    }
 
-    // Block count (u16) = 0
-    buf.put_u16(0);
+    // the client starts waiting for the chunks around it
+    let game_event_packet = ClientboundGameEventPacket {
+        event: 13,
+        value: 0.0,
+    };
+    connection.write_packet(Box::new(game_event_packet));
+}
 
-    // Bits per block (u8) = 0
-    buf.put_u8(0);
+pub fn handle_keep_alive(connection: &mut Connection, packet: &ServerboundKeepAlivePacket) {
+    let player = connection.player().clone();
+    if player.confirm_keep_alive(packet.id) {
+        connection
+            .server
+            .submit(move |server| server.broadcast_latency(&player));
+    }
+}
 
-    // Palette length (VarInt) = 1
-    buf.put_u8(1);
+/// Returns whether the message can be shown in the chat: it isn't too long
+/// and has neither the formatting codes nor the control characters.
+fn is_valid_chat_message(message: &str) -> bool {
+    message.chars().count() <= MAX_CHAT_MESSAGE_LENGTH
+        && !message
+            .chars()
+            .any(|character| character == '\u{a7}' || character < ' ' || character == '\u{7f}')
+}
 
-    // Palette[0] (VarInt) = 0 (air)
-    buf.put_u8(0);
+/// Disconnects the client whose chat has failed the validation.
+fn disconnect_chat_error(connection: &mut Connection, error: ChatError) {
+    println!(
+        "{} has failed the chat validation: {error}",
+        connection.player().name
+    );
+    connection.disconnect(error.disconnect_reason());
+}
 
-    // Data array length (VarInt) = 0
-    buf.put_u8(0);
+pub fn handle_message_acknowledgment(
+    connection: &mut Connection,
+    packet: &ServerboundMessageAcknowledgmentPacket,
+) {
+    if let Err(e) = connection.player().acknowledge_messages(packet.offset.0) {
+        disconnect_chat_error(connection, e);
+    }
+}
 
-    // Biome palette length (VarInt) = 1 (just plains)
-    buf.put_u8(1);
+pub fn handle_chat_message(connection: &mut Connection, packet: &ServerboundChatMessagePacket) {
+    let player = connection.player().clone();
+    if !is_valid_chat_message(&packet.message) {
+        println!("{} has sent an invalid chat message", player.name);
+        let reason =
+            TextComponent::translatable("multiplayer.disconnect.illegal_characters", vec![]);
+        connection.disconnect(reason);
+        return;
+    }
+    let last_seen = match player.apply_last_seen(
+        packet.message_count.0,
+        packet.acknowledged,
+        packet.checksum,
+    ) {
+        Ok(last_seen) => last_seen,
+        Err(e) => {
+            disconnect_chat_error(connection, e);
+            return;
+        }
+    };
+    // the notice is sent past the chat mode, as `send_message` would drop it
+    if player.chat_mode() == ClientInformationChatMode::Hidden {
+        let mut message = TextComponent::translatable("chat.disabled.options", vec![]);
+        message.color = Some(Color::Named(NamedColor::Red));
+        player.send_packet(&ClientboundSystemChatPacket {
+            content: message,
+            overlay: false,
+        });
+        return;
+    }
 
-    // Biome[0] (VarInt) = 0 (plains biome)
-    buf.put_u8(0);
+    let mut message = PlayerChatMessage {
+        sender: player.uuid,
+        index: 0,
+        signature: packet.signature.0,
+        message: packet.message.clone(),
+        timestamp: packet.timestamp,
+        salt: packet.salt,
+        last_seen,
+    };
+    let enforce_secure_chat = connection.server.config.enforce_secure_chat;
+    if let Err(e) = player.validate_chat_message(&mut message, enforce_secure_chat) {
+        disconnect_chat_error(connection, e);
+        return;
+    }
+    if message.is_expired(current_millis()) {
+        println!("{} has sent an expired chat message", player.name);
+    }
 
-    // Biome data array length (VarInt) = 0 (since only one biome in palette)
-    buf.put_u8(0);
+    println!("<{}> {}", player.name, packet.message);
+    connection
+        .server
+        .submit(move |server| server.broadcast_chat(&player, &message));
+}
 
-    buf.to_vec()
+pub fn handle_player_session(connection: &mut Connection, packet: &ServerboundPlayerSessionPacket) {
+    let player = connection.player().clone();
+    match player.set_chat_session(packet.session.clone(), &connection.server.services_keys) {
+        Ok(true) => {
+            connection
+                .server
+                .submit(move |server| server.broadcast_chat_session(&player));
+        }
+        Ok(false) => {}
+        Err(e) => disconnect_chat_error(connection, e),
+    }
 }
 
-pub fn handle_confirm_teleportation(
+pub fn handle_chunk_batch_received(
     connection: &mut Connection,
-    packet: &ServerboundConfirmTeleportationPacket,
+    packet: &ServerboundChunkBatchReceivedPacket,
 ) {
-    println!("{:?}", packet);
+    connection
+        .chunk_tracker
+        .batch_received(packet.chunks_per_tick);
+    send_chunk_batch(connection);
+}
 
-    let game_event_packet = ClientboundGameEventPacket {
-        event: 13,
-        value: 0.0,
+pub fn handle_client_information(
+    connection: &mut Connection,
+    packet: &ServerboundPlayClientInformationPacket,
+) {
+    connection
+        .player()
+        .set_client_information(packet.clone().into());
+
+    let view_distance = connection.view_distance();
+    let unloaded = connection.chunk_tracker.set_view_distance(view_distance);
+    unload_chunks(connection, &unloaded);
+    send_chunk_batch(connection);
+}
+
+pub fn handle_set_position(
+    connection: &mut Connection,
+    packet: &ServerboundSetPlayerPositionPacket,
+) {
+    let position = Position {
+        x: packet.x,
+        y: packet.y,
+        z: packet.z,
+        ..connection.player().position()
     };
-    connection.write_packet(Box::new(game_event_packet));
+    handle_move(connection, position, packet.flags);
+}
+
+pub fn handle_set_position_and_rotation(
+    connection: &mut Connection,
+    packet: &ServerboundSetPlayerPositionAndRotationPacket,
+) {
+    let position = Position {
+        x: packet.x,
+        y: packet.y,
+        z: packet.z,
+        yaw: packet.yaw,
+        pitch: packet.pitch,
+    };
+    handle_move(connection, position, packet.flags);
+}
+
+pub fn handle_set_rotation(
+    connection: &mut Connection,
+    packet: &ServerboundSetPlayerRotationPacket,
+) {
+    let position = Position {
+        yaw: packet.yaw,
+        pitch: packet.pitch,
+        ..connection.player().position()
+    };
+    handle_move(connection, position, packet.flags);
+}
+
+pub fn handle_set_movement_flags(
+    connection: &mut Connection,
+    packet: &ServerboundSetPlayerMovementFlagsPacket,
+) {
+    let position = connection.player().position();
+    handle_move(connection, position, packet.flags);
+}
 
-    let fake_heightmap_nbt = vec![
-        0x0a, 0x00, 0x00, // TAG_Compound ""
-        0x09, 0x00, 0x0f, // TAG_Long_Array "MOTION_BLOCKING"
-        b'M', b'O', b'T', b'I', b'O', b'N', b'_', b'B', b'L', b'O', b'C', b'K', b'I', b'N', b'G',
-        0x00, 0x00, 0x00, 0x10, // 16 entries
-        // 16*8 bytes zero (fake, should be enough for your world height)
-        // ... fill in with zeroes ...
-        0x00, // TAG_End
-    ];
-    let heightmap = PrefixedArray(vec![fake_heightmap_nbt]);
-
-    let mut all_sections = Vec::new();
-    for _ in 0..16 {
-        let section = encode_all_air_section();
-        all_sections.extend_from_slice(&section);
+/// Moves the player to the position sent by the client, if it's valid.
+/// Otherwise, the client is teleported back to the previous position. Moves
+/// made before the client has confirmed the last teleport are ignored.
+fn handle_move(connection: &mut Connection, position: Position, flags: u8) {
+    let player = connection.player().clone();
+    if player.is_teleporting() {
+        return;
     }
 
-    let heightmaps = vec![Heightmap::new(VarInt(0)), Heightmap::new(VarInt(1))];
-    let packet = ClientboundChunkDataAndLightPacket {
-        chunk_x: 0,
-        chunk_z: 0,
-        chunk_data: ChunkData {
-            heightmap: PrefixedArray(heightmaps),
-            data: Chunk::new(0, 0),
-            block_entities: PrefixedArray(vec![]),
-        },
-        light_data: LightData {
-            sky_light_mask: BitSet::empty(),
-            block_light_mask: BitSet::empty(),
-            empty_sky_light_mask: BitSet::empty(),
-            empty_block_light_mask: BitSet::empty(),
-            sky_lights: PrefixedArray(vec![]),
-            block_lights: PrefixedArray(vec![]),
+    if let Err(e) = player.check_move(&connection.world, position) {
+        println!("{} {e}, teleporting it back", player.name);
+        // the rotation of the client is kept
+        let location = Location {
+            world: connection.world.clone(),
+            position: Position {
+                yaw: 0.0,
+                pitch: 0.0,
+                ..player.position()
+            },
+        };
+        teleport(connection, location, TeleportFlags::ROTATION);
+        return;
+    }
+
+    player.set_position(position);
+    player.set_on_ground(flags & ON_GROUND_FLAG != 0);
+    move_chunk_center(connection, position.x, position.z);
+}
+
+/// Teleports the player to the location. Fields of the position marked by
+/// the flags are relative to the current ones. If the location is in another
+/// world, the client moves into it first, and the relative fields are counted
+/// from the position in the previous world. Movement of the client is ignored
+/// until it confirms the teleport.
+pub fn teleport(connection: &mut Connection, location: Location, flags: TeleportFlags) {
+    let current = connection.player().position();
+    let target = current.teleported(location.position, flags);
+
+    // the client doesn't keep its position when it moves into another world,
+    // so it's sent the absolute one
+    let (position, flags) = if Arc::ptr_eq(&connection.world, &location.world) {
+        (location.position, flags)
+    } else {
+        respawn(connection, location.world);
+        (target, TeleportFlags::empty())
+    };
+
+    let teleport_id = connection.player().start_teleport(target);
+    let synchronize_player_position_packet = ClientboundSynchronizePlayerPositionPacket {
+        teleport_id: VarInt(teleport_id),
+        x: position.x,
+        y: position.y,
+        z: position.z,
+        velocity_x: 0.0,
+        velocity_y: 0.0,
+        velocity_z: 0.0,
+        yaw: position.yaw,
+        pitch: position.pitch,
+        flags,
+    };
+    connection.write_packet(Box::new(synchronize_player_position_packet));
+    move_chunk_center(connection, target.x, target.z);
+}
+
+/// Returns the location of the spawn of the world.
+fn spawn_location(world: &Arc<World>) -> Location {
+    let (spawn_x, spawn_y, spawn_z) = world.spawn_position();
+    Location {
+        world: world.clone(),
+        position: Position {
+            x: spawn_x as f64 + 0.5,
+            y: spawn_y as f64,
+            z: spawn_z as f64 + 0.5,
+            yaw: 0.0,
+            pitch: 0.0,
         },
+    }
+}
+
+/// Teleports the client to the spawn of its world and starts sending the
+/// chunks around it.
+pub fn teleport_to_spawn(connection: &mut Connection) {
+    let time_packet = connection.world.time_packet();
+    connection.write_packet(Box::new(time_packet));
+
+    let view_distance = connection.view_distance();
+    connection.chunk_tracker.set_view_distance(view_distance);
+    let location = spawn_location(&connection.world);
+    teleport(connection, location, TeleportFlags::empty());
+}
+
+/// Moves the client into the provided world and teleports it to the spawn
+/// there.
+pub fn change_world(connection: &mut Connection, world: Arc<World>) {
+    if Arc::ptr_eq(&connection.world, &world) {
+        return;
+    }
+    teleport(connection, spawn_location(&world), TeleportFlags::empty());
+}
+
+/// Moves the client into the provided world with the respawn packet. Chunks
+/// of the previous world are released, as the client drops them itself.
+fn respawn(connection: &mut Connection, world: Arc<World>) {
+    let sent_chunks = connection.chunk_tracker.sent_chunks();
+    connection
+        .world
+        .chunks
+        .release(&sent_chunks, &connection.writer);
+    connection.chunk_tracker.reset();
+    connection.world = world.clone();
+    connection.player().set_world(world.clone());
+
+    let respawn_packet = ClientboundRespawnPacket {
+        dimension_type: world.dimension_type_id,
+        dimension_name: Identifier::parse(&world.name),
+        hashed_seed: world.hashed_seed(),
+        game_mode: connection.player().game_mode() as u8,
+        previous_game_mode: -1_i8,
+        is_debug: false,
+        is_flat: world.is_flat,
+        has_death_location: false,
+        death_dimension_name: None,
+        death_location: None,
+        portal_cooldown: VarInt(20),
+        sea_level: VarInt(world.sea_level),
+        data_kept: 0,
     };
-    connection.write_packet(Box::new(packet));
+    connection.write_packet(Box::new(respawn_packet));
+    println!("Client has moved into {}", world.name);
+
+    let time_packet = world.time_packet();
+    connection.write_packet(Box::new(time_packet));
+    let view_distance = connection.view_distance();
+    connection.chunk_tracker.set_view_distance(view_distance);
+}
+
+/// Moves the center of the chunks tracked for the client to the chunk at the
+/// provided block coordinates. Chunks that went out of the view distance are
+/// unloaded, and the new ones are queued to be sent.
+pub fn move_chunk_center(connection: &mut Connection, x: f64, z: f64) {
+    let ChunkPos {
+        x: chunk_x,
+        z: chunk_z,
+    } = ChunkPos::from_coordinates(x, z);
+    if connection.chunk_tracker.center() == Some((chunk_x, chunk_z)) {
+        return;
+    }
+
+    connection.write_packet(Box::new(ClientboundSetCenterChunkPacket {
+        chunk_x: VarInt(chunk_x),
+        chunk_z: VarInt(chunk_z),
+    }));
+
+    let unloaded = connection.chunk_tracker.move_to(chunk_x, chunk_z);
+    unload_chunks(connection, &unloaded);
+    send_chunk_batch(connection);
+}
+
+/// Unloads the chunks on the client and releases them in the world.
+fn unload_chunks(connection: &mut Connection, positions: &[(i32, i32)]) {
+    for &(chunk_x, chunk_z) in positions {
+        connection.write_packet(Box::new(ClientboundUnloadChunkPacket { chunk_z, chunk_x }));
+    }
+    connection
+        .world
+        .chunks
+        .release(positions, &connection.writer);
+}
+
+/// Sends the next batch of the chunks around the client, if the previous one
+/// was acknowledged.
+pub fn send_chunk_batch(connection: &mut Connection) {
+    let positions = connection.chunk_tracker.next_batch();
+    if positions.is_empty() {
+        return;
+    }
+
+    connection.write_packet(Box::new(ClientboundChunkBatchStartPacket {}));
+    let world = connection.world.clone();
+    world.chunks.acquire(&positions, &connection.writer);
+    connection.write_packet(Box::new(ClientboundChunkBatchFinishedPacket {
+        batch_size: VarInt(positions.len() as i32),
+    }));
 }
@@ -20,6 +20,18 @@ impl Identifier {
             value: value.to_string(),
         }
     }
+
+    /// Parses the identifier from the `namespace:value` string. Values without
+    /// the namespace are put into the `minecraft` one.
+    pub fn parse(raw_value: &str) -> Self {
+        match raw_value.split_once(':') {
+            Some((namespace, value)) => Self {
+                namespace: namespace.to_string(),
+                value: value.to_string(),
+            },
+            None => Self::minecraft(raw_value),
+        }
+    }
 }
 
 impl Display for Identifier {
@@ -32,24 +44,7 @@ impl Readable for Identifier {
     fn read(buffer: &[u8]) -> Result<(Self, usize), super::ReadError> {
         let mut reader = BufferReader::new(buffer);
         let raw_value = reader.read(String::read)?;
-
-        if let Some((namespace, value)) = raw_value.split_once(':') {
-            return Ok((
-                Self {
-                    namespace: namespace.to_string(),
-                    value: value.to_string(),
-                },
-                reader.consumed(),
-            ));
-        }
-
-        Ok((
-            Self {
-                namespace: String::from("minecraft"),
-                value: raw_value,
-            },
-            reader.consumed(),
-        ))
+        Ok((Self::parse(&raw_value), reader.consumed()))
     }
 }
 
@@ -14,8 +14,10 @@ macro_rules! register_packet {
     };
 }
 
+/// Defines a packet that is only sent to the clients, so it's never read.
+/// Its fields only have to be `Writeable`.
 #[macro_export]
-macro_rules! define_packet {
+macro_rules! define_clientbound_packet {
     ($name: ident, $id: expr, $state: ident, { $($field: ident : $type: ty$(,)?)* }) => {
         #[derive(Debug, Clone)]
         pub struct $name {
@@ -31,17 +33,6 @@ macro_rules! define_packet {
             pub const PACKET_STATE: $crate::protocol::ProtocolState = $crate::protocol::ProtocolState::$state;
         }
 
-        impl $crate::protocol::Readable for $name {
-            fn read(_buffer: &[u8]) -> std::result::Result<(Self, usize), $crate::protocol::ReadError> {
-                let mut total_read_length: usize = 0;
-                $(
-                    let ($field, read_length) = <$type as $crate::protocol::Readable>::read(&_buffer[total_read_length..])?;
-                    total_read_length += read_length;
-                )*
-                Ok((Self { $($field,)* }, total_read_length))
-            }
-        }
-
         impl $crate::protocol::Writeable for $name {
             fn write(&self) -> std::result::Result<bytes::Bytes, $crate::protocol::WriteError> {
                 let mut buffer = bytes::BytesMut::new(); // TODO: detect the maximum allocation size
@@ -64,11 +55,29 @@ macro_rules! define_packet {
     };
 }
 
+#[macro_export]
+macro_rules! define_packet {
+    ($name: ident, $id: expr, $state: ident, { $($field: ident : $type: ty$(,)?)* }) => {
+        $crate::define_clientbound_packet!($name, $id, $state, { $($field: $type,)* });
+
+        impl $crate::protocol::Readable for $name {
+            fn read(_buffer: &[u8]) -> std::result::Result<(Self, usize), $crate::protocol::ReadError> {
+                let mut total_read_length: usize = 0;
+                $(
+                    let ($field, read_length) = <$type as $crate::protocol::Readable>::read(&_buffer[total_read_length..])?;
+                    total_read_length += read_length;
+                )*
+                Ok((Self { $($field,)* }, total_read_length))
+            }
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! define_varint_enum {
     ($name: ident, { $($variant_name: ident = $variant_value: expr$(,)?)* }) => {
         #[repr(i32)]
-        #[derive(Debug, Clone)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
         pub enum $name {
             $($variant_name = $variant_value,)*
         }
@@ -11,6 +11,7 @@ use crate::{
 
 pub mod identifier;
 pub mod macros;
+pub mod nbt;
 pub mod packets;
 pub mod server_list_ping;
 
@@ -55,6 +56,10 @@ pub enum ReadError {
     /// Indicates that the provided buffer is malformed for this type.
     #[error("the provided buffer is malformed")]
     MalformedBuffer,
+    /// Indicates that the received block state ID isn't in the block
+    /// registry.
+    #[error("unknown block state ID: {0}")]
+    UnknownBlockState(i32),
 
     /// Represents an error occurred while converting raw read bytes into the
     /// string.
@@ -66,6 +71,35 @@ pub enum ReadError {
 #[derive(Debug, Clone)]
 pub struct PrefixedArray<T: Readable + Writeable>(pub Vec<T>);
 
+/// Represents an optional value that is prefixed with a boolean telling
+/// whether it's present.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrefixedOption<T>(pub Option<T>);
+
+/// Rotation angle in steps of 1/256 of a full turn.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Angle(pub u8);
+
+impl Angle {
+    /// Converts the angle in degrees, wrapping it into a single turn.
+    pub fn from_degrees(degrees: f32) -> Self {
+        Self((degrees * 256.0 / 360.0).floor() as i32 as u8)
+    }
+}
+
+impl Readable for Angle {
+    fn read(buffer: &[u8]) -> Result<(Self, usize), ReadError> {
+        let (value, read_length) = u8::read(buffer)?;
+        Ok((Self(value), read_length))
+    }
+}
+
+impl Writeable for Angle {
+    fn write(&self) -> Result<Bytes, WriteError> {
+        self.0.write()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct BitSet {
     inner: Vec<u64>,
@@ -84,6 +118,14 @@ impl BitSet {
             false
         }
     }
+
+    pub fn set(&mut self, bit: usize) {
+        let (word, bit) = (bit / 64, bit % 64);
+        if self.inner.len() <= word {
+            self.inner.resize(word + 1, 0);
+        }
+        self.inner[word] |= 1 << bit;
+    }
 }
 
 impl Readable for BitSet {
@@ -200,21 +242,44 @@ impl<T: Readable + Writeable> Readable for PrefixedArray<T> {
     }
 }
 
+impl<T: Readable> Readable for PrefixedOption<T> {
+    fn read(buffer: &[u8]) -> Result<(Self, usize), ReadError> {
+        let (is_present, mut total_length) = bool::read(buffer)?;
+        if !is_present {
+            return Ok((PrefixedOption(None), total_length));
+        }
+        let (value, read_length) = T::read(&buffer[total_length..])?;
+        total_length += read_length;
+        Ok((PrefixedOption(Some(value)), total_length))
+    }
+}
+
+impl<const N: usize> Readable for [u8; N] {
+    fn read(buffer: &[u8]) -> Result<(Self, usize), ReadError> {
+        let array_buffer = buffer.get(..N).ok_or(ReadError::Incomplete)?;
+        let array: [u8; N] = array_buffer.try_into().unwrap(); // safe: `array_buffer` is always N bytes
+        Ok((array, N))
+    }
+}
+
 impl Readable for u8 {
     fn read(buffer: &[u8]) -> Result<(Self, usize), ReadError> {
-        Ok((buffer[0], 1))
+        let byte = *buffer.first().ok_or(ReadError::Incomplete)?;
+        Ok((byte, 1))
     }
 }
 
 impl Readable for bool {
     fn read(buffer: &[u8]) -> Result<(Self, usize), ReadError> {
-        Ok((buffer[0] == 0x01, 1))
+        let byte = *buffer.first().ok_or(ReadError::Incomplete)?;
+        Ok((byte == 0x01, 1))
     }
 }
 
 impl Readable for i8 {
     fn read(buffer: &[u8]) -> Result<(Self, usize), ReadError> {
-        Ok((buffer[0] as i8, 1))
+        let byte = *buffer.first().ok_or(ReadError::Incomplete)?;
+        Ok((byte as i8, 1))
     }
 }
 
@@ -224,6 +289,14 @@ impl Readable for Vec<u8> {
     }
 }
 
+impl Readable for i16 {
+    fn read(buffer: &[u8]) -> Result<(Self, usize), ReadError> {
+        let i16_buffer = buffer.get(..2).ok_or(ReadError::Incomplete)?;
+        let array: [u8; 2] = i16_buffer.try_into().unwrap(); // safe: `i16_buffer` is always 2 bytes
+        Ok((i16::from_be_bytes(array), 2))
+    }
+}
+
 impl Readable for i32 {
     fn read(buffer: &[u8]) -> Result<(Self, usize), ReadError> {
         let i32_buffer = buffer.get(..4).ok_or(ReadError::Incomplete)?;
@@ -325,6 +398,23 @@ impl<T: Readable + Writeable> Writeable for PrefixedArray<T> {
     }
 }
 
+impl<T: Writeable> Writeable for PrefixedOption<T> {
+    fn write(&self) -> Result<Bytes, WriteError> {
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&self.0.is_some().write()?);
+        if let Some(value) = &self.0 {
+            buffer.extend_from_slice(&value.write()?);
+        }
+        Ok(buffer.freeze())
+    }
+}
+
+impl<const N: usize> Writeable for [u8; N] {
+    fn write(&self) -> Result<Bytes, WriteError> {
+        Ok(Bytes::copy_from_slice(self))
+    }
+}
+
 impl Writeable for u8 {
     fn write(&self) -> Result<Bytes, WriteError> {
         let mut buffer = BytesMut::with_capacity(1);
@@ -357,6 +447,12 @@ impl Writeable for Vec<u8> {
     }
 }
 
+impl Writeable for i16 {
+    fn write(&self) -> Result<Bytes, WriteError> {
+        Ok(Bytes::copy_from_slice(&self.to_be_bytes()))
+    }
+}
+
 impl Writeable for i32 {
     fn write(&self) -> Result<Bytes, WriteError> {
         let mut buffer = BytesMut::with_capacity(4);
@@ -367,10 +463,10 @@ impl Writeable for i32 {
 
 impl<T: Writeable> Writeable for Option<T> {
     fn write(&self) -> Result<Bytes, WriteError> {
-        if self.is_none() {
-            return Ok(Bytes::new());
+        match self {
+            Some(value) => value.write(),
+            None => Ok(Bytes::new()),
         }
-        self.write()
     }
 }
 
@@ -0,0 +1,444 @@
+use std::collections::BTreeMap;
+
+use bytes::Bytes;
+
+use crate::protocol::{ReadError, Readable, WriteError, Writeable};
+
+/// Maximum nesting depth of compounds and lists, as per vanilla limits.
+const MAX_DEPTH: usize = 512;
+
+const TAG_END: u8 = 0;
+const TAG_BYTE: u8 = 1;
+const TAG_SHORT: u8 = 2;
+const TAG_INT: u8 = 3;
+const TAG_LONG: u8 = 4;
+const TAG_FLOAT: u8 = 5;
+const TAG_DOUBLE: u8 = 6;
+const TAG_BYTE_ARRAY: u8 = 7;
+const TAG_STRING: u8 = 8;
+const TAG_LIST: u8 = 9;
+const TAG_COMPOUND: u8 = 10;
+const TAG_INT_ARRAY: u8 = 11;
+const TAG_LONG_ARRAY: u8 = 12;
+
+/// Returns the slice of the provided length, or `ReadError::Incomplete` if
+/// there are not enough bytes.
+fn take(buffer: &[u8], offset: usize, length: usize) -> Result<&[u8], ReadError> {
+    buffer
+        .get(offset..offset + length)
+        .ok_or(ReadError::Incomplete)
+}
+
+/// Reads the length of an array or a list, which is a signed 32-bit integer.
+fn read_length(buffer: &[u8], offset: usize) -> Result<usize, ReadError> {
+    let (length, _) = i32::read(take(buffer, offset, 4)?)?;
+    usize::try_from(length).map_err(|_| ReadError::MalformedBuffer)
+}
+
+/// Returns the length of the payload of the tag with the provided type, which
+/// starts at the provided offset.
+fn payload_length(
+    buffer: &[u8],
+    offset: usize,
+    tag_type: u8,
+    depth: usize,
+) -> Result<usize, ReadError> {
+    if depth > MAX_DEPTH {
+        return Err(ReadError::MalformedBuffer);
+    }
+
+    let length = match tag_type {
+        TAG_END => 0,
+        TAG_BYTE => 1,
+        TAG_SHORT => 2,
+        TAG_INT | TAG_FLOAT => 4,
+        TAG_LONG | TAG_DOUBLE => 8,
+        TAG_BYTE_ARRAY => 4 + read_length(buffer, offset)?,
+        TAG_INT_ARRAY => 4 + read_length(buffer, offset)? * 4,
+        TAG_LONG_ARRAY => 4 + read_length(buffer, offset)? * 8,
+        TAG_STRING => {
+            let (length, _) = u16::read(take(buffer, offset, 2)?)?;
+            2 + length as usize
+        }
+        TAG_LIST => {
+            let element_type = *take(buffer, offset, 1)?.first().unwrap();
+            let count = read_length(buffer, offset + 1)?;
+
+            let mut length = 5;
+            for _ in 0..count {
+                length += payload_length(buffer, offset + length, element_type, depth + 1)?;
+            }
+            length
+        }
+        TAG_COMPOUND => {
+            let mut length = 0;
+            loop {
+                let field_type = *take(buffer, offset + length, 1)?.first().unwrap();
+                length += 1;
+                if field_type == TAG_END {
+                    break;
+                }
+
+                let (name_length, _) = u16::read(take(buffer, offset + length, 2)?)?;
+                length += 2 + name_length as usize;
+                length += payload_length(buffer, offset + length, field_type, depth + 1)?;
+            }
+            length
+        }
+        _ => return Err(ReadError::MalformedBuffer),
+    };
+
+    take(buffer, offset, length)?;
+    Ok(length)
+}
+
+/// Returns the length of the network NBT (the root tag without a name) at the
+/// start of the provided buffer.
+pub fn network_nbt_length(buffer: &[u8]) -> Result<usize, ReadError> {
+    let tag_type = *buffer.first().ok_or(ReadError::Incomplete)?;
+    Ok(1 + payload_length(buffer, 1, tag_type, 0)?)
+}
+
+/// Represents an NBT value sent over the network, kept in its encoded form.
+/// Use `pumpkin_nbt` to (de)serialize the actual value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkNbt(pub Vec<u8>);
+
+impl Readable for NetworkNbt {
+    fn read(buffer: &[u8]) -> Result<(Self, usize), ReadError> {
+        let length = network_nbt_length(buffer)?;
+        Ok((Self(buffer[..length].to_vec()), length))
+    }
+}
+
+impl Writeable for NetworkNbt {
+    fn write(&self) -> Result<Bytes, WriteError> {
+        Ok(Bytes::copy_from_slice(&self.0))
+    }
+}
+
+/// A compound tag - the map of named tags.
+pub type NbtCompound = BTreeMap<String, NbtTag>;
+
+/// Dynamically typed NBT value, for the data which has no fixed structure
+/// (i.e. block entities or chunks of the world files). Use `pumpkin_nbt` for
+/// the data that maps onto Rust types.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NbtTag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    /// List of tags of the same type.
+    List(Vec<NbtTag>),
+    Compound(NbtCompound),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+/// Cursor over the encoded NBT, which keeps track of the read position.
+struct NbtReader<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> NbtReader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], ReadError> {
+        let bytes = take(self.buffer, self.offset, length)?;
+        self.offset += length;
+        Ok(bytes)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], ReadError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn read_length(&mut self) -> Result<usize, ReadError> {
+        let length = read_length(self.buffer, self.offset)?;
+        self.offset += 4;
+        Ok(length)
+    }
+
+    fn read_string(&mut self) -> Result<String, ReadError> {
+        let length = u16::from_be_bytes(self.take_array()?) as usize;
+        // strings are in the modified UTF-8, which matches the regular one
+        // for everything but the null character and the supplementary ones
+        Ok(std::str::from_utf8(self.take(length)?)?.to_string())
+    }
+
+    fn read_payload(&mut self, tag_type: u8, depth: usize) -> Result<NbtTag, ReadError> {
+        if depth > MAX_DEPTH {
+            return Err(ReadError::MalformedBuffer);
+        }
+
+        Ok(match tag_type {
+            TAG_BYTE => NbtTag::Byte(self.take_array::<1>()?[0] as i8),
+            TAG_SHORT => NbtTag::Short(i16::from_be_bytes(self.take_array()?)),
+            TAG_INT => NbtTag::Int(i32::from_be_bytes(self.take_array()?)),
+            TAG_LONG => NbtTag::Long(i64::from_be_bytes(self.take_array()?)),
+            TAG_FLOAT => NbtTag::Float(f32::from_be_bytes(self.take_array()?)),
+            TAG_DOUBLE => NbtTag::Double(f64::from_be_bytes(self.take_array()?)),
+            TAG_BYTE_ARRAY => {
+                let length = self.read_length()?;
+                NbtTag::ByteArray(self.take(length)?.iter().map(|&b| b as i8).collect())
+            }
+            TAG_STRING => NbtTag::String(self.read_string()?),
+            TAG_LIST => {
+                let element_type = self.take_array::<1>()?[0];
+                let length = self.read_length()?;
+                let mut elements = Vec::with_capacity(length.min(self.buffer.len()));
+                for _ in 0..length {
+                    elements.push(self.read_payload(element_type, depth + 1)?);
+                }
+                NbtTag::List(elements)
+            }
+            TAG_COMPOUND => {
+                let mut compound = NbtCompound::new();
+                loop {
+                    let field_type = self.take_array::<1>()?[0];
+                    if field_type == TAG_END {
+                        break;
+                    }
+                    let name = self.read_string()?;
+                    compound.insert(name, self.read_payload(field_type, depth + 1)?);
+                }
+                NbtTag::Compound(compound)
+            }
+            TAG_INT_ARRAY => {
+                let length = self.read_length()?;
+                let bytes = self.take(length * 4)?;
+                NbtTag::IntArray(
+                    bytes
+                        .chunks_exact(4)
+                        .map(|b| i32::from_be_bytes(b.try_into().unwrap()))
+                        .collect(),
+                )
+            }
+            TAG_LONG_ARRAY => {
+                let length = self.read_length()?;
+                let bytes = self.take(length * 8)?;
+                NbtTag::LongArray(
+                    bytes
+                        .chunks_exact(8)
+                        .map(|b| i64::from_be_bytes(b.try_into().unwrap()))
+                        .collect(),
+                )
+            }
+            _ => return Err(ReadError::MalformedBuffer),
+        })
+    }
+}
+
+impl NbtTag {
+    /// Returns the type ID of this tag.
+    pub fn tag_type(&self) -> u8 {
+        match self {
+            NbtTag::Byte(_) => TAG_BYTE,
+            NbtTag::Short(_) => TAG_SHORT,
+            NbtTag::Int(_) => TAG_INT,
+            NbtTag::Long(_) => TAG_LONG,
+            NbtTag::Float(_) => TAG_FLOAT,
+            NbtTag::Double(_) => TAG_DOUBLE,
+            NbtTag::ByteArray(_) => TAG_BYTE_ARRAY,
+            NbtTag::String(_) => TAG_STRING,
+            NbtTag::List(_) => TAG_LIST,
+            NbtTag::Compound(_) => TAG_COMPOUND,
+            NbtTag::IntArray(_) => TAG_INT_ARRAY,
+            NbtTag::LongArray(_) => TAG_LONG_ARRAY,
+        }
+    }
+
+    /// Reads the root tag with a name, as it's stored in files. Returns the
+    /// name, the tag and the amount of read bytes.
+    pub fn read_named(buffer: &[u8]) -> Result<(String, Self, usize), ReadError> {
+        let mut reader = NbtReader { buffer, offset: 0 };
+        let tag_type = reader.take_array::<1>()?[0];
+        let name = reader.read_string()?;
+        let tag = reader.read_payload(tag_type, 0)?;
+        Ok((name, tag, reader.offset))
+    }
+
+    /// Reads the root tag without a name, as it's sent over the network.
+    pub fn read_nameless(buffer: &[u8]) -> Result<(Self, usize), ReadError> {
+        let mut reader = NbtReader { buffer, offset: 0 };
+        let tag_type = reader.take_array::<1>()?[0];
+        let tag = reader.read_payload(tag_type, 0)?;
+        Ok((tag, reader.offset))
+    }
+
+    fn write_string(value: &str, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        buffer.extend_from_slice(value.as_bytes());
+    }
+
+    fn write_payload(&self, buffer: &mut Vec<u8>) {
+        match self {
+            NbtTag::Byte(value) => buffer.push(*value as u8),
+            NbtTag::Short(value) => buffer.extend_from_slice(&value.to_be_bytes()),
+            NbtTag::Int(value) => buffer.extend_from_slice(&value.to_be_bytes()),
+            NbtTag::Long(value) => buffer.extend_from_slice(&value.to_be_bytes()),
+            NbtTag::Float(value) => buffer.extend_from_slice(&value.to_be_bytes()),
+            NbtTag::Double(value) => buffer.extend_from_slice(&value.to_be_bytes()),
+            NbtTag::ByteArray(values) => {
+                buffer.extend_from_slice(&(values.len() as i32).to_be_bytes());
+                buffer.extend(values.iter().map(|&value| value as u8));
+            }
+            NbtTag::String(value) => Self::write_string(value, buffer),
+            NbtTag::List(elements) => {
+                buffer.push(elements.first().map_or(TAG_END, NbtTag::tag_type));
+                buffer.extend_from_slice(&(elements.len() as i32).to_be_bytes());
+                for element in elements {
+                    element.write_payload(buffer);
+                }
+            }
+            NbtTag::Compound(compound) => {
+                for (name, tag) in compound {
+                    buffer.push(tag.tag_type());
+                    Self::write_string(name, buffer);
+                    tag.write_payload(buffer);
+                }
+                buffer.push(TAG_END);
+            }
+            NbtTag::IntArray(values) => {
+                buffer.extend_from_slice(&(values.len() as i32).to_be_bytes());
+                for value in values {
+                    buffer.extend_from_slice(&value.to_be_bytes());
+                }
+            }
+            NbtTag::LongArray(values) => {
+                buffer.extend_from_slice(&(values.len() as i32).to_be_bytes());
+                for value in values {
+                    buffer.extend_from_slice(&value.to_be_bytes());
+                }
+            }
+        }
+    }
+
+    /// Encodes this tag as the root one with the provided name, as it's
+    /// stored in files.
+    pub fn to_named_bytes(&self, name: &str) -> Vec<u8> {
+        let mut buffer = vec![self.tag_type()];
+        Self::write_string(name, &mut buffer);
+        self.write_payload(&mut buffer);
+        buffer
+    }
+
+    /// Encodes this tag as the root one without a name, as it's sent over
+    /// the network.
+    pub fn to_nameless_bytes(&self) -> Vec<u8> {
+        let mut buffer = vec![self.tag_type()];
+        self.write_payload(&mut buffer);
+        buffer
+    }
+
+    pub fn as_compound(&self) -> Option<&NbtCompound> {
+        match self {
+            NbtTag::Compound(compound) => Some(compound),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[NbtTag]> {
+        match self {
+            NbtTag::List(elements) => Some(elements),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            NbtTag::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of any integer tag, widened to `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            NbtTag::Byte(value) => Some(*value as i64),
+            NbtTag::Short(value) => Some(*value as i64),
+            NbtTag::Int(value) => Some(*value as i64),
+            NbtTag::Long(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_byte_array(&self) -> Option<&[i8]> {
+        match self {
+            NbtTag::ByteArray(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_int_array(&self) -> Option<&[i32]> {
+        match self {
+            NbtTag::IntArray(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_long_array(&self) -> Option<&[i64]> {
+        match self {
+            NbtTag::LongArray(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_nbt_length() {
+        #[rustfmt::skip]
+        let nbt = [
+            TAG_COMPOUND,
+            TAG_STRING, 0, 1, b'a', 0, 2, b'h', b'i',
+            TAG_LIST, 0, 1, b'b', TAG_INT, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2,
+            TAG_COMPOUND, 0, 1, b'c', TAG_BYTE, 0, 1, b'd', 1, TAG_END,
+            TAG_END,
+            0xFF, // trailing data that doesn't belong to NBT
+        ];
+        assert_eq!(network_nbt_length(&nbt).unwrap(), nbt.len() - 1);
+        assert!(matches!(
+            network_nbt_length(&nbt[..10]),
+            Err(ReadError::Incomplete)
+        ));
+        assert_eq!(network_nbt_length(&[TAG_END]).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_nbt_tag_round_trip() {
+        let compound = NbtCompound::from([
+            (String::from("name"), NbtTag::String(String::from("kasumi"))),
+            (String::from("heights"), NbtTag::LongArray(vec![1, -2, 3])),
+            (
+                String::from("list"),
+                NbtTag::List(vec![NbtTag::Short(4), NbtTag::Short(5)]),
+            ),
+            (String::from("empty"), NbtTag::List(vec![])),
+            (
+                String::from("nested"),
+                NbtTag::Compound(NbtCompound::from([(
+                    String::from("bytes"),
+                    NbtTag::ByteArray(vec![-1, 0, 1]),
+                )])),
+            ),
+        ]);
+        let tag = NbtTag::Compound(compound);
+
+        let named = tag.to_named_bytes("root");
+        let (name, read_tag, read_length) = NbtTag::read_named(&named).unwrap();
+        assert_eq!((name.as_str(), read_length), ("root", named.len()));
+        assert_eq!(read_tag, tag);
+
+        let nameless = tag.to_nameless_bytes();
+        assert_eq!(network_nbt_length(&nameless).unwrap(), nameless.len());
+        assert_eq!(NbtTag::read_nameless(&nameless).unwrap().0, tag);
+    }
+}
@@ -16,14 +16,15 @@ pub fn setup_registry(registry: &mut PacketsRegistry) {
 }
 
 /// Represents a single player game property from a game profile sent in
-/// `LoginSuccess` packet.
+/// `LoginSuccess` packet, i.e. the skin of the player.
 #[derive(Debug, Clone)]
 pub struct Property {
     /// Name of this property. Must be unique.
     pub name: String,
     /// The value of this property.
     pub value: String,
-    // TODO: signatures
+    /// Signature of the value by Mojang, if the property is signed.
+    pub signature: Option<String>,
 }
 
 impl Readable for Property {
@@ -31,7 +32,18 @@ impl Readable for Property {
         let mut reader = BufferReader::new(buffer);
         let name = reader.read(String::read)?;
         let value = reader.read(String::read)?;
-        Ok((Self { name, value }, reader.consumed()))
+        let signature = match reader.read(bool::read)? {
+            true => Some(reader.read(String::read)?),
+            false => None,
+        };
+        Ok((
+            Self {
+                name,
+                value,
+                signature,
+            },
+            reader.consumed(),
+        ))
     }
 }
 
@@ -40,6 +52,8 @@ impl Writeable for Property {
         let mut buffer = BytesMut::new();
         buffer.extend_from_slice(&self.name.write()?);
         buffer.extend_from_slice(&self.value.write()?);
+        buffer.extend_from_slice(&self.signature.is_some().write()?);
+        buffer.extend_from_slice(&self.signature.write()?);
         Ok(buffer.freeze())
     }
 }
@@ -1,26 +1,132 @@
+use std::any::Any;
+
+use bitflags::bitflags;
 use bytes::BytesMut;
+use uuid::Uuid;
 
 use crate::{
-    define_packet,
+    chat::{ChatSessionData, LAST_SEEN_BYTES, LastSeenMessages, MessageSignature},
+    define_clientbound_packet, define_packet,
+    entity::{equipment::Equipment, metadata::Metadata},
     network::BufferReader,
     protocol::{
-        BitSet, PrefixedArray, Readable, Writeable, identifier::Identifier,
+        Angle, BitSet, PrefixedArray, PrefixedOption, ProtocolState, Readable, Writeable,
+        identifier::Identifier,
+        nbt::{NbtTag, NetworkNbt},
+        packets::{
+            Packet,
+            configuration::{
+                ClientInformationChatMode, ClientInformationMainHand,
+                ClientInformationParticleStatus, ServerboundClientInformationPacket,
+            },
+            login::Property,
+        },
         registry::PacketsRegistry,
+        text::TextComponent,
     },
     register_packet,
-    varint::VarInt,
-    world::{Chunk, Heightmap},
+    varint::{VarInt, VarLong},
+    world::{
+        Chunk,
+        block_entity::BlockEntity,
+        heightmap::{Heightmap, PackedHeightmap},
+        position::{BlockPos, SectionPos},
+    },
 };
 
 /// Setups the registry for this packets set and protocol state. Only
 /// serverbound packets are registered, through.
 pub fn setup_registry(registry: &mut PacketsRegistry) {
     register_packet!(registry, ServerboundConfirmTeleportationPacket);
+    register_packet!(registry, ServerboundMessageAcknowledgmentPacket);
+    register_packet!(registry, ServerboundChatMessagePacket);
+    register_packet!(registry, ServerboundPlayerSessionPacket);
+    register_packet!(registry, ServerboundKeepAlivePacket);
+    register_packet!(registry, ServerboundChunkBatchReceivedPacket);
+    register_packet!(registry, ServerboundPlayClientInformationPacket);
+    register_packet!(registry, ServerboundSetPlayerPositionPacket);
+    register_packet!(registry, ServerboundSetPlayerPositionAndRotationPacket);
+    register_packet!(registry, ServerboundSetPlayerRotationPacket);
+    register_packet!(registry, ServerboundSetPlayerMovementFlagsPacket);
 }
 
 define_packet!(ServerboundConfirmTeleportationPacket, 0x00, Play, {
     teleport_id: VarInt
 });
+define_packet!(ServerboundMessageAcknowledgmentPacket, 0x05, Play, {
+    offset: VarInt,
+});
+define_packet!(ServerboundChatMessagePacket, 0x08, Play, {
+    message: String,
+    timestamp: i64,
+    salt: i64,
+    signature: PrefixedOption<MessageSignature>,
+    // offset of the window of the last seen messages
+    message_count: VarInt,
+    // fixed bit set of the messages of the window the client has seen
+    acknowledged: [u8; LAST_SEEN_BYTES],
+    checksum: u8,
+});
+define_packet!(ServerboundPlayerSessionPacket, 0x09, Play, {
+    session: ChatSessionData,
+});
+define_packet!(ServerboundChunkBatchReceivedPacket, 0x0A, Play, {
+    chunks_per_tick: f32,
+});
+// The same as `configuration::ServerboundClientInformationPacket`, but sent
+// when the settings are changed during the game.
+define_packet!(ServerboundPlayClientInformationPacket, 0x0D, Play, {
+    locale: String,
+    view_distance: u8,
+    chat_mode: ClientInformationChatMode,
+    is_chat_colors: bool,
+    displayed_skin_parts: u8,
+    main_hand: ClientInformationMainHand,
+    enable_text_filtering: bool,
+    allow_server_listings: bool,
+    particle_status: ClientInformationParticleStatus,
+});
+impl From<ServerboundPlayClientInformationPacket> for ServerboundClientInformationPacket {
+    fn from(packet: ServerboundPlayClientInformationPacket) -> Self {
+        Self {
+            locale: packet.locale,
+            view_distance: packet.view_distance,
+            chat_mode: packet.chat_mode,
+            is_chat_colors: packet.is_chat_colors,
+            displayed_skin_parts: packet.displayed_skin_parts,
+            main_hand: packet.main_hand,
+            enable_text_filtering: packet.enable_text_filtering,
+            allow_server_listings: packet.allow_server_listings,
+            particle_status: packet.particle_status,
+        }
+    }
+}
+
+define_packet!(ServerboundKeepAlivePacket, 0x1B, Play, {
+    id: i64,
+});
+define_packet!(ServerboundSetPlayerPositionPacket, 0x1D, Play, {
+    x: f64,
+    y: f64,
+    z: f64,
+    flags: u8, // on ground (0x01), pushing against a wall (0x02)
+});
+define_packet!(ServerboundSetPlayerPositionAndRotationPacket, 0x1E, Play, {
+    x: f64,
+    y: f64,
+    z: f64,
+    yaw: f32,
+    pitch: f32,
+    flags: u8,
+});
+define_packet!(ServerboundSetPlayerRotationPacket, 0x1F, Play, {
+    yaw: f32,
+    pitch: f32,
+    flags: u8,
+});
+define_packet!(ServerboundSetPlayerMovementFlagsPacket, 0x20, Play, {
+    flags: u8,
+});
 
 define_packet!(ClientboundPlayPacket, 0x2B, Play, {
     entity_id: i32,
@@ -41,11 +147,26 @@ define_packet!(ClientboundPlayPacket, 0x2B, Play, {
     is_flat: bool,
     has_death_location: bool,
     death_dimension_name: Option<Identifier>,
-    death_location: Option<i64>,
+    death_location: Option<BlockPos>,
     portal_cooldown: VarInt,
     sea_level: VarInt,
     enforces_secure_chat: bool,
 });
+define_packet!(ClientboundRespawnPacket, 0x4B, Play, {
+    dimension_type: VarInt,
+    dimension_name: Identifier,
+    hashed_seed: i64,
+    game_mode: u8,
+    previous_game_mode: i8,
+    is_debug: bool,
+    is_flat: bool,
+    has_death_location: bool,
+    death_dimension_name: Option<Identifier>,
+    death_location: Option<BlockPos>,
+    portal_cooldown: VarInt,
+    sea_level: VarInt,
+    data_kept: u8,
+});
 define_packet!(ClientboundSynchronizePlayerPositionPacket, 0x41, Play, {
     teleport_id: VarInt,
     x: f64,
@@ -56,29 +177,408 @@ define_packet!(ClientboundSynchronizePlayerPositionPacket, 0x41, Play, {
     velocity_z: f64,
     yaw: f32,
     pitch: f32,
-    flags: i32,
+    flags: TeleportFlags,
 });
+
+bitflags! {
+    /// Fields of the teleport that are relative to the current values on the
+    /// client, instead of being absolute.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct TeleportFlags: i32 {
+        const X = 0x0001;
+        const Y = 0x0002;
+        const Z = 0x0004;
+        const YAW = 0x0008;
+        const PITCH = 0x0010;
+        const VELOCITY_X = 0x0020;
+        const VELOCITY_Y = 0x0040;
+        const VELOCITY_Z = 0x0080;
+        /// Rotates the velocity by the change of the rotation.
+        const ROTATE_VELOCITY = 0x0100;
+
+        const POSITION = Self::X.bits() | Self::Y.bits() | Self::Z.bits();
+        const ROTATION = Self::YAW.bits() | Self::PITCH.bits();
+        const VELOCITY =
+            Self::VELOCITY_X.bits() | Self::VELOCITY_Y.bits() | Self::VELOCITY_Z.bits();
+    }
+}
+
+impl Readable for TeleportFlags {
+    fn read(buffer: &[u8]) -> Result<(Self, usize), crate::protocol::ReadError> {
+        let (bits, read_length) = i32::read(buffer)?;
+        Ok((Self::from_bits_retain(bits), read_length))
+    }
+}
+
+impl Writeable for TeleportFlags {
+    fn write(&self) -> Result<bytes::Bytes, crate::protocol::WriteError> {
+        self.bits().write()
+    }
+}
 define_packet!(ClientboundGameEventPacket, 0x22, Play, {
     event: u8,
     value: f32,
 });
+define_packet!(ClientboundSetCenterChunkPacket, 0x57, Play, {
+    chunk_x: VarInt,
+    chunk_z: VarInt,
+});
+define_packet!(ClientboundUnloadChunkPacket, 0x21, Play, {
+    chunk_z: i32,
+    chunk_x: i32,
+});
+define_packet!(ClientboundBlockUpdatePacket, 0x08, Play, {
+    position: BlockPos,
+    block_id: VarInt,
+});
+define_packet!(ClientboundUpdateSectionBlocksPacket, 0x4D, Play, {
+    section_position: SectionPos,
+    // state ID << 12 | local X << 8 | local Z << 4 | local Y
+    blocks: PrefixedArray<VarLong>,
+});
+define_packet!(ClientboundUpdateLightPacket, 0x2A, Play, {
+    chunk_x: VarInt,
+    chunk_z: VarInt,
+    light_data: LightData,
+});
+define_packet!(ClientboundUpdateTimePacket, 0x6A, Play, {
+    world_age: i64,
+    time_of_day: i64,
+    time_of_day_increasing: bool,
+});
+define_packet!(ClientboundKeepAlivePacket, 0x26, Play, {
+    id: i64,
+});
+define_packet!(ClientboundChunkBatchStartPacket, 0x0C, Play, {});
+define_packet!(ClientboundChunkBatchFinishedPacket, 0x0B, Play, {
+    batch_size: VarInt,
+});
+
+define_packet!(ClientboundSpawnEntityPacket, 0x01, Play, {
+    entity_id: VarInt,
+    uuid: Uuid,
+    entity_type: VarInt,
+    x: f64,
+    y: f64,
+    z: f64,
+    pitch: Angle,
+    yaw: Angle,
+    head_yaw: Angle,
+    data: VarInt,
+    // in 1/8000 of a block per tick
+    velocity_x: i16,
+    velocity_y: i16,
+    velocity_z: i16,
+});
+// Deltas are in 1/4096 of a block.
+define_packet!(ClientboundUpdateEntityPositionPacket, 0x2E, Play, {
+    entity_id: VarInt,
+    delta_x: i16,
+    delta_y: i16,
+    delta_z: i16,
+    on_ground: bool,
+});
+define_packet!(ClientboundUpdateEntityPositionAndRotationPacket, 0x2F, Play, {
+    entity_id: VarInt,
+    delta_x: i16,
+    delta_y: i16,
+    delta_z: i16,
+    yaw: Angle,
+    pitch: Angle,
+    on_ground: bool,
+});
+define_packet!(ClientboundUpdateEntityRotationPacket, 0x31, Play, {
+    entity_id: VarInt,
+    yaw: Angle,
+    pitch: Angle,
+    on_ground: bool,
+});
+define_packet!(ClientboundSetHeadRotationPacket, 0x4C, Play, {
+    entity_id: VarInt,
+    head_yaw: Angle,
+});
+define_packet!(ClientboundTeleportEntityPacket, 0x76, Play, {
+    entity_id: VarInt,
+    x: f64,
+    y: f64,
+    z: f64,
+    velocity_x: f64,
+    velocity_y: f64,
+    velocity_z: f64,
+    yaw: f32,
+    pitch: f32,
+    flags: TeleportFlags,
+    on_ground: bool,
+});
+// an unsigned player message, decorated by the client with the chat type
+define_clientbound_packet!(ClientboundDisguisedChatPacket, 0x1C, Play, {
+    message: TextComponent,
+    // protocol ID of the chat type plus one, as 0 is an inline chat type
+    chat_type: VarInt,
+    sender_name: TextComponent,
+    target_name: PrefixedOption<TextComponent>,
+});
+define_clientbound_packet!(ClientboundDisconnectPacket, 0x1D, Play, {
+    reason: TextComponent,
+});
+define_clientbound_packet!(ClientboundPlayerChatPacket, 0x3A, Play, {
+    // index among all the player chats sent to the client
+    global_index: VarInt,
+    sender: Uuid,
+    // index in the chain of the messages of the sender
+    index: VarInt,
+    signature: PrefixedOption<MessageSignature>,
+    message: String,
+    timestamp: i64,
+    salt: i64,
+    last_seen: LastSeenMessages,
+    unsigned_content: PrefixedOption<TextComponent>,
+    // 0 means that the message isn't filtered
+    filter_type: VarInt,
+    // protocol ID of the chat type plus one, as 0 is an inline chat type
+    chat_type: VarInt,
+    sender_name: TextComponent,
+    target_name: PrefixedOption<TextComponent>,
+});
+define_clientbound_packet!(ClientboundSystemChatPacket, 0x72, Play, {
+    content: TextComponent,
+    // shows the message above the hotbar instead of in the chat
+    overlay: bool,
+});
+define_clientbound_packet!(ClientboundSetEntityMetadataPacket, 0x5C, Play, {
+    entity_id: VarInt,
+    metadata: Metadata,
+});
+// in 1/8000 of a block per tick
+define_packet!(ClientboundSetEntityVelocityPacket, 0x5E, Play, {
+    entity_id: VarInt,
+    velocity_x: i16,
+    velocity_y: i16,
+    velocity_z: i16,
+});
+define_clientbound_packet!(ClientboundSetEquipmentPacket, 0x5F, Play, {
+    entity_id: VarInt,
+    equipment: Equipment,
+});
+define_packet!(ClientboundRemoveEntitiesPacket, 0x46, Play, {
+    entity_ids: PrefixedArray<VarInt>,
+});
+define_packet!(ClientboundPlayerInfoRemovePacket, 0x3E, Play, {
+    uuids: PrefixedArray<Uuid>,
+});
+
+bitflags! {
+    /// Actions of the player info update, which define the fields that are
+    /// sent for each of the players.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PlayerInfoActions: u8 {
+        const ADD_PLAYER = 0x01;
+        const INITIALIZE_CHAT = 0x02;
+        const UPDATE_GAME_MODE = 0x04;
+        const UPDATE_LISTED = 0x08;
+        const UPDATE_LATENCY = 0x10;
+        const UPDATE_DISPLAY_NAME = 0x20;
+        const UPDATE_LIST_PRIORITY = 0x40;
+        const UPDATE_HAT = 0x80;
+    }
+}
+
+/// A player in the tab list. Only the fields of the actions of the packet
+/// are sent.
+#[derive(Debug, Clone)]
+pub struct PlayerInfoEntry {
+    pub uuid: Uuid,
+    pub name: String,
+    /// Properties of the profile, i.e. the skin.
+    pub properties: PrefixedArray<Property>,
+    pub game_mode: VarInt,
+    /// Whether the player is shown in the tab list.
+    pub listed: bool,
+    /// Latency of the player in milliseconds.
+    pub latency: VarInt,
+    /// Position in the tab list, where the higher ones go first.
+    pub list_priority: VarInt,
+    /// Whether the hat layer of the skin is shown.
+    pub show_hat: bool,
+    /// Chat session the messages of the player are verified with.
+    pub chat_session: PrefixedOption<ChatSessionData>,
+}
+
+/// Adds the players to the tab list or updates their entries. It's encoded
+/// by hand, as the fields of the entries depend on the actions.
+#[derive(Debug, Clone)]
+pub struct ClientboundPlayerInfoUpdatePacket {
+    pub actions: PlayerInfoActions,
+    pub entries: Vec<PlayerInfoEntry>,
+}
+
+impl ClientboundPlayerInfoUpdatePacket {
+    /// ID of this packet. May not be unique between multiple protocol
+    /// states.
+    pub const PACKET_ID: VarInt = VarInt(0x3F);
+
+    /// State that this packet is designed for.
+    pub const PACKET_STATE: ProtocolState = ProtocolState::Play;
+}
+
+impl Writeable for ClientboundPlayerInfoUpdatePacket {
+    fn write(&self) -> Result<bytes::Bytes, crate::protocol::WriteError> {
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&self.actions.bits().write()?);
+        buffer.extend_from_slice(&VarInt(self.entries.len() as i32).write()?);
+        for entry in &self.entries {
+            buffer.extend_from_slice(&entry.uuid.write()?);
+            if self.actions.contains(PlayerInfoActions::ADD_PLAYER) {
+                buffer.extend_from_slice(&entry.name.write()?);
+                buffer.extend_from_slice(&entry.properties.write()?);
+            }
+            if self.actions.contains(PlayerInfoActions::INITIALIZE_CHAT) {
+                buffer.extend_from_slice(&entry.chat_session.write()?);
+            }
+            if self.actions.contains(PlayerInfoActions::UPDATE_GAME_MODE) {
+                buffer.extend_from_slice(&entry.game_mode.write()?);
+            }
+            if self.actions.contains(PlayerInfoActions::UPDATE_LISTED) {
+                buffer.extend_from_slice(&entry.listed.write()?);
+            }
+            if self.actions.contains(PlayerInfoActions::UPDATE_LATENCY) {
+                buffer.extend_from_slice(&entry.latency.write()?);
+            }
+            if self
+                .actions
+                .contains(PlayerInfoActions::UPDATE_DISPLAY_NAME)
+            {
+                // no display name, so the name is shown
+                buffer.extend_from_slice(&false.write()?);
+            }
+            if self
+                .actions
+                .contains(PlayerInfoActions::UPDATE_LIST_PRIORITY)
+            {
+                buffer.extend_from_slice(&entry.list_priority.write()?);
+            }
+            if self.actions.contains(PlayerInfoActions::UPDATE_HAT) {
+                buffer.extend_from_slice(&entry.show_hat.write()?);
+            }
+        }
+        Ok(buffer.freeze())
+    }
+}
+
+impl Packet for ClientboundPlayerInfoUpdatePacket {
+    fn id(&self) -> VarInt {
+        Self::PACKET_ID
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+define_packet!(ClientboundBlockEntityDataPacket, 0x06, Play, {
+    position: BlockPos,
+    type_id: VarInt,
+    data: NetworkNbt,
+});
+
+impl ClientboundChunkDataAndLightPacket {
+    /// Creates the packet with the contents and light of the chunk. `min_y`
+    /// is the lowest Y coordinate of the world.
+    pub fn from_chunk(chunk: &Chunk, min_y: i32) -> Self {
+        Self {
+            chunk_x: chunk.x,
+            chunk_z: chunk.z,
+            chunk_data: ChunkData {
+                heightmap: chunk.client_heightmaps(),
+                data: chunk.clone(),
+                block_entities: PrefixedArray(
+                    chunk
+                        .block_entities
+                        .iter()
+                        .filter_map(|block_entity| ChunkBlockEntity::new(block_entity, min_y))
+                        .collect(),
+                ),
+            },
+            light_data: chunk.light.light_data(),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct ChunkData {
-    pub heightmap: PrefixedArray<Heightmap>,
+    pub heightmap: Vec<Heightmap>,
     pub data: Chunk,
-    pub block_entities: PrefixedArray<u8>,
+    pub block_entities: PrefixedArray<ChunkBlockEntity>,
+}
+
+/// A block entity within the chunk data.
+#[derive(Debug, Clone)]
+pub struct ChunkBlockEntity {
+    /// Coordinates of the block within the chunk: X << 4 | Z.
+    pub packed_xz: u8,
+    /// Absolute Y coordinate of the block.
+    pub y: i16,
+    pub type_id: VarInt,
+    pub data: NetworkNbt,
+}
+
+impl ChunkBlockEntity {
+    /// Creates the entry of the block entity, if its type is known to the
+    /// clients. `min_y` is the lowest Y coordinate of the world.
+    pub fn new(block_entity: &BlockEntity, min_y: i32) -> Option<Self> {
+        Some(Self {
+            packed_xz: (block_entity.x << 4 | block_entity.z) as u8,
+            y: (min_y + block_entity.y as i32) as i16,
+            type_id: block_entity.type_id()?,
+            data: NetworkNbt(NbtTag::Compound(block_entity.client_data()).to_nameless_bytes()),
+        })
+    }
+}
+
+impl Readable for ChunkBlockEntity {
+    fn read(buffer: &[u8]) -> Result<(Self, usize), crate::protocol::ReadError> {
+        let mut reader = BufferReader::new(buffer);
+        let packed_xz = reader.read(u8::read)?;
+        let y = reader.read(i16::read)?;
+        let type_id = reader.read(VarInt::read)?;
+        let data = reader.read(NetworkNbt::read)?;
+        Ok((
+            Self {
+                packed_xz,
+                y,
+                type_id,
+                data,
+            },
+            reader.consumed(),
+        ))
+    }
+}
+
+impl Writeable for ChunkBlockEntity {
+    fn write(&self) -> Result<bytes::Bytes, crate::protocol::WriteError> {
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&self.packed_xz.write()?);
+        buffer.extend_from_slice(&self.y.write()?);
+        buffer.extend_from_slice(&self.type_id.write()?);
+        buffer.extend_from_slice(&self.data.write()?);
+        Ok(buffer.freeze())
+    }
 }
 
 impl Readable for ChunkData {
     fn read(buffer: &[u8]) -> Result<(Self, usize), crate::protocol::ReadError> {
         let mut reader = BufferReader::new(buffer);
-        let heightmap = reader.read(PrefixedArray::read)?;
+        // heightmaps are unpacked once the height of the chunk is known
+        let heightmap: PrefixedArray<PackedHeightmap> = reader.read(PrefixedArray::read)?;
         let data = reader.read(Chunk::read)?;
         let block_entities = reader.read(PrefixedArray::read)?;
         Ok((
             Self {
-                heightmap,
+                heightmap: heightmap
+                    .0
+                    .iter()
+                    .map(|heightmap| heightmap.unpack(data.height()))
+                    .collect(),
                 data,
                 block_entities,
             },
@@ -90,7 +590,10 @@ impl Readable for ChunkData {
 impl Writeable for ChunkData {
     fn write(&self) -> Result<bytes::Bytes, crate::protocol::WriteError> {
         let mut buffer = BytesMut::new();
-        buffer.extend_from_slice(&self.heightmap.write()?);
+        buffer.extend_from_slice(&VarInt(self.heightmap.len() as i32).write()?);
+        for heightmap in &self.heightmap {
+            buffer.extend_from_slice(&heightmap.write()?);
+        }
         buffer.extend_from_slice(&self.data.write()?);
         buffer.extend_from_slice(&self.block_entities.write()?);
         Ok(buffer.freeze())
@@ -103,8 +606,8 @@ pub struct LightData {
     pub block_light_mask: BitSet,
     pub empty_sky_light_mask: BitSet,
     pub empty_block_light_mask: BitSet,
-    pub sky_lights: PrefixedArray<Vec<u8>>,
-    pub block_lights: PrefixedArray<Vec<u8>>,
+    pub sky_lights: PrefixedArray<PrefixedArray<u8>>,
+    pub block_lights: PrefixedArray<PrefixedArray<u8>>,
 }
 
 impl Readable for LightData {
@@ -149,3 +652,33 @@ define_packet!(ClientboundChunkDataAndLightPacket, 0x27, Play, {
     chunk_data: ChunkData,
     light_data: LightData,
 });
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serverbound_packet_ids() {
+        // the IDs of the supported version (1.21.5), where Change Game Mode
+        // was added as 0x04
+        let ids = [
+            ServerboundConfirmTeleportationPacket::PACKET_ID,
+            ServerboundMessageAcknowledgmentPacket::PACKET_ID,
+            ServerboundChatMessagePacket::PACKET_ID,
+            ServerboundPlayerSessionPacket::PACKET_ID,
+            ServerboundChunkBatchReceivedPacket::PACKET_ID,
+            ServerboundPlayClientInformationPacket::PACKET_ID,
+            ServerboundKeepAlivePacket::PACKET_ID,
+            ServerboundSetPlayerPositionPacket::PACKET_ID,
+            ServerboundSetPlayerPositionAndRotationPacket::PACKET_ID,
+            ServerboundSetPlayerRotationPacket::PACKET_ID,
+            ServerboundSetPlayerMovementFlagsPacket::PACKET_ID,
+        ];
+        assert_eq!(
+            ids.map(|id| id.0),
+            [
+                0x00, 0x05, 0x08, 0x09, 0x0A, 0x0D, 0x1B, 0x1D, 0x1E, 0x1F, 0x20
+            ]
+        );
+    }
+}
@@ -7,8 +7,10 @@ use crate::{
 
 /// Represents the packets' registry, where the value (see
 /// `ProtocolRegistry` documentation) is the decoder function for each packet.
-pub type PacketsRegistry =
-    ProtocolRegistry<Box<dyn Fn(&[u8]) -> Result<(Box<dyn Packet>, usize), ReadError>>>;
+/// Decoders are shared between the threads of the connections.
+pub type PacketsRegistry = ProtocolRegistry<
+    Box<dyn Fn(&[u8]) -> Result<(Box<dyn Packet>, usize), ReadError> + Send + Sync>,
+>;
 
 /// Represents the registry for all packets handlers (see `ProtocolRegistry`)
 /// documentation, where value is the handler function itself.
@@ -1,4 +1,11 @@
+use bytes::Bytes;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::protocol::{
+    WriteError, Writeable,
+    nbt::{NbtCompound, NbtTag},
+};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TextComponentScoreboard {
@@ -163,9 +170,81 @@ pub struct TextComponent {
     pub hover_event: Option<TextComponentHoverEvent>,
 }
 
+impl TextComponent {
+    /// Creates a plain text component without any formatting.
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            kind: TextComponentKind::Text { text: text.into() },
+            extra: None,
+            color: None,
+            font: None,
+            bold: None,
+            italic: None,
+            underlined: None,
+            strikethrough: None,
+            obfuscated: None,
+            shadow_color: None,
+            insertion: None,
+            click_event: None,
+            hover_event: None,
+        }
+    }
+
+    /// Creates a component translated by the client, where the arguments
+    /// fill the placeholders of the translation.
+    pub fn translatable(key: impl Into<String>, with: Vec<TextComponent>) -> Self {
+        let key = key.into();
+        Self {
+            kind: TextComponentKind::Translatable {
+                fallback: key.clone(),
+                translate: key,
+                with,
+            },
+            ..Self::text("")
+        }
+    }
+
+    /// Converts the component into NBT, which is how it's sent in the play
+    /// state. The structure is the same as the one of the JSON.
+    pub fn to_nbt(&self) -> NbtTag {
+        json_to_nbt(serde_json::to_value(self).unwrap())
+            .unwrap_or(NbtTag::Compound(NbtCompound::new()))
+    }
+}
+
+/// Converts the JSON value into the NBT with the same structure. Returns
+/// `None` for the null values, which are left out.
+fn json_to_nbt(value: Value) -> Option<NbtTag> {
+    Some(match value {
+        Value::Null => return None,
+        Value::Bool(value) => NbtTag::Byte(value as i8),
+        Value::Number(number) => match number.as_i64() {
+            Some(value) => match i32::try_from(value) {
+                Ok(value) => NbtTag::Int(value),
+                Err(_) => NbtTag::Long(value),
+            },
+            None => NbtTag::Double(number.as_f64().unwrap_or_default()),
+        },
+        Value::String(value) => NbtTag::String(value),
+        Value::Array(values) => NbtTag::List(values.into_iter().filter_map(json_to_nbt).collect()),
+        Value::Object(entries) => NbtTag::Compound(
+            entries
+                .into_iter()
+                .filter_map(|(name, value)| Some((name, json_to_nbt(value)?)))
+                .collect(),
+        ),
+    })
+}
+
+impl Writeable for TextComponent {
+    fn write(&self) -> Result<Bytes, WriteError> {
+        Ok(Bytes::from(self.to_nbt().to_nameless_bytes()))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::protocol::text::TextComponent;
+    use crate::protocol::{nbt::NbtTag, text::TextComponent};
 
     #[test]
     fn test_component() {
@@ -207,4 +286,17 @@ mod tests {
             serde_json::to_string_pretty(&text_component).unwrap()
         )
     }
+
+    #[test]
+    fn test_component_nbt() {
+        let text_component = TextComponent {
+            bold: Some(true),
+            ..TextComponent::text("Hello")
+        };
+        let nbt = text_component.to_nbt();
+        let compound = nbt.as_compound().unwrap();
+        assert_eq!(compound["text"], NbtTag::String("Hello".to_owned()));
+        assert_eq!(compound["bold"], NbtTag::Byte(1));
+        assert!(!compound.contains_key("italic"));
+    }
 }
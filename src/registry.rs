@@ -1,9 +1,40 @@
-use std::collections::HashMap;
+use std::{
+    collections::BTreeMap,
+    sync::{LazyLock, OnceLock},
+};
 
 use bytes::BytesMut;
 use serde::{Deserialize, Serialize};
 
-use crate::protocol::{PrefixedArray, Readable, Writeable, text::Color};
+use crate::{
+    network::BufferReader,
+    protocol::{PrefixedArray, ReadError, Readable, Writeable, nbt::NetworkNbt, text::Color},
+    varint::VarInt,
+};
+
+/// The global registry, generated from the vanilla data (see `generator.py`).
+/// Entries are sorted by their names, so the protocol ID of an entry is its
+/// index in the registry.
+pub static REGISTRY: LazyLock<Registry> = LazyLock::new(|| {
+    let raw_registry_json = include_str!("../new_registry.json");
+    let mut registry: Registry = serde_json::from_str(raw_registry_json).unwrap();
+    if let Some(dimension_types) = CUSTOM_DIMENSION_TYPES.get() {
+        registry.dimension_type.extend(dimension_types.clone());
+    }
+    registry
+});
+
+/// Dimension types added to the vanilla ones, i.e. from the configuration.
+static CUSTOM_DIMENSION_TYPES: OnceLock<BTreeMap<String, RegistryDimensionType>> = OnceLock::new();
+
+/// Adds the dimension types to the global registry, replacing the vanilla
+/// ones with the same names. It has to be called before the registry is used
+/// for the first time, and only once. Returns whether the types were added.
+pub fn set_custom_dimension_types(
+    dimension_types: BTreeMap<String, RegistryDimensionType>,
+) -> bool {
+    CUSTOM_DIMENSION_TYPES.set(dimension_types).is_ok()
+}
 
 #[derive(Debug)]
 pub struct RegistryData {
@@ -29,8 +60,16 @@ impl RegistryDataEntry {
 }
 
 impl Readable for RegistryDataEntry {
-    fn read(buffer: &[u8]) -> Result<(Self, usize), crate::protocol::ReadError> {
-        todo!()
+    fn read(buffer: &[u8]) -> Result<(Self, usize), ReadError> {
+        let mut reader = BufferReader::new(buffer);
+        let entry_id = reader.read(String::read)?;
+        let has_data = reader.read(bool::read)?;
+        let data = if has_data {
+            Some(reader.read(NetworkNbt::read)?.0.into_boxed_slice())
+        } else {
+            None
+        };
+        Ok((Self { entry_id, data }, reader.consumed()))
     }
 }
 
@@ -41,12 +80,27 @@ impl Writeable for RegistryDataEntry {
 
         buffer.extend_from_slice(&self.data.is_some().write()?);
         if let Some(data) = &self.data {
-            buffer.extend_from_slice(&data);
+            buffer.extend_from_slice(data);
         }
         Ok(buffer.freeze())
     }
 }
 
+impl Readable for RegistryData {
+    fn read(buffer: &[u8]) -> Result<(Self, usize), ReadError> {
+        let mut reader = BufferReader::new(buffer);
+        let registry_id = reader.read(String::read)?;
+        let entries = reader.read(PrefixedArray::read)?;
+        Ok((
+            Self {
+                registry_id,
+                entries,
+            },
+            reader.consumed(),
+        ))
+    }
+}
+
 impl Writeable for RegistryData {
     fn write(&self) -> Result<bytes::Bytes, crate::protocol::WriteError> {
         let mut buffer = BytesMut::new();
@@ -207,7 +261,7 @@ pub struct RegistryPaintingVariant {
     pub author: Option<RegistryPaintingVariantText>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegistryDimensionTypeMonsterSpawnLightLevel {
     #[serde(rename = "type")]
     pub light_level_type: String,
@@ -215,14 +269,14 @@ pub struct RegistryDimensionTypeMonsterSpawnLightLevel {
     pub min_inclusive: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum RegistryDimensionTypeMonsterSpawnLightLevelType {
     Int(i32),
     RegistryDimensionTypeMonsterSpawnLightLevel(RegistryDimensionTypeMonsterSpawnLightLevel),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegistryDimensionType {
     pub ambient_light: f32,
     pub bed_works: bool,
@@ -254,30 +308,91 @@ pub struct RegistryDamageType {
     pub scaling: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegistryChatTypeStyle {
+    /// Name of the color or its hex code, i.e. `gray` or `#808080`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bold: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub italic: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub underlined: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strikethrough: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub obfuscated: Option<bool>,
+}
+
+/// How the client decorates a message of the chat type: the translation
+/// key is filled with the parameters (`sender`, `target` and `content`).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegistryChatTypeDecoration {
+    pub parameters: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<RegistryChatTypeStyle>,
+    pub translation_key: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegistryChatType {
+    pub chat: RegistryChatTypeDecoration,
+    pub narration: RegistryChatTypeDecoration,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Registry {
     #[serde(rename = "minecraft:worldgen/biome")]
-    pub biome: HashMap<String, RegistryBiome>,
+    pub biome: BTreeMap<String, RegistryBiome>,
     #[serde(rename = "minecraft:cat_variant")]
-    pub cat_variant: HashMap<String, RegistryMobVariant>,
+    pub cat_variant: BTreeMap<String, RegistryMobVariant>,
     #[serde(rename = "minecraft:chicken_variant")]
-    pub chicken_variant: HashMap<String, RegistryMobVariant>,
+    pub chicken_variant: BTreeMap<String, RegistryMobVariant>,
     #[serde(rename = "minecraft:cow_variant")]
-    pub cow_variant: HashMap<String, RegistryMobVariant>,
+    pub cow_variant: BTreeMap<String, RegistryMobVariant>,
     #[serde(rename = "minecraft:frog_variant")]
-    pub frog_variant: HashMap<String, RegistryMobVariant>,
+    pub frog_variant: BTreeMap<String, RegistryMobVariant>,
     #[serde(rename = "minecraft:pig_variant")]
-    pub pig_variant: HashMap<String, RegistryMobVariant>,
+    pub pig_variant: BTreeMap<String, RegistryMobVariant>,
     #[serde(rename = "minecraft:wolf_variant")]
-    pub wolf_variant: HashMap<String, RegistryWolfVariant>,
+    pub wolf_variant: BTreeMap<String, RegistryWolfVariant>,
     #[serde(rename = "minecraft:wolf_sound_variant")]
-    pub wolf_sound_variant: HashMap<String, RegistryWolfSoundVariant>,
+    pub wolf_sound_variant: BTreeMap<String, RegistryWolfSoundVariant>,
     #[serde(rename = "minecraft:painting_variant")]
-    pub painting_variant: HashMap<String, RegistryPaintingVariant>,
+    pub painting_variant: BTreeMap<String, RegistryPaintingVariant>,
     #[serde(rename = "minecraft:dimension_type")]
-    pub dimension_type: HashMap<String, RegistryDimensionType>,
+    pub dimension_type: BTreeMap<String, RegistryDimensionType>,
     #[serde(rename = "minecraft:damage_type")]
-    pub damage_type: HashMap<String, RegistryDamageType>,
+    pub damage_type: BTreeMap<String, RegistryDamageType>,
+    #[serde(rename = "minecraft:chat_type")]
+    pub chat_type: BTreeMap<String, RegistryChatType>,
+}
+
+impl Registry {
+    /// Returns the protocol ID of the biome with the provided name.
+    pub fn biome_id(&self, name: &str) -> Option<VarInt> {
+        let index = self.biome.keys().position(|biome| biome == name)?;
+        Some(VarInt(index as i32))
+    }
+
+    /// Returns the name of the biome with the provided protocol ID.
+    pub fn biome_name(&self, id: VarInt) -> Option<&str> {
+        let index = usize::try_from(id.0).ok()?;
+        self.biome.keys().nth(index).map(String::as_str)
+    }
+
+    /// Returns the protocol ID of the dimension type with the provided name.
+    pub fn dimension_type_id(&self, name: &str) -> Option<VarInt> {
+        let index = self.dimension_type.keys().position(|entry| entry == name)?;
+        Some(VarInt(index as i32))
+    }
+
+    /// Returns the protocol ID of the chat type with the provided name.
+    pub fn chat_type_id(&self, name: &str) -> Option<VarInt> {
+        let index = self.chat_type.keys().position(|entry| entry == name)?;
+        Some(VarInt(index as i32))
+    }
 }
 
 #[macro_export]
@@ -320,22 +435,54 @@ generate_registry_builder!(build_wolf_sound_variant, wolf_sound_variant);
 generate_registry_builder!(build_painting_variant, painting_variant);
 generate_registry_builder!(build_dimension_type, dimension_type);
 generate_registry_builder!(build_damage_type, damage_type);
+generate_registry_builder!(build_chat_type, chat_type);
 
 pub fn build_registries_data() -> Result<Vec<RegistryData>, pumpkin_nbt::Error> {
-    let raw_registry_json = include_str!("../new_registry.json");
-    let registry: Registry = serde_json::from_str(raw_registry_json).unwrap();
+    let registry = &*REGISTRY;
 
     Ok(vec![
-        build_biome(&registry),
-        build_cat_variant(&registry),
-        build_chicken_variant(&registry),
-        build_cow_variant(&registry),
-        build_frog_variant(&registry),
-        build_pig_variant(&registry),
-        build_wolf_variant(&registry),
-        build_wolf_sound_variant(&registry),
-        build_painting_variant(&registry),
-        build_dimension_type(&registry),
-        build_damage_type(&registry),
+        build_biome(registry),
+        build_cat_variant(registry),
+        build_chicken_variant(registry),
+        build_cow_variant(registry),
+        build_frog_variant(registry),
+        build_pig_variant(registry),
+        build_wolf_variant(registry),
+        build_wolf_sound_variant(registry),
+        build_painting_variant(registry),
+        build_dimension_type(registry),
+        build_damage_type(registry),
+        build_chat_type(registry),
     ])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_data_round_trip() {
+        let registry_data = build_dimension_type(&REGISTRY);
+        let buffer = registry_data.write().unwrap();
+
+        let (read, read_length) = RegistryData::read(&buffer).unwrap();
+        assert_eq!(read_length, buffer.len());
+        assert_eq!(read.registry_id, "minecraft:dimension_type");
+        assert_eq!(read.entries.0.len(), REGISTRY.dimension_type.len());
+        assert_eq!(read.write().unwrap(), buffer);
+    }
+
+    #[test]
+    fn test_chat_type() {
+        assert_eq!(REGISTRY.chat_type_id("minecraft:chat"), Some(VarInt(0)));
+        assert_eq!(REGISTRY.chat_type_id("minecraft:unknown"), None);
+
+        let outgoing = &REGISTRY.chat_type["minecraft:msg_command_outgoing"];
+        assert_eq!(outgoing.chat.parameters, ["target", "content"]);
+        let style = outgoing.chat.style.as_ref().unwrap();
+        assert_eq!(style.italic, Some(true));
+
+        let chat_type = build_chat_type(&REGISTRY);
+        assert_eq!(chat_type.entries.0.len(), 7);
+    }
+}
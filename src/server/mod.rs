@@ -0,0 +1,326 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicI32, Ordering},
+    },
+};
+
+use rsa::RsaPublicKey;
+use uuid::Uuid;
+
+use crate::{
+    chat::PlayerChatMessage,
+    config::Config,
+    entity::{Entity, EntityType},
+    player::{Location, Player},
+    protocol::{
+        PrefixedArray, PrefixedOption,
+        packets::{
+            Packet,
+            play::{
+                ClientboundDisguisedChatPacket, ClientboundPlayerInfoRemovePacket,
+                ClientboundPlayerInfoUpdatePacket, PlayerInfoActions,
+            },
+        },
+        text::TextComponent,
+    },
+    registry::REGISTRY,
+    tick::{Scheduler, TaskId, TickMetrics},
+    varint::VarInt,
+    world::Worlds,
+};
+
+pub mod tracking;
+
+/// Chat type of the messages the players send to the chat.
+const PLAYER_CHAT_TYPE: &str = "minecraft:chat";
+
+/// State of the server that outlives the connections and is shared between
+/// them: the worlds and the players that are online.
+pub struct Server {
+    pub config: Config,
+    /// All worlds hosted by the server.
+    pub worlds: Arc<Worlds>,
+    /// Scheduler of the tick loop, where the actions of the players are
+    /// queued to run on the tick.
+    pub scheduler: Arc<Scheduler>,
+    /// Durations of the last ticks, recorded by the tick loop.
+    pub tick_metrics: Mutex<TickMetrics>,
+    /// Public keys of Mojang's services, which the chat sessions of the
+    /// players are verified with.
+    pub services_keys: Vec<RsaPublicKey>,
+    /// Players that are online, by their UUIDs.
+    players: Mutex<BTreeMap<Uuid, Arc<Player>>>,
+    /// Entities other than the players, by their IDs.
+    entities: Mutex<BTreeMap<i32, Arc<Entity>>>,
+    next_entity_id: AtomicI32,
+}
+
+impl Server {
+    pub fn new(config: Config, worlds: Arc<Worlds>, scheduler: Arc<Scheduler>) -> Self {
+        let services_keys = config
+            .services_keys()
+            .expect("services keys are validated along with the configuration");
+        Self {
+            config,
+            worlds,
+            scheduler,
+            tick_metrics: Mutex::new(TickMetrics::default()),
+            services_keys,
+            players: Mutex::new(BTreeMap::new()),
+            entities: Mutex::new(BTreeMap::new()),
+            next_entity_id: AtomicI32::new(1),
+        }
+    }
+
+    /// Runs the action on the next tick. Actions of the players are submitted
+    /// this way, so they run in the order they were made in, and the changes
+    /// of the tick are sent to the clients together.
+    pub fn submit(self: &Arc<Self>, action: impl FnOnce(&Server) + Send + 'static) -> TaskId {
+        let server = self.clone();
+        self.scheduler.submit(move || action(&server))
+    }
+
+    /// Returns a new entity ID, which no other entity of the server has.
+    pub fn allocate_entity_id(&self) -> i32 {
+        self.next_entity_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Adds the player to the online ones. The player is added to the tab
+    /// list of everyone, and receives the entries of the others. Returns the
+    /// player that was online with the same UUID before, if there was one.
+    pub fn add_player(&self, player: Arc<Player>) -> Option<Arc<Player>> {
+        let previous = self
+            .players
+            .lock()
+            .unwrap()
+            .insert(player.uuid, player.clone());
+
+        let actions = PlayerInfoActions::ADD_PLAYER
+            | PlayerInfoActions::INITIALIZE_CHAT
+            | PlayerInfoActions::UPDATE_GAME_MODE
+            | PlayerInfoActions::UPDATE_LISTED
+            | PlayerInfoActions::UPDATE_LATENCY;
+        let players = self.players();
+        player.send_packet(&ClientboundPlayerInfoUpdatePacket {
+            actions,
+            entries: players.iter().map(|online| online.info_entry()).collect(),
+        });
+        let player_info_update_packet = ClientboundPlayerInfoUpdatePacket {
+            actions,
+            entries: vec![player.info_entry()],
+        };
+        for online in players
+            .iter()
+            .filter(|online| !Arc::ptr_eq(online, &player))
+        {
+            online.send_packet(&player_info_update_packet);
+        }
+        previous
+    }
+
+    /// Removes the player from the online ones and from the tab list of the
+    /// others. Its entity is removed on the next tick. A player that has
+    /// replaced it with the same UUID is kept. Returns `false` if it wasn't
+    /// online.
+    pub fn remove_player(&self, player: &Arc<Player>) -> bool {
+        {
+            let mut players = self.players.lock().unwrap();
+            if !players
+                .get(&player.uuid)
+                .is_some_and(|online| Arc::ptr_eq(online, player))
+            {
+                return false;
+            }
+            players.remove(&player.uuid);
+        }
+
+        self.broadcast_packet(&ClientboundPlayerInfoRemovePacket {
+            uuids: PrefixedArray(vec![player.uuid]),
+        });
+        true
+    }
+
+    /// Sends the packet to all online players.
+    pub fn broadcast_packet(&self, packet: &dyn Packet) {
+        for player in self.players() {
+            player.send_packet(packet);
+        }
+    }
+
+    /// Sends the system message to everyone that doesn't hide the chat.
+    pub fn broadcast_message(&self, message: TextComponent) {
+        for player in self.players() {
+            player.send_message(message.clone());
+        }
+    }
+
+    /// Sends the message of the player to everyone that shows the chat. The
+    /// clients decorate it with the chat type.
+    pub fn broadcast_chat(&self, sender: &Player, message: &PlayerChatMessage) {
+        let chat_type = REGISTRY.chat_type_id(PLAYER_CHAT_TYPE).unwrap();
+        let chat_type = VarInt(chat_type.0 + 1);
+        let sender_name = TextComponent::text(sender.name.clone());
+        let recipients = self
+            .players()
+            .into_iter()
+            .filter(|player| player.accepts_chat_messages());
+
+        if message.signature.is_some() {
+            for player in recipients {
+                player.send_chat_message(message, chat_type, sender_name.clone());
+            }
+            return;
+        }
+        // unsigned messages are disguised, so the clients don't mark them as
+        // not secure
+        let packet = ClientboundDisguisedChatPacket {
+            message: TextComponent::text(message.message.clone()),
+            chat_type,
+            sender_name,
+            target_name: PrefixedOption(None),
+        };
+        for player in recipients {
+            player.send_packet(&packet);
+        }
+    }
+
+    /// Sends the chat session of the player to everyone, so the clients can
+    /// verify its messages.
+    pub fn broadcast_chat_session(&self, player: &Player) {
+        self.broadcast_packet(&ClientboundPlayerInfoUpdatePacket {
+            actions: PlayerInfoActions::INITIALIZE_CHAT,
+            entries: vec![player.info_entry()],
+        });
+    }
+
+    /// Updates the latency of the player in the tab list of everyone.
+    pub fn broadcast_latency(&self, player: &Player) {
+        self.broadcast_packet(&ClientboundPlayerInfoUpdatePacket {
+            actions: PlayerInfoActions::UPDATE_LATENCY,
+            entries: vec![player.info_entry()],
+        });
+    }
+
+    /// Returns the online player with the provided UUID.
+    pub fn player(&self, uuid: &Uuid) -> Option<Arc<Player>> {
+        self.players.lock().unwrap().get(uuid).cloned()
+    }
+
+    /// Returns the online player with the provided name, ignoring the case.
+    pub fn player_by_name(&self, name: &str) -> Option<Arc<Player>> {
+        self.players
+            .lock()
+            .unwrap()
+            .values()
+            .find(|player| player.name.eq_ignore_ascii_case(name))
+            .cloned()
+    }
+
+    /// Returns all online players.
+    pub fn players(&self) -> Vec<Arc<Player>> {
+        self.players.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn player_count(&self) -> usize {
+        self.players.lock().unwrap().len()
+    }
+
+    /// Creates the entity of the type at the location, which the players
+    /// nearby see from the next tick.
+    pub fn spawn_entity(&self, entity_type: EntityType, location: Location) -> Arc<Entity> {
+        let entity = Entity::new(self.allocate_entity_id(), entity_type, location);
+        self.add_entity(entity)
+    }
+
+    /// Adds the entity created with an ID of this server, i.e. one that is
+    /// set up before the players see it.
+    pub fn add_entity(&self, entity: Entity) -> Arc<Entity> {
+        let entity = Arc::new(entity);
+        self.entities
+            .lock()
+            .unwrap()
+            .insert(entity.entity_id, entity.clone());
+        entity
+    }
+
+    /// Removes the entity, which disappears for the players on the next tick.
+    pub fn remove_entity(&self, entity_id: i32) -> Option<Arc<Entity>> {
+        self.entities.lock().unwrap().remove(&entity_id)
+    }
+
+    pub fn entity(&self, entity_id: i32) -> Option<Arc<Entity>> {
+        self.entities.lock().unwrap().get(&entity_id).cloned()
+    }
+
+    /// Returns all entities other than the players.
+    pub fn entities(&self) -> Vec<Arc<Entity>> {
+        self.entities.lock().unwrap().values().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{collections::HashSet, io, thread};
+
+    use super::*;
+    use crate::{network::PacketWriter, player::GameMode};
+
+    pub(crate) fn create_server() -> Arc<Server> {
+        let mut config = Config::default();
+        for dimension in config.dimensions.values_mut() {
+            dimension.world_directory = None;
+        }
+        let worlds = Arc::new(Worlds::from_config(&config).unwrap());
+        Arc::new(Server::new(config, worlds, Arc::default()))
+    }
+
+    pub(super) fn create_player(server: &Server, uuid: Uuid, name: &str) -> Arc<Player> {
+        Arc::new(Player::new(
+            uuid,
+            name.to_string(),
+            server.allocate_entity_id(),
+            GameMode::Survival,
+            server.worlds.spawn_world().clone(),
+            Arc::new(PacketWriter::new(io::sink())),
+        ))
+    }
+
+    #[test]
+    fn test_players() {
+        let server = create_server();
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let server = server.clone();
+                thread::spawn(move || {
+                    (0..100)
+                        .map(|_| server.allocate_entity_id())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let entity_ids: HashSet<i32> = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect();
+        assert_eq!(entity_ids.len(), 800);
+
+        let uuid = Uuid::from_u128(1);
+        let player = create_player(&server, uuid, "Steve");
+        assert!(server.add_player(player.clone()).is_none());
+        assert!(Arc::ptr_eq(
+            &server.player_by_name("steve").unwrap(),
+            &player
+        ));
+
+        // the same player joins again, which replaces the previous one
+        let rejoined = create_player(&server, uuid, "Steve");
+        assert!(server.add_player(rejoined.clone()).is_some());
+        assert!(!server.remove_player(&player));
+        assert_eq!(server.player_count(), 1);
+        assert!(server.remove_player(&rejoined));
+        assert!(server.player(&uuid).is_none());
+    }
+}
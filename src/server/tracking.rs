@@ -0,0 +1,505 @@
+use std::{collections::HashSet, sync::Arc, time::Instant};
+
+use uuid::Uuid;
+
+use crate::{
+    entity::{Entity, EntityType},
+    player::{Player, Position},
+    protocol::{
+        Angle, PrefixedArray,
+        packets::{
+            Packet,
+            play::{
+                ClientboundKeepAlivePacket, ClientboundRemoveEntitiesPacket,
+                ClientboundSetEntityMetadataPacket, ClientboundSetEntityVelocityPacket,
+                ClientboundSetEquipmentPacket, ClientboundSetHeadRotationPacket,
+                ClientboundSpawnEntityPacket, ClientboundTeleportEntityPacket,
+                ClientboundUpdateEntityPositionAndRotationPacket,
+                ClientboundUpdateEntityPositionPacket, ClientboundUpdateEntityRotationPacket,
+                TeleportFlags,
+            },
+        },
+        text::TextComponent,
+    },
+    varint::VarInt,
+    world::World,
+};
+
+use super::Server;
+
+/// Interval of the keep-alives sent to the clients, in ticks.
+const KEEP_ALIVE_INTERVAL: u64 = 300;
+
+/// Returns the difference between the coordinates in the units of the
+/// movement packets, which are 1/4096 of a block. Returns `None` if it
+/// doesn't fit, and the entity has to be teleported then.
+fn position_delta(from: f64, to: f64) -> Option<i16> {
+    let delta = (to * 4096.0).round() as i64 - (from * 4096.0).round() as i64;
+    i16::try_from(delta).ok()
+}
+
+/// Returns the packets that move the entity from one position to the other
+/// for the clients that track it.
+fn movement_packets(
+    entity_id: i32,
+    from: Position,
+    to: Position,
+    on_ground: bool,
+) -> Vec<Box<dyn Packet>> {
+    let entity_id = VarInt(entity_id);
+    let moved = (from.x, from.y, from.z) != (to.x, to.y, to.z);
+    let rotated = (from.yaw, from.pitch) != (to.yaw, to.pitch);
+    let yaw = Angle::from_degrees(to.yaw);
+    let pitch = Angle::from_degrees(to.pitch);
+
+    let mut packets: Vec<Box<dyn Packet>> = vec![];
+    let deltas = (
+        position_delta(from.x, to.x),
+        position_delta(from.y, to.y),
+        position_delta(from.z, to.z),
+    );
+    match deltas {
+        _ if !moved && rotated => packets.push(Box::new(ClientboundUpdateEntityRotationPacket {
+            entity_id,
+            yaw,
+            pitch,
+            on_ground,
+        })),
+        _ if !moved => {}
+        (Some(delta_x), Some(delta_y), Some(delta_z)) if rotated => {
+            packets.push(Box::new(ClientboundUpdateEntityPositionAndRotationPacket {
+                entity_id,
+                delta_x,
+                delta_y,
+                delta_z,
+                yaw,
+                pitch,
+                on_ground,
+            }))
+        }
+        (Some(delta_x), Some(delta_y), Some(delta_z)) => {
+            packets.push(Box::new(ClientboundUpdateEntityPositionPacket {
+                entity_id,
+                delta_x,
+                delta_y,
+                delta_z,
+                on_ground,
+            }))
+        }
+        // moved too far for the deltas
+        _ => packets.push(Box::new(ClientboundTeleportEntityPacket {
+            entity_id,
+            x: to.x,
+            y: to.y,
+            z: to.z,
+            velocity_x: 0.0,
+            velocity_y: 0.0,
+            velocity_z: 0.0,
+            yaw: to.yaw,
+            pitch: to.pitch,
+            flags: TeleportFlags::empty(),
+            on_ground,
+        })),
+    }
+    if rotated {
+        packets.push(Box::new(ClientboundSetHeadRotationPacket {
+            entity_id,
+            head_yaw: yaw,
+        }));
+    }
+    packets
+}
+
+/// Returns the packet that spawns the entity at the position.
+fn spawn_packet(
+    entity_id: i32,
+    uuid: Uuid,
+    entity_type: EntityType,
+    position: Position,
+    data: i32,
+    velocity: (i16, i16, i16),
+) -> ClientboundSpawnEntityPacket {
+    let yaw = Angle::from_degrees(position.yaw);
+    ClientboundSpawnEntityPacket {
+        entity_id: VarInt(entity_id),
+        uuid,
+        entity_type: VarInt(entity_type.id()),
+        x: position.x,
+        y: position.y,
+        z: position.z,
+        pitch: Angle::from_degrees(position.pitch),
+        yaw,
+        head_yaw: yaw,
+        data: VarInt(data),
+        velocity_x: velocity.0,
+        velocity_y: velocity.1,
+        velocity_z: velocity.2,
+    }
+}
+
+/// Something that is spawned for the players that see it.
+enum Tracked<'a> {
+    Player(&'a Arc<Player>),
+    Entity(&'a Arc<Entity>),
+}
+
+impl Tracked<'_> {
+    fn entity_id(&self) -> i32 {
+        match self {
+            Tracked::Player(player) => player.entity_id,
+            Tracked::Entity(entity) => entity.entity_id,
+        }
+    }
+
+    fn world(&self) -> Arc<World> {
+        match self {
+            Tracked::Player(player) => player.world(),
+            Tracked::Entity(entity) => entity.world(),
+        }
+    }
+
+    fn is_visible_to(&self, viewer: &Player) -> bool {
+        match self {
+            Tracked::Player(player) => player.entity_id != viewer.entity_id,
+            Tracked::Entity(entity) => entity.is_visible_to(&viewer.uuid),
+        }
+    }
+
+    fn synced_position(&self) -> Option<Position> {
+        match self {
+            Tracked::Player(player) => player.synced_position().map(|(position, _)| position),
+            Tracked::Entity(entity) => entity.synced_position(),
+        }
+    }
+
+    /// Returns the packets that spawn it at the position with all of its
+    /// current state, as the viewer sees it.
+    fn spawn_packets(&self, position: Position, viewer: &Player) -> Vec<Box<dyn Packet>> {
+        match self {
+            Tracked::Player(player) => vec![Box::new(spawn_packet(
+                player.entity_id,
+                player.uuid,
+                EntityType::new("player").unwrap(),
+                position,
+                0,
+                (0, 0, 0),
+            ))],
+            Tracked::Entity(entity) => {
+                let mut packets: Vec<Box<dyn Packet>> = vec![Box::new(spawn_packet(
+                    entity.entity_id,
+                    entity.uuid,
+                    entity.entity_type,
+                    position,
+                    entity.data(),
+                    entity.velocity().to_protocol(),
+                ))];
+                let metadata = entity.metadata_for(&viewer.uuid);
+                if !metadata.is_empty() {
+                    packets.push(Box::new(ClientboundSetEntityMetadataPacket {
+                        entity_id: VarInt(entity.entity_id),
+                        metadata,
+                    }));
+                }
+                let equipment = entity.equipment();
+                if !equipment.is_empty() {
+                    packets.push(Box::new(ClientboundSetEquipmentPacket {
+                        entity_id: VarInt(entity.entity_id),
+                        equipment,
+                    }));
+                }
+                packets
+            }
+        }
+    }
+}
+
+/// Sends the packets to the players that track the entity.
+fn send_to_viewers(players: &[Arc<Player>], entity_id: i32, packets: &[Box<dyn Packet>]) {
+    if packets.is_empty() {
+        return;
+    }
+    for viewer in players
+        .iter()
+        .filter(|viewer| viewer.is_tracking(entity_id))
+    {
+        for packet in packets {
+            viewer.send_packet(packet.as_ref());
+        }
+    }
+}
+
+impl Server {
+    /// Runs a single tick of the players and the entities: their changes are
+    /// sent to the players that see them, the ones that came into the view
+    /// distance of the players are spawned, and the ones that left it are
+    /// removed.
+    pub fn tick(&self) {
+        self.disconnect_timed_out(&self.players(), Instant::now());
+        let players = self.players();
+        let entities = self.entities();
+        self.sync_players(&players);
+        self.sync_entities(&players, &entities);
+        self.update_tracking(&players, &entities);
+        if self
+            .scheduler
+            .current_tick()
+            .is_multiple_of(KEEP_ALIVE_INTERVAL)
+        {
+            self.send_keep_alives(&players);
+        }
+    }
+
+    /// Sends the movement of each player since the last tick to the players
+    /// that track it.
+    fn sync_players(&self, players: &[Arc<Player>]) {
+        for player in players {
+            let Some((from, _)) = player.sync_position() else {
+                continue;
+            };
+            let (to, on_ground) = player.synced_position().unwrap();
+            let packets = movement_packets(player.entity_id, from, to, on_ground);
+            send_to_viewers(players, player.entity_id, &packets);
+        }
+    }
+
+    /// Sends the movement, velocity, metadata and equipment of each entity
+    /// that have changed since the last tick to the players that track it.
+    fn sync_entities(&self, players: &[Arc<Player>], entities: &[Arc<Entity>]) {
+        for entity in entities {
+            let entity_id = VarInt(entity.entity_id);
+            let mut packets = match entity.sync_position() {
+                Some(from) => movement_packets(
+                    entity.entity_id,
+                    from,
+                    entity.position(),
+                    entity.is_on_ground(),
+                ),
+                None => vec![],
+            };
+            if let Some(velocity) = entity.take_velocity_change() {
+                let (velocity_x, velocity_y, velocity_z) = velocity.to_protocol();
+                packets.push(Box::new(ClientboundSetEntityVelocityPacket {
+                    entity_id,
+                    velocity_x,
+                    velocity_y,
+                    velocity_z,
+                }));
+            }
+            if let Some(equipment) = entity.take_equipment_changes() {
+                packets.push(Box::new(ClientboundSetEquipmentPacket {
+                    entity_id,
+                    equipment,
+                }));
+            }
+
+            let viewers: Vec<_> = players
+                .iter()
+                .filter(|viewer| viewer.is_tracking(entity.entity_id))
+                .collect();
+            let viewer_uuids: Vec<_> = viewers.iter().map(|viewer| viewer.uuid).collect();
+            // the metadata differs between the viewers with their own entries
+            let mut metadata = entity.take_metadata_changes(&viewer_uuids);
+            for viewer in viewers {
+                for packet in &packets {
+                    viewer.send_packet(packet.as_ref());
+                }
+                if let Some(metadata) = metadata.remove(&viewer.uuid) {
+                    viewer.send_packet(&ClientboundSetEntityMetadataPacket {
+                        entity_id,
+                        metadata,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Spawns the players and the entities that came into the view distance
+    /// of each viewer, and removes the ones that left it or the server.
+    fn update_tracking(&self, players: &[Arc<Player>], entities: &[Arc<Entity>]) {
+        let tracked: Vec<_> = players
+            .iter()
+            .map(Tracked::Player)
+            .chain(entities.iter().map(Tracked::Entity))
+            .collect();
+        for viewer in players {
+            let world = viewer.world();
+            let center = viewer.position();
+            let range = (viewer.view_distance(self.config.view_distance) * 16) as f64;
+            let visible: Vec<_> = tracked
+                .iter()
+                .filter(|tracked| tracked.is_visible_to(viewer))
+                .filter(|tracked| Arc::ptr_eq(&tracked.world(), &world))
+                .filter_map(|tracked| {
+                    let position = tracked.synced_position()?;
+                    let distance_squared =
+                        (position.x - center.x).powi(2) + (position.z - center.z).powi(2);
+                    (distance_squared <= range * range).then_some((tracked, position))
+                })
+                .collect();
+
+            let visible_ids: HashSet<i32> = visible
+                .iter()
+                .map(|(tracked, _)| tracked.entity_id())
+                .collect();
+            let (spawned, removed) = viewer.update_tracked_entities(visible_ids);
+            if !removed.is_empty() {
+                viewer.send_packet(&ClientboundRemoveEntitiesPacket {
+                    entity_ids: PrefixedArray(removed.into_iter().map(VarInt).collect()),
+                });
+            }
+            for (tracked, position) in visible
+                .iter()
+                .filter(|(tracked, _)| spawned.contains(&tracked.entity_id()))
+            {
+                for packet in tracked.spawn_packets(*position, viewer) {
+                    viewer.send_packet(packet.as_ref());
+                }
+            }
+        }
+    }
+
+    /// Disconnects the players whose clients haven't answered the keep-alive
+    /// in time. They leave the server right away, as their connections may
+    /// take a while to notice that the clients are gone.
+    fn disconnect_timed_out(&self, players: &[Arc<Player>], now: Instant) {
+        for player in players
+            .iter()
+            .filter(|player| player.is_keep_alive_overdue(now))
+        {
+            if self.remove_player(player) {
+                println!("{} has timed out", player.name);
+            }
+            player.disconnect(TextComponent::translatable("disconnect.timeout", vec![]));
+        }
+    }
+
+    /// Sends the keep-alives, which measure the latency of the clients.
+    fn send_keep_alives(&self, players: &[Arc<Player>]) {
+        let id = self.scheduler.current_tick() as i64;
+        for player in players {
+            player.start_keep_alive(id);
+            player.send_packet(&ClientboundKeepAlivePacket { id });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        entity::metadata::{self, MetadataValue},
+        player::Location,
+        server::tests::{create_player, create_server},
+    };
+
+    #[test]
+    fn test_position_delta() {
+        assert_eq!(position_delta(0.0, 1.0), Some(4096));
+        assert_eq!(position_delta(10.5, 10.25), Some(-1024));
+        assert_eq!(position_delta(0.0, 8.0), None);
+
+        let from = Position::default();
+        let to = Position { x: 100.0, ..from };
+        let packets = movement_packets(1, from, to, true);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].id(), ClientboundTeleportEntityPacket::PACKET_ID);
+    }
+
+    #[test]
+    fn test_tracking() {
+        let server = create_server();
+        let steve = create_player(&server, Uuid::from_u128(1), "Steve");
+        let alex = create_player(&server, Uuid::from_u128(2), "Alex");
+        server.add_player(steve.clone());
+        server.add_player(alex.clone());
+
+        server.tick();
+        assert!(steve.is_tracking(alex.entity_id));
+        assert!(alex.is_tracking(steve.entity_id));
+        assert!(!steve.is_tracking(steve.entity_id));
+
+        // out of the view distance of each other
+        alex.set_position(Position {
+            x: 10_000.0,
+            ..alex.position()
+        });
+        server.tick();
+        assert!(!steve.is_tracking(alex.entity_id));
+
+        alex.set_position(Position::default());
+        server.tick();
+        assert!(steve.is_tracking(alex.entity_id));
+        server.remove_player(&alex);
+        server.tick();
+        assert!(!steve.is_tracking(alex.entity_id));
+    }
+
+    #[test]
+    fn test_keep_alive_timeout() {
+        let server = create_server();
+        let steve = create_player(&server, Uuid::from_u128(1), "Steve");
+        let alex = create_player(&server, Uuid::from_u128(2), "Alex");
+        server.add_player(steve.clone());
+        server.add_player(alex.clone());
+
+        let sent_at = Instant::now();
+        server.send_keep_alives(&server.players());
+        assert!(alex.confirm_keep_alive(server.scheduler.current_tick() as i64));
+        server.disconnect_timed_out(&server.players(), sent_at + Duration::from_secs(10));
+        assert_eq!(server.player_count(), 2);
+
+        // only Steve hasn't answered
+        server.disconnect_timed_out(&server.players(), sent_at + Duration::from_secs(16));
+        assert_eq!(
+            server
+                .players()
+                .iter()
+                .map(|player| player.uuid)
+                .collect::<Vec<_>>(),
+            [alex.uuid]
+        );
+        assert!(
+            steve
+                .writer
+                .write_packet(&ClientboundKeepAlivePacket { id: 0 })
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_entity_tracking() {
+        let server = create_server();
+        let steve = create_player(&server, Uuid::from_u128(1), "Steve");
+        server.add_player(steve.clone());
+        let armor_stand = server.spawn_entity(
+            EntityType::new("armor_stand").unwrap(),
+            Location {
+                world: steve.world(),
+                position: Position::default(),
+            },
+        );
+        assert_eq!(armor_stand.entity_type.name(), "minecraft:armor_stand");
+
+        armor_stand.set_metadata(metadata::CUSTOM_NAME_VISIBLE, MetadataValue::Boolean(true));
+        server.tick();
+        assert!(steve.is_tracking(armor_stand.entity_id));
+        // the viewers got the whole metadata with the spawn
+        assert!(armor_stand.take_metadata_changes(&[steve.uuid]).is_empty());
+
+        armor_stand.set_metadata(metadata::CUSTOM_NAME_VISIBLE, MetadataValue::Boolean(false));
+        assert!(!armor_stand.take_metadata_changes(&[steve.uuid]).is_empty());
+
+        armor_stand.hide_from(steve.uuid);
+        server.tick();
+        assert!(!steve.is_tracking(armor_stand.entity_id));
+        armor_stand.show_to(steve.uuid);
+        server.tick();
+        assert!(steve.is_tracking(armor_stand.entity_id));
+
+        server.remove_entity(armor_stand.entity_id);
+        server.tick();
+        assert!(!steve.is_tracking(armor_stand.entity_id));
+    }
+}
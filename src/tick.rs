@@ -0,0 +1,423 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    mem,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::server::Server;
+
+/// Duration of a single server tick, which makes 20 ticks per second.
+pub const TICK_DURATION: Duration = Duration::from_millis(50);
+
+/// The amount of ticks per second the server aims for.
+pub const TICKS_PER_SECOND: u64 = 20;
+
+/// The amount of ticks the server runs back to back to catch up after a lag.
+/// If it's further behind, the missed ticks are skipped instead.
+const MAX_CATCH_UP_TICKS: u32 = 40;
+
+/// The amount of the last ticks the metrics are calculated over.
+const METRICS_WINDOW: usize = 100;
+
+/// Identifier of a scheduled task, used to cancel it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TaskId(u64);
+
+type Task = Box<dyn FnMut() + Send>;
+
+struct ScheduledTask {
+    id: TaskId,
+    /// The tick the task runs at next time.
+    tick: u64,
+    /// Interval between the runs of the repeating tasks.
+    period: Option<u64>,
+    task: Task,
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    /// The amount of the ticks that have been run.
+    current_tick: u64,
+    next_id: u64,
+    tasks: Vec<ScheduledTask>,
+    /// Tasks that are being run at the moment.
+    running: HashSet<TaskId>,
+    /// Running tasks that were cancelled, which mustn't be scheduled again.
+    cancelled: HashSet<TaskId>,
+}
+
+/// Tasks that run on the tick thread, i.e. the queued actions of the players
+/// or the repeating jobs. Tasks of the same tick run in the order they were
+/// scheduled in.
+#[derive(Default)]
+pub struct Scheduler {
+    state: Mutex<SchedulerState>,
+}
+
+impl Scheduler {
+    fn schedule(&self, delay: u64, period: Option<u64>, task: Task) -> TaskId {
+        let mut state = self.state.lock().unwrap();
+        let id = TaskId(state.next_id);
+        state.next_id += 1;
+
+        // a task that is scheduled during a tick runs on the next one at
+        // earliest
+        let tick = state.current_tick + delay.max(1);
+        state.tasks.push(ScheduledTask {
+            id,
+            tick,
+            period,
+            task,
+        });
+        id
+    }
+
+    /// Runs the task at the start of the next tick.
+    pub fn submit(&self, task: impl FnOnce() + Send + 'static) -> TaskId {
+        self.run_later(1, task)
+    }
+
+    /// Runs the task once after the provided amount of ticks.
+    pub fn run_later(&self, delay: u64, task: impl FnOnce() + Send + 'static) -> TaskId {
+        let mut task = Some(task);
+        self.schedule(
+            delay,
+            None,
+            Box::new(move || {
+                if let Some(task) = task.take() {
+                    task();
+                }
+            }),
+        )
+    }
+
+    /// Runs the task after the provided amount of ticks, and then every
+    /// `period` ticks until it's cancelled.
+    pub fn run_every(
+        &self,
+        delay: u64,
+        period: u64,
+        task: impl FnMut() + Send + 'static,
+    ) -> TaskId {
+        self.schedule(delay, Some(period.max(1)), Box::new(task))
+    }
+
+    /// Cancels the task, so it won't run anymore. Returns `false` if the task
+    /// has already finished or was cancelled before.
+    pub fn cancel(&self, id: TaskId) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if let Some(index) = state.tasks.iter().position(|task| task.id == id) {
+            state.tasks.remove(index);
+            return true;
+        }
+        state.running.contains(&id) && state.cancelled.insert(id)
+    }
+
+    /// Returns the amount of the ticks that have been run.
+    pub fn current_tick(&self) -> u64 {
+        self.state.lock().unwrap().current_tick
+    }
+
+    /// Returns the amount of the scheduled tasks.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Advances the scheduler to the next tick and runs the tasks that are
+    /// due. The lock isn't held while they run, so they can schedule more. A
+    /// task that panics is reported, and the others run as usual.
+    fn tick(&self) {
+        let mut due = {
+            let mut state = self.state.lock().unwrap();
+            state.current_tick += 1;
+            let current_tick = state.current_tick;
+
+            let (due, pending) = mem::take(&mut state.tasks)
+                .into_iter()
+                .partition::<Vec<_>, _>(|task| task.tick <= current_tick);
+            state.tasks = pending;
+            state.running = due.iter().map(|task| task.id).collect();
+            due
+        };
+        due.sort_by_key(|task| task.id);
+
+        for scheduled in &mut due {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| (scheduled.task)()));
+            if result.is_err() {
+                eprintln!("Scheduled task {} has panicked", scheduled.id.0);
+            }
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let current_tick = state.current_tick;
+        for mut scheduled in due {
+            if let Some(period) = scheduled.period
+                && !state.cancelled.contains(&scheduled.id)
+            {
+                scheduled.tick = current_tick + period;
+                state.tasks.push(scheduled);
+            }
+        }
+        state.running.clear();
+        state.cancelled.clear();
+    }
+}
+
+/// Durations of the last ticks, to tell how loaded the server is.
+#[derive(Debug, Default, Clone)]
+pub struct TickMetrics {
+    durations: VecDeque<Duration>,
+    /// The amount of the ticks that have been run.
+    pub tick_count: u64,
+    /// The amount of the ticks that were skipped because of the lag.
+    pub skipped_ticks: u64,
+}
+
+impl TickMetrics {
+    fn record(&mut self, duration: Duration) {
+        if self.durations.len() == METRICS_WINDOW {
+            self.durations.pop_front();
+        }
+        self.durations.push_back(duration);
+        self.tick_count += 1;
+    }
+
+    /// Returns the mean duration of the last ticks (MSPT).
+    pub fn mean_tick_duration(&self) -> Duration {
+        if self.durations.is_empty() {
+            return Duration::ZERO;
+        }
+        self.durations.iter().sum::<Duration>() / self.durations.len() as u32
+    }
+
+    /// Returns the longest of the last ticks.
+    pub fn max_tick_duration(&self) -> Duration {
+        self.durations.iter().copied().max().unwrap_or_default()
+    }
+
+    /// Returns the amount of the ticks the server could run per second with
+    /// the current tick durations, which is at most `TICKS_PER_SECOND`.
+    pub fn ticks_per_second(&self) -> f64 {
+        let mean = self.mean_tick_duration();
+        if mean <= TICK_DURATION {
+            return TICKS_PER_SECOND as f64;
+        }
+        1.0 / mean.as_secs_f64()
+    }
+}
+
+/// Returns the time the next tick should start at, and the amount of the
+/// skipped ticks. Ticks that are late run back to back without sleeping, but
+/// if the server is more than `MAX_CATCH_UP_TICKS` behind, the missed ticks
+/// are skipped.
+fn catch_up(next_tick: Instant, now: Instant) -> (Instant, u64) {
+    let behind = now.saturating_duration_since(next_tick);
+    let behind_ticks = (behind.as_nanos() / TICK_DURATION.as_nanos()) as u32;
+    if behind_ticks <= MAX_CATCH_UP_TICKS {
+        return (next_tick, 0);
+    }
+    (
+        next_tick + TICK_DURATION * behind_ticks,
+        behind_ticks as u64,
+    )
+}
+
+/// The game loop, which runs the ticks of the server at a fixed rate. The
+/// durations of the ticks are recorded into the metrics of the server.
+pub struct TickLoop {
+    server: Arc<Server>,
+}
+
+impl TickLoop {
+    pub fn new(server: Arc<Server>) -> Self {
+        Self { server }
+    }
+
+    /// Runs a single tick: the scheduled tasks first, then the worlds and the
+    /// players, and the changes are sent to the clients at the end.
+    pub fn tick(&self) {
+        self.server.scheduler.tick();
+        for world in self.server.worlds.iter() {
+            world.tick();
+        }
+        self.server.tick();
+        for world in self.server.worlds.iter() {
+            world.flush_block_changes();
+        }
+    }
+
+    /// Runs a single tick and records its duration.
+    fn timed_tick(&self) {
+        let start = Instant::now();
+        self.tick();
+        let duration = start.elapsed();
+        self.server.tick_metrics.lock().unwrap().record(duration);
+    }
+
+    /// Runs the ticks forever on the current thread.
+    pub fn run(self) {
+        let mut next_tick = Instant::now();
+        loop {
+            let now = Instant::now();
+            if now < next_tick {
+                thread::sleep(next_tick - now);
+            }
+
+            let (scheduled_tick, skipped) = catch_up(next_tick, Instant::now());
+            if skipped > 0 {
+                eprintln!(
+                    "Can't keep up! Skipping {skipped} ticks ({}ms behind)",
+                    skipped * TICK_DURATION.as_millis() as u64
+                );
+                self.server.tick_metrics.lock().unwrap().skipped_ticks += skipped;
+            }
+
+            self.timed_tick();
+            next_tick = scheduled_tick + TICK_DURATION;
+        }
+    }
+
+    /// Starts the loop on its own thread.
+    pub fn spawn(self) {
+        thread::spawn(move || self.run());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+    use crate::server::tests::create_server;
+
+    #[test]
+    fn test_scheduler() {
+        let scheduler = Arc::new(Scheduler::default());
+        let log = Arc::new(Mutex::new(vec![]));
+
+        let later_log = log.clone();
+        scheduler.run_later(2, move || later_log.lock().unwrap().push("later"));
+        let submitted_log = log.clone();
+        let nested_scheduler = scheduler.clone();
+        scheduler.submit(move || {
+            submitted_log.lock().unwrap().push("submitted");
+            // scheduled from a task, so it runs on the next tick
+            let nested_log = submitted_log.clone();
+            nested_scheduler.submit(move || nested_log.lock().unwrap().push("nested"));
+        });
+
+        let counter = Arc::new(AtomicU64::new(0));
+        let repeated_counter = counter.clone();
+        let repeating = scheduler.run_every(1, 2, move || {
+            repeated_counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        scheduler.tick();
+        assert_eq!(*log.lock().unwrap(), ["submitted"]);
+        scheduler.tick();
+        assert_eq!(*log.lock().unwrap(), ["submitted", "later", "nested"]);
+        for _ in 0..4 {
+            scheduler.tick();
+        }
+        // ticks 1, 3 and 5
+        assert_eq!(counter.load(Ordering::Relaxed), 3);
+
+        assert!(scheduler.cancel(repeating));
+        assert!(!scheduler.cancel(repeating));
+        scheduler.tick();
+        scheduler.tick();
+        assert_eq!(counter.load(Ordering::Relaxed), 3);
+        assert!(scheduler.is_empty());
+        assert_eq!(scheduler.current_tick(), 8);
+    }
+
+    #[test]
+    fn test_task_cancels_itself() {
+        let scheduler = Arc::new(Scheduler::default());
+        let counter = Arc::new(AtomicU64::new(0));
+        let id = Arc::new(Mutex::new(None));
+
+        let task_scheduler = scheduler.clone();
+        let task_counter = counter.clone();
+        let task_id = id.clone();
+        let repeating = scheduler.run_every(1, 1, move || {
+            if task_counter.fetch_add(1, Ordering::Relaxed) == 1 {
+                assert!(task_scheduler.cancel(task_id.lock().unwrap().unwrap()));
+            }
+        });
+        *id.lock().unwrap() = Some(repeating);
+
+        for _ in 0..5 {
+            scheduler.tick();
+        }
+        assert_eq!(counter.load(Ordering::Relaxed), 2);
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn test_panicking_task() {
+        let scheduler = Scheduler::default();
+        let counter = Arc::new(AtomicU64::new(0));
+        scheduler.submit(|| panic!("the task has failed"));
+        let repeated_counter = counter.clone();
+        scheduler.run_every(1, 1, move || {
+            repeated_counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        scheduler.tick();
+        scheduler.tick();
+        assert_eq!(counter.load(Ordering::Relaxed), 2);
+        assert_eq!(scheduler.len(), 1);
+    }
+
+    #[test]
+    fn test_catch_up() {
+        let start = Instant::now();
+        // slightly late ticks run right away
+        let now = start + TICK_DURATION * 3;
+        assert_eq!(catch_up(start, now), (start, 0));
+
+        // too late ones are skipped
+        let now = start + TICK_DURATION * (MAX_CATCH_UP_TICKS + 10) + TICK_DURATION / 2;
+        let skipped = (MAX_CATCH_UP_TICKS + 10) as u64;
+        assert_eq!(
+            catch_up(start, now),
+            (start + TICK_DURATION * skipped as u32, skipped)
+        );
+    }
+
+    #[test]
+    fn test_tick_loop_records_metrics() {
+        let server = create_server();
+        let tick_loop = TickLoop::new(server.clone());
+        tick_loop.timed_tick();
+        tick_loop.timed_tick();
+        assert_eq!(server.scheduler.current_tick(), 2);
+        assert_eq!(server.tick_metrics.lock().unwrap().tick_count, 2);
+    }
+
+    #[test]
+    fn test_tick_metrics() {
+        let mut metrics = TickMetrics::default();
+        assert_eq!(metrics.ticks_per_second(), 20.0);
+        for _ in 0..METRICS_WINDOW {
+            metrics.record(Duration::from_millis(10));
+        }
+        assert_eq!(metrics.mean_tick_duration(), Duration::from_millis(10));
+        assert_eq!(metrics.ticks_per_second(), 20.0);
+
+        for _ in 0..METRICS_WINDOW {
+            metrics.record(Duration::from_millis(100));
+        }
+        assert_eq!(metrics.tick_count, 2 * METRICS_WINDOW as u64);
+        assert_eq!(metrics.max_tick_duration(), Duration::from_millis(100));
+        assert_eq!(metrics.ticks_per_second(), 10.0);
+    }
+}
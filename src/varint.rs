@@ -15,6 +15,9 @@ const SHIFT_VALUE: u32 = 7;
 /// The maximum number of bytes a single VarInt can occupy in the buffer.
 const VARINT_BITS_SIZE: usize = 5;
 
+/// The maximum number of bytes a single VarLong can occupy in the buffer.
+const VARLONG_BITS_SIZE: usize = 10;
+
 /// Errors that can occur when encoding or decoding a Minecraft protocol VarInt.
 #[derive(Error, Debug)]
 pub enum VarIntError {
@@ -108,6 +111,69 @@ impl Writeable for VarInt {
     }
 }
 
+/// Reads Minecraft protocol VarLong from a provided buffer slice. It returns
+/// the read VarLong and the number of read bytes.
+fn read_varlong(buffer: &[u8]) -> Result<(i64, usize), VarIntError> {
+    let mut result: i64 = 0;
+
+    for (read_bytes, byte) in buffer.iter().take(VARLONG_BITS_SIZE).enumerate() {
+        result |= ((byte & SEGMENT_BITS) as i64) << (read_bytes as u32 * SHIFT_VALUE);
+        if (byte & CONTINUE_BIT) == 0 {
+            return Ok((result, read_bytes + 1));
+        }
+    }
+
+    if buffer.len() < VARLONG_BITS_SIZE {
+        Err(VarIntError::Incomplete)
+    } else {
+        Err(VarIntError::TooManyBytes)
+    }
+}
+
+/// Writes the provided number as a Minecraft protocol VarLong. Returns the
+/// buffer and the number of bytes written.
+fn write_varlong(value: i64) -> ([u8; VARLONG_BITS_SIZE], usize) {
+    let mut buffer = [0u8; VARLONG_BITS_SIZE];
+    let mut value = value as u64;
+    let mut i = 0;
+
+    loop {
+        let mut temp = (value as u8) & SEGMENT_BITS;
+        value >>= SHIFT_VALUE;
+
+        if value != 0 {
+            temp |= CONTINUE_BIT;
+        }
+
+        buffer[i] = temp;
+        i += 1;
+
+        if value == 0 {
+            break;
+        }
+    }
+
+    (buffer, i)
+}
+
+/// Representation of a `VarLong` - the 64-bit variant of `VarInt`.
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub struct VarLong(pub i64);
+
+impl Readable for VarLong {
+    fn read(buffer: &[u8]) -> Result<(Self, usize), crate::protocol::ReadError> {
+        let (value, read_length) = read_varlong(buffer)?;
+        Ok((Self(value), read_length))
+    }
+}
+
+impl Writeable for VarLong {
+    fn write(&self) -> Result<bytes::Bytes, crate::protocol::WriteError> {
+        let (buffer, written_bytes) = write_varlong(self.0);
+        Ok(bytes::Bytes::copy_from_slice(&buffer[..written_bytes]))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -146,4 +212,17 @@ mod tests {
         let buf = [0x80, 0x80];
         assert!(matches!(read_varint(&buf), Err(VarIntError::Incomplete)));
     }
+
+    #[test]
+    fn test_varlong() {
+        for value in [0, 300, -1, i64::MAX, i64::MIN] {
+            let (buf, len) = write_varlong(value);
+            assert_eq!(read_varlong(&buf[..len]).unwrap(), (value, len));
+        }
+        assert_eq!(write_varlong(-1).1, 10);
+        assert!(matches!(
+            read_varlong(&[0x80, 0x80]),
+            Err(VarIntError::Incomplete)
+        ));
+    }
 }
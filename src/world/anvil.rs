@@ -0,0 +1,1229 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use flate2::{
+    Compression,
+    read::{GzDecoder, ZlibDecoder},
+    write::ZlibEncoder,
+};
+use thiserror::Error;
+
+use crate::{
+    protocol::{
+        ReadError,
+        nbt::{NbtCompound, NbtTag},
+    },
+    registry::REGISTRY,
+    varint::VarInt,
+    world::{
+        Chunk, ChunkSection, DEFAULT_BIOME,
+        block::{BlockState, BlockStateError},
+        block_entity::BlockEntity,
+        generator::WorldHeight,
+        light::{ChunkLight, LightArray, MAX_LIGHT_LEVEL},
+        palette::{pack_data_array, unpack_data_array},
+    },
+};
+
+/// Size of a sector of the region file, which is the unit of the chunk
+/// offsets and sizes.
+const SECTOR_SIZE: usize = 4096;
+
+/// Amount of sectors taken by the header of the region file: the locations
+/// of the chunks and the times they were saved at.
+const HEADER_SECTORS: usize = 2;
+
+/// Amount of chunks on each side of a region.
+const REGION_SIZE: i32 = 32;
+
+/// Bit of the compression type which means that the chunk is stored in a
+/// separate `.mcc` file, as it's too big for the region.
+const EXTERNAL_CHUNK_FLAG: u8 = 0x80;
+
+const COMPRESSION_GZIP: u8 = 1;
+const COMPRESSION_ZLIB: u8 = 2;
+const COMPRESSION_NONE: u8 = 3;
+const COMPRESSION_LZ4: u8 = 4;
+
+/// Magic of the blocks written by `LZ4BlockOutputStream` of lz4-java.
+const LZ4_MAGIC: &[u8] = b"LZ4Block";
+const LZ4_METHOD_RAW: u8 = 0x10;
+const LZ4_METHOD_LZ4: u8 = 0x20;
+
+/// Version of the world data of the supported game version (1.21.5), which
+/// tells vanilla that the chunks don't have to be upgraded.
+const DATA_VERSION: i32 = 4325;
+
+/// Status of the chunks that were generated completely. Others are in the
+/// middle of the world generation.
+const FULL_CHUNK_STATUS: &str = "minecraft:full";
+
+/// Keys of the chunk NBT written by the server. The others are kept as they
+/// were stored when the chunk is saved again.
+const SAVED_CHUNK_KEYS: [&str; 6] = [
+    "DataVersion",
+    "Status",
+    "isLightOn",
+    "sections",
+    "Heightmaps",
+    "block_entities",
+];
+
+/// Errors that can occur while reading the Anvil world files.
+#[derive(Debug, Error)]
+pub enum AnvilError {
+    /// Indicates that a region file couldn't be read.
+    #[error("I/O error has occurred: {0}")]
+    IoError(#[from] io::Error),
+    /// Indicates that the NBT of a chunk is malformed.
+    #[error("failed to read the chunk NBT: {0}")]
+    NbtError(#[from] ReadError),
+    /// Indicates that the chunk is compressed with an unknown algorithm.
+    #[error("unknown chunk compression: {0}")]
+    UnknownCompression(u8),
+    /// Indicates that the LZ4 compressed chunk couldn't be decompressed.
+    #[error("failed to decompress the LZ4 block: {0}")]
+    Lz4Error(#[from] lz4_flex::block::DecompressError),
+    /// Indicates that the region file or the chunk doesn't follow the format.
+    #[error("malformed chunk: {0}")]
+    MalformedChunk(&'static str),
+}
+
+/// A single `r.X.Z.mca` file, which holds 32x32 chunks.
+pub struct RegionFile {
+    path: PathBuf,
+    file: File,
+    /// Offsets and sizes (in sectors) of the chunks, indexed by
+    /// `local_x + local_z * 32`. Zero means the chunk isn't stored.
+    locations: [(u32, u8); 1024],
+    /// Whether each sector of the file is occupied, either by the header or
+    /// by a chunk.
+    used_sectors: Vec<bool>,
+}
+
+impl RegionFile {
+    /// Opens the region file and reads its header. The file is created if it
+    /// doesn't exist yet.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, AnvilError> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        // the header takes two sectors: locations and timestamps
+        let mut length = file.metadata()?.len() as usize;
+        if length < HEADER_SECTORS * SECTOR_SIZE {
+            file.set_len((HEADER_SECTORS * SECTOR_SIZE) as u64)?;
+            length = HEADER_SECTORS * SECTOR_SIZE;
+        }
+
+        let mut header = vec![0; SECTOR_SIZE];
+        file.read_exact(&mut header)?;
+
+        let mut used_sectors = vec![false; length.div_ceil(SECTOR_SIZE)];
+        used_sectors[..HEADER_SECTORS].fill(true);
+
+        let mut locations = [(0, 0); 1024];
+        for (location, entry) in locations.iter_mut().zip(header.chunks_exact(4)) {
+            let offset = u32::from_be_bytes([0, entry[0], entry[1], entry[2]]);
+            *location = (offset, entry[3]);
+
+            let sectors = offset as usize..offset as usize + entry[3] as usize;
+            if offset as usize >= HEADER_SECTORS && sectors.end <= used_sectors.len() {
+                used_sectors[sectors].fill(true);
+            }
+        }
+
+        Ok(Self {
+            path,
+            file,
+            locations,
+            used_sectors,
+        })
+    }
+
+    /// Reads the NBT of the chunk at the provided coordinates within the
+    /// region. Returns `None` if the chunk isn't stored.
+    pub fn read_chunk_nbt(
+        &mut self,
+        local_x: usize,
+        local_z: usize,
+    ) -> Result<Option<NbtCompound>, AnvilError> {
+        let (offset, sector_count) = self.locations[local_x + local_z * REGION_SIZE as usize];
+        if offset == 0 || sector_count == 0 {
+            return Ok(None);
+        }
+
+        self.file
+            .seek(SeekFrom::Start(offset as u64 * SECTOR_SIZE as u64))?;
+        let mut header = [0; 5];
+        self.file.read_exact(&mut header)?;
+
+        // the length includes the compression type byte
+        let length = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+        if length == 0 || length > sector_count as usize * SECTOR_SIZE {
+            return Err(AnvilError::MalformedChunk(
+                "chunk length exceeds its sectors",
+            ));
+        }
+        let compression = header[4];
+
+        let data = if compression & EXTERNAL_CHUNK_FLAG != 0 {
+            std::fs::read(self.external_chunk_path(local_x, local_z))?
+        } else {
+            let mut data = vec![0; length - 1];
+            self.file.read_exact(&mut data)?;
+            data
+        };
+
+        let data = decompress(&data, compression & !EXTERNAL_CHUNK_FLAG)?;
+        match NbtTag::read_named(&data)?.1 {
+            NbtTag::Compound(compound) => Ok(Some(compound)),
+            _ => Err(AnvilError::MalformedChunk("chunk root isn't a compound")),
+        }
+    }
+
+    /// Writes the NBT of the chunk at the provided coordinates within the
+    /// region, compressed with zlib. Chunks that don't fit into 255 sectors
+    /// are stored in a separate `.mcc` file, as vanilla does.
+    pub fn write_chunk_nbt(
+        &mut self,
+        local_x: usize,
+        local_z: usize,
+        nbt: &NbtCompound,
+    ) -> Result<(), AnvilError> {
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(&NbtTag::Compound(nbt.clone()).to_named_bytes(""))?;
+        let data = encoder.finish()?;
+
+        let external_path = self.external_chunk_path(local_x, local_z);
+        let is_external = (data.len() + 5).div_ceil(SECTOR_SIZE) > u8::MAX as usize;
+        let mut payload = Vec::with_capacity(SECTOR_SIZE);
+        if is_external {
+            std::fs::write(&external_path, &data)?;
+            payload.extend_from_slice(&1u32.to_be_bytes());
+            payload.push(COMPRESSION_ZLIB | EXTERNAL_CHUNK_FLAG);
+        } else {
+            payload.extend_from_slice(&(data.len() as u32 + 1).to_be_bytes());
+            payload.push(COMPRESSION_ZLIB);
+            payload.extend_from_slice(&data);
+        }
+        let sector_count = payload.len().div_ceil(SECTOR_SIZE);
+        payload.resize(sector_count * SECTOR_SIZE, 0);
+
+        // the stored chunk stays intact until the header points to the new
+        // sectors, so a failed write never leaves it half-written
+        let offset = self.allocate_sectors(sector_count);
+        self.file
+            .seek(SeekFrom::Start((offset * SECTOR_SIZE) as u64))?;
+        self.file.write_all(&payload)?;
+        self.file.flush()?;
+
+        let index = local_x + local_z * REGION_SIZE as usize;
+        let (old_offset, old_sector_count) = self.locations[index];
+        self.locations[index] = (offset as u32, sector_count as u8);
+        let location = ((offset as u32) << 8) | sector_count as u32;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs() as u32);
+        self.file.seek(SeekFrom::Start(index as u64 * 4))?;
+        self.file.write_all(&location.to_be_bytes())?;
+        self.file
+            .seek(SeekFrom::Start((SECTOR_SIZE + index * 4) as u64))?;
+        self.file.write_all(&timestamp.to_be_bytes())?;
+        self.file.flush()?;
+
+        if old_offset as usize >= HEADER_SECTORS {
+            let old_sectors = old_offset as usize..old_offset as usize + old_sector_count as usize;
+            if old_sectors.end <= self.used_sectors.len() {
+                self.used_sectors[old_sectors].fill(false);
+            }
+        }
+        if !is_external && external_path.exists() {
+            std::fs::remove_file(&external_path)?;
+        }
+        Ok(())
+    }
+
+    /// Marks the first run of the provided amount of free sectors as used,
+    /// growing the file if there is none, and returns its offset.
+    fn allocate_sectors(&mut self, count: usize) -> usize {
+        let mut run_start = HEADER_SECTORS;
+        for sector in HEADER_SECTORS..self.used_sectors.len() {
+            if self.used_sectors[sector] {
+                run_start = sector + 1;
+            } else if sector + 1 - run_start == count {
+                break;
+            }
+        }
+
+        if run_start + count > self.used_sectors.len() {
+            self.used_sectors.resize(run_start + count, false);
+        }
+        self.used_sectors[run_start..run_start + count].fill(true);
+        run_start
+    }
+
+    /// Returns the path of the `c.X.Z.mcc` file of the chunk, which is stored
+    /// next to the region file and uses the absolute chunk coordinates.
+    fn external_chunk_path(&self, local_x: usize, local_z: usize) -> PathBuf {
+        let name = self.path.file_name().and_then(|name| name.to_str());
+        let (region_x, region_z) = name
+            .and_then(|name| {
+                let mut parts = name.split('.').skip(1);
+                Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+            })
+            .unwrap_or((0, 0));
+
+        self.path.with_file_name(format!(
+            "c.{}.{}.mcc",
+            region_x * REGION_SIZE + local_x as i32,
+            region_z * REGION_SIZE + local_z as i32
+        ))
+    }
+}
+
+/// Decompresses the chunk data compressed with the provided algorithm.
+fn decompress(data: &[u8], compression: u8) -> Result<Vec<u8>, AnvilError> {
+    let mut output = vec![];
+    match compression {
+        COMPRESSION_GZIP => {
+            GzDecoder::new(data).read_to_end(&mut output)?;
+        }
+        COMPRESSION_ZLIB => {
+            ZlibDecoder::new(data).read_to_end(&mut output)?;
+        }
+        COMPRESSION_NONE => output.extend_from_slice(data),
+        COMPRESSION_LZ4 => output = decompress_lz4_blocks(data)?,
+        _ => return Err(AnvilError::UnknownCompression(compression)),
+    }
+    Ok(output)
+}
+
+/// Decompresses the stream of blocks written by `LZ4BlockOutputStream` of
+/// lz4-java, which vanilla uses. Each block has a 21 bytes header: the magic,
+/// the method, compressed and decompressed lengths and the checksum, all
+/// little-endian. The stream ends with an empty block.
+fn decompress_lz4_blocks(mut data: &[u8]) -> Result<Vec<u8>, AnvilError> {
+    let mut output = vec![];
+    while !data.is_empty() {
+        let header = data
+            .get(..LZ4_MAGIC.len() + 13)
+            .filter(|header| header.starts_with(LZ4_MAGIC))
+            .ok_or(AnvilError::MalformedChunk("malformed LZ4 block header"))?;
+        let method = header[8] & 0xF0;
+        let read_u32 = |offset: usize| {
+            u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap()) as usize
+        };
+        let (compressed_length, decompressed_length) = (read_u32(9), read_u32(13));
+
+        data = &data[header.len()..];
+        if decompressed_length == 0 {
+            break;
+        }
+
+        let block = data
+            .get(..compressed_length)
+            .ok_or(AnvilError::MalformedChunk("LZ4 block is incomplete"))?;
+        match method {
+            LZ4_METHOD_RAW => output.extend_from_slice(block),
+            LZ4_METHOD_LZ4 => {
+                output.extend_from_slice(&lz4_flex::block::decompress(block, decompressed_length)?)
+            }
+            _ => return Err(AnvilError::MalformedChunk("unknown LZ4 block method")),
+        }
+        data = &data[compressed_length..];
+    }
+    Ok(output)
+}
+
+/// Chunk read from the world files.
+pub struct StoredChunk {
+    pub chunk: Chunk,
+    /// Whether the light was stored with the chunk. Otherwise, it has to be
+    /// calculated.
+    pub is_lit: bool,
+}
+
+/// Whether the NBT of a chunk from the world files is of a fully generated
+/// chunk.
+fn is_full_chunk(nbt: &NbtCompound) -> bool {
+    let status = nbt.get("Status").and_then(NbtTag::as_str);
+    status.is_none_or(|status| status == FULL_CHUNK_STATUS || status == "full")
+}
+
+/// Converts the NBT of a chunk from the world files into the chunk. Returns
+/// `None` if the chunk isn't fully generated yet.
+pub fn chunk_from_nbt(
+    nbt: &NbtCompound,
+    height: WorldHeight,
+    has_sky_light: bool,
+) -> Result<Option<StoredChunk>, AnvilError> {
+    if !is_full_chunk(nbt) {
+        return Ok(None);
+    }
+
+    let get_int = |key| {
+        nbt.get(key)
+            .and_then(NbtTag::as_i64)
+            .ok_or(AnvilError::MalformedChunk("chunk coordinates are missing"))
+    };
+    let mut chunk = height.empty_chunk(get_int("xPos")? as i32, get_int("zPos")? as i32);
+
+    let section_count = chunk.sections.len();
+    let min_section = height.min_y.div_euclid(16);
+    let mut sky_light: Vec<Option<LightArray>> = vec![None; section_count + 2];
+    let mut block_light = ChunkLight::new(section_count, has_sky_light).block;
+    let mut replaced_blocks = HashSet::new();
+
+    let sections = nbt
+        .get("sections")
+        .and_then(NbtTag::as_list)
+        .unwrap_or_default();
+    for section in sections.iter().filter_map(NbtTag::as_compound) {
+        let Some(section_y) = section.get("Y").and_then(NbtTag::as_i64) else {
+            return Err(AnvilError::MalformedChunk("section Y is missing"));
+        };
+        // light is stored for one more section below and above the world
+        let light_index = section_y - min_section as i64 + 1;
+        if !(0..section_count as i64 + 2).contains(&light_index) {
+            continue;
+        }
+        let light_index = light_index as usize;
+
+        let light_array = |key| {
+            let bytes = section.get(key).and_then(NbtTag::as_byte_array)?;
+            let bytes: Vec<u8> = bytes.iter().map(|&byte| byte as u8).collect();
+            LightArray::from_bytes(&bytes)
+        };
+        sky_light[light_index] = light_array("SkyLight");
+        if let Some(array) = light_array("BlockLight") {
+            block_light[light_index] = array;
+        }
+
+        if (1..=section_count).contains(&light_index) {
+            let LoadedSection {
+                section: chunk_section,
+                substitutions,
+                replaced,
+            } = section_from_nbt(section)?;
+            for (substitute, e) in substitutions {
+                eprintln!(
+                    "Block of chunk ({}, {}) is replaced with {substitute}: {e}",
+                    chunk.x, chunk.z
+                );
+                chunk.is_lossy = true;
+            }
+            let base_y = (light_index - 1) * 16;
+            replaced_blocks.extend(
+                replaced
+                    .into_iter()
+                    .map(|index| (index & 15, base_y + (index >> 8), (index >> 4) & 15)),
+            );
+            chunk.sections[light_index - 1] = Some(chunk_section);
+        }
+    }
+    chunk.recalculate_heightmaps();
+
+    let is_lit = nbt
+        .get("isLightOn")
+        .and_then(NbtTag::as_i64)
+        .is_some_and(|value| value != 0);
+    chunk.light = ChunkLight::new(section_count, has_sky_light);
+    if is_lit {
+        chunk.light.block = block_light;
+        if has_sky_light {
+            chunk.light.sky = fill_missing_sky_light(sky_light);
+        }
+    }
+
+    let block_entities = nbt
+        .get("block_entities")
+        .and_then(NbtTag::as_list)
+        .unwrap_or_default();
+    chunk.block_entities = block_entities
+        .iter()
+        .filter_map(NbtTag::as_compound)
+        .filter_map(|compound| BlockEntity::from_nbt(compound, height.min_y))
+        .filter(|block_entity| block_entity.y < chunk.height())
+        .filter(|block_entity| {
+            // the block entity belonged to a block that was replaced
+            let position = (block_entity.x, block_entity.y, block_entity.z);
+            let is_orphaned = replaced_blocks.contains(&position);
+            if is_orphaned {
+                eprintln!(
+                    "Block entity {} of chunk ({}, {}) is dropped, as its block was replaced",
+                    block_entity.id, chunk.x, chunk.z
+                );
+            }
+            !is_orphaned
+        })
+        .collect();
+
+    Ok(Some(StoredChunk { chunk, is_lit }))
+}
+
+/// Converts the chunk into its NBT in the world files, the same way as
+/// vanilla of the supported version stores the fully generated chunks.
+pub fn chunk_to_nbt(chunk: &Chunk, height: WorldHeight) -> NbtCompound {
+    let min_section = height.min_y.div_euclid(16);
+    let light = &chunk.light;
+
+    let mut sections = vec![];
+    for light_index in 0..chunk.sections.len() + 2 {
+        let mut section = NbtCompound::from([(
+            String::from("Y"),
+            NbtTag::Byte((min_section - 1 + light_index as i32) as i8),
+        )]);
+
+        let in_world = (1..=chunk.sections.len()).contains(&light_index);
+        if in_world {
+            let chunk_section = chunk.sections[light_index - 1].unwrap_or_default();
+            section.extend(section_to_nbt(&chunk_section));
+        }
+
+        let mut has_light = false;
+        let arrays = [("BlockLight", &light.block), ("SkyLight", &light.sky)];
+        for (key, arrays) in arrays {
+            if key == "SkyLight" && !light.has_sky_light {
+                continue;
+            }
+            let array = &arrays[light_index];
+            // vanilla restores the missing sky light from above, so it has to
+            // be stored even when it's empty
+            if !array.is_empty() || key == "SkyLight" {
+                let bytes = array.as_bytes().iter().map(|&byte| byte as i8).collect();
+                section.insert(key.to_string(), NbtTag::ByteArray(bytes));
+                has_light = true;
+            }
+        }
+
+        if in_world || has_light {
+            sections.push(NbtTag::Compound(section));
+        }
+    }
+
+    let heightmaps = chunk
+        .heightmaps
+        .iter()
+        .map(|heightmap| {
+            let packed = heightmap.pack().into_iter().map(|word| word as i64);
+            (
+                heightmap.kind.nbt_name().to_string(),
+                NbtTag::LongArray(packed.collect()),
+            )
+        })
+        .collect();
+
+    let block_entities = chunk
+        .block_entities
+        .iter()
+        .map(|block_entity| NbtTag::Compound(block_entity.to_nbt(chunk.x, chunk.z, height.min_y)))
+        .collect();
+
+    NbtCompound::from([
+        (String::from("DataVersion"), NbtTag::Int(DATA_VERSION)),
+        (String::from("xPos"), NbtTag::Int(chunk.x)),
+        (String::from("yPos"), NbtTag::Int(min_section)),
+        (String::from("zPos"), NbtTag::Int(chunk.z)),
+        (
+            String::from("Status"),
+            NbtTag::String(FULL_CHUNK_STATUS.to_string()),
+        ),
+        (String::from("LastUpdate"), NbtTag::Long(0)),
+        (String::from("InhabitedTime"), NbtTag::Long(0)),
+        (String::from("isLightOn"), NbtTag::Byte(1)),
+        (String::from("sections"), NbtTag::List(sections)),
+        (String::from("Heightmaps"), NbtTag::Compound(heightmaps)),
+        (String::from("block_entities"), NbtTag::List(block_entities)),
+        (String::from("block_ticks"), NbtTag::List(vec![])),
+        (String::from("fluid_ticks"), NbtTag::List(vec![])),
+        (String::from("PostProcessing"), NbtTag::List(vec![])),
+        (
+            String::from("structures"),
+            NbtTag::Compound(NbtCompound::from([
+                (
+                    String::from("References"),
+                    NbtTag::Compound(NbtCompound::new()),
+                ),
+                (String::from("starts"), NbtTag::Compound(NbtCompound::new())),
+            ])),
+        ),
+    ])
+}
+
+/// Converts the section into the `block_states` and `biomes` containers of
+/// the section compound.
+fn section_to_nbt(section: &ChunkSection) -> NbtCompound {
+    let (palette, indices) = build_palette(&section.block_states);
+    let palette = palette
+        .into_iter()
+        .map(|state| {
+            let mut entry = NbtCompound::from([(
+                String::from("Name"),
+                NbtTag::String(state.name().to_string()),
+            )]);
+            let properties: NbtCompound = state
+                .properties()
+                .map(|(property, value)| (property.to_string(), NbtTag::String(value.to_string())))
+                .collect();
+            if !properties.is_empty() {
+                entry.insert(String::from("Properties"), NbtTag::Compound(properties));
+            }
+            NbtTag::Compound(entry)
+        })
+        .collect();
+    let block_states = paletted_container_nbt(palette, &indices, 4);
+
+    let (palette, indices) = build_palette(&section.biomes);
+    let palette = palette
+        .into_iter()
+        .map(|biome| {
+            let name = REGISTRY.biome_name(biome).unwrap_or(DEFAULT_BIOME);
+            NbtTag::String(name.to_string())
+        })
+        .collect();
+    let biomes = paletted_container_nbt(palette, &indices, 1);
+
+    NbtCompound::from([
+        (String::from("block_states"), NbtTag::Compound(block_states)),
+        (String::from("biomes"), NbtTag::Compound(biomes)),
+    ])
+}
+
+/// Returns the distinct values in the order of their first appearance and
+/// the index of each value in them.
+fn build_palette<T: Copy + PartialEq>(values: &[T]) -> (Vec<T>, Vec<u32>) {
+    let mut palette: Vec<T> = vec![];
+    let indices = values
+        .iter()
+        .map(
+            |value| match palette.iter().position(|entry| entry == value) {
+                Some(index) => index as u32,
+                None => {
+                    palette.push(*value);
+                    palette.len() as u32 - 1
+                }
+            },
+        )
+        .collect();
+    (palette, indices)
+}
+
+/// Builds the paletted container compound. See `palette_indices` for how
+/// the bits per entry are chosen.
+fn paletted_container_nbt(palette: Vec<NbtTag>, indices: &[u32], min_bits: u8) -> NbtCompound {
+    let palette_length = palette.len();
+    let mut container = NbtCompound::from([(String::from("palette"), NbtTag::List(palette))]);
+    if palette_length > 1 {
+        let bits_per_entry =
+            ((usize::BITS - (palette_length - 1).leading_zeros()) as u8).max(min_bits);
+        let data = pack_data_array(indices, bits_per_entry);
+        container.insert(
+            String::from("data"),
+            NbtTag::LongArray(data.into_iter().map(|word| word as i64).collect()),
+        );
+    }
+    container
+}
+
+/// Restores the sky light of the sections that weren't stored. Vanilla omits
+/// them when each column of the section has the same light as the lowest
+/// block above it, so the light is copied down from the section above.
+fn fill_missing_sky_light(arrays: Vec<Option<LightArray>>) -> Vec<LightArray> {
+    let mut filled: Vec<LightArray> = Vec::with_capacity(arrays.len());
+    for array in arrays.into_iter().rev() {
+        let array = array.unwrap_or_else(|| match filled.last() {
+            Some(above) => {
+                let mut array = LightArray::filled(0);
+                for x in 0..16 {
+                    for z in 0..16 {
+                        let level = above.get(x, 0, z);
+                        for y in 0..16 {
+                            array.set(x, y, z, level);
+                        }
+                    }
+                }
+                array
+            }
+            None => LightArray::filled(MAX_LIGHT_LEVEL),
+        });
+        filled.push(array);
+    }
+    filled.reverse();
+    filled
+}
+
+/// Section converted from its compound in the world files.
+struct LoadedSection {
+    section: ChunkSection,
+    /// Substitutes of the block states that aren't in the registry, with the
+    /// reason.
+    substitutions: Vec<(BlockState, BlockStateError)>,
+    /// Indices of the blocks that were replaced with another block, so their
+    /// block entities don't belong to them anymore.
+    replaced: Vec<usize>,
+}
+
+/// Converts a section compound into the section, resolving its palettes
+/// through the block and biome registries. Block states that aren't in the
+/// registry are replaced.
+fn section_from_nbt(nbt: &NbtCompound) -> Result<LoadedSection, AnvilError> {
+    let mut section = ChunkSection::default();
+    let mut substitutions = vec![];
+    let mut replaced = vec![];
+
+    if let Some(block_states) = nbt.get("block_states").and_then(NbtTag::as_compound) {
+        let mut is_replaced = vec![];
+        let palette: Vec<BlockState> = palette_entries(block_states)
+            .iter()
+            .map(|entry| {
+                let entry = entry.as_compound()?;
+                let name = entry.get("Name")?.as_str()?;
+                let properties: Vec<(&str, &str)> = entry
+                    .get("Properties")
+                    .and_then(NbtTag::as_compound)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|(property, value)| Some((property.as_str(), value.as_str()?)))
+                    .collect();
+                let state = match BlockState::with_properties_lossy(name, &properties) {
+                    Ok(state) => {
+                        is_replaced.push(false);
+                        state
+                    }
+                    Err((substitute, e)) => {
+                        // otherwise, only some properties of the block were lost
+                        is_replaced.push(matches!(e, BlockStateError::UnknownBlock(_)));
+                        substitutions.push((substitute, e));
+                        substitute
+                    }
+                };
+                Some(state)
+            })
+            .collect::<Option<_>>()
+            .ok_or(AnvilError::MalformedChunk("malformed block state palette"))?;
+
+        let indices = palette_indices(block_states, palette.len(), 4096, 4)?;
+        for (block_index, (block_state, index)) in
+            section.block_states.iter_mut().zip(indices).enumerate()
+        {
+            *block_state = palette[index];
+            if is_replaced[index] {
+                replaced.push(block_index);
+            }
+        }
+    }
+
+    if let Some(biomes) = nbt.get("biomes").and_then(NbtTag::as_compound) {
+        let default_biome = REGISTRY.biome_id(DEFAULT_BIOME).unwrap();
+        let palette: Vec<VarInt> = palette_entries(biomes)
+            .iter()
+            .map(|entry| {
+                let name = entry.as_str()?;
+                Some(REGISTRY.biome_id(name).unwrap_or(default_biome))
+            })
+            .collect::<Option<_>>()
+            .ok_or(AnvilError::MalformedChunk("malformed biome palette"))?;
+
+        let indices = palette_indices(biomes, palette.len(), 64, 1)?;
+        for (biome, index) in section.biomes.iter_mut().zip(indices) {
+            *biome = palette[index];
+        }
+    }
+
+    Ok(LoadedSection {
+        section,
+        substitutions,
+        replaced,
+    })
+}
+
+fn palette_entries(container: &NbtCompound) -> &[NbtTag] {
+    container
+        .get("palette")
+        .and_then(NbtTag::as_list)
+        .unwrap_or_default()
+}
+
+/// Unpacks the palette indices of a container. Unlike the network format,
+/// the amount of bits per entry isn't stored and depends only on the size of
+/// the palette, but never goes below `min_bits`. Containers with a single
+/// palette entry don't have the data at all.
+fn palette_indices(
+    container: &NbtCompound,
+    palette_length: usize,
+    count: usize,
+    min_bits: u8,
+) -> Result<Vec<usize>, AnvilError> {
+    match palette_length {
+        0 => Err(AnvilError::MalformedChunk("palette is empty")),
+        1 => Ok(vec![0; count]),
+        _ => {
+            let bits_per_entry =
+                ((usize::BITS - (palette_length - 1).leading_zeros()) as u8).max(min_bits);
+            let data = container
+                .get("data")
+                .and_then(NbtTag::as_long_array)
+                .ok_or(AnvilError::MalformedChunk("paletted data is missing"))?;
+            let data: Vec<u64> = data.iter().map(|&word| word as u64).collect();
+
+            unpack_data_array(&data, bits_per_entry, count)
+                .into_iter()
+                .map(|index| {
+                    let index = index as usize;
+                    (index < palette_length)
+                        .then_some(index)
+                        .ok_or(AnvilError::MalformedChunk("palette index is out of bounds"))
+                })
+                .collect()
+        }
+    }
+}
+
+/// Reads and writes the chunks of a dimension directory in the vanilla
+/// format, which contains the `region` directory. Region files are opened
+/// once and kept open.
+pub struct RegionStorage {
+    directory: PathBuf,
+    height: WorldHeight,
+    has_sky_light: bool,
+    /// Opened region files by their coordinates, or `None` if there is no
+    /// such file.
+    regions: HashMap<(i32, i32), Option<RegionFile>>,
+}
+
+impl RegionStorage {
+    pub fn new(directory: impl Into<PathBuf>, height: WorldHeight, has_sky_light: bool) -> Self {
+        Self {
+            directory: directory.into(),
+            height,
+            has_sky_light,
+            regions: HashMap::new(),
+        }
+    }
+
+    /// Returns the region file containing the provided chunk. If there is no
+    /// such file, it's created only when `create` is set.
+    fn region(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+        create: bool,
+    ) -> Result<Option<&mut RegionFile>, AnvilError> {
+        let region_position = (
+            chunk_x.div_euclid(REGION_SIZE),
+            chunk_z.div_euclid(REGION_SIZE),
+        );
+        let region = self.regions.entry(region_position).or_insert(None);
+        if region.is_none() {
+            let region_directory = self.directory.join("region");
+            let path =
+                region_directory.join(format!("r.{}.{}.mca", region_position.0, region_position.1));
+            if path.exists() || create {
+                std::fs::create_dir_all(region_directory)?;
+                *region = Some(RegionFile::open(path)?);
+            }
+        }
+        Ok(region.as_mut())
+    }
+
+    /// Loads the chunk at the provided chunk coordinates. Returns `None` if
+    /// it isn't stored or isn't fully generated.
+    pub fn load_chunk(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<Option<StoredChunk>, AnvilError> {
+        let Some(region) = self.region(chunk_x, chunk_z, false)? else {
+            return Ok(None);
+        };
+        let Some(nbt) = region.read_chunk_nbt(
+            chunk_x.rem_euclid(REGION_SIZE) as usize,
+            chunk_z.rem_euclid(REGION_SIZE) as usize,
+        )?
+        else {
+            return Ok(None);
+        };
+
+        chunk_from_nbt(&nbt, self.height, self.has_sky_light)
+    }
+
+    /// Saves the chunk into its region file, replacing the stored one.
+    /// Chunks whose blocks were replaced when they were loaded are skipped,
+    /// as the stored blocks would be lost.
+    pub fn save_chunk(&mut self, chunk: &Chunk) -> Result<(), AnvilError> {
+        if chunk.is_lossy {
+            eprintln!(
+                "Chunk ({}, {}) isn't saved, as some of its blocks were replaced",
+                chunk.x, chunk.z
+            );
+            return Ok(());
+        }
+        let mut nbt = chunk_to_nbt(chunk, self.height);
+        let region = self.region(chunk.x, chunk.z, true)?.unwrap();
+        let local_x = chunk.x.rem_euclid(REGION_SIZE) as usize;
+        let local_z = chunk.z.rem_euclid(REGION_SIZE) as usize;
+
+        // the stored chunk keeps everything the server doesn't handle, like
+        // the structures and the scheduled ticks
+        match region.read_chunk_nbt(local_x, local_z) {
+            Ok(Some(mut stored)) if is_full_chunk(&stored) => {
+                for key in SAVED_CHUNK_KEYS {
+                    if let Some(tag) = nbt.remove(key) {
+                        stored.insert(key.to_string(), tag);
+                    }
+                }
+                nbt = stored;
+            }
+            Ok(_) => {}
+            Err(error) => eprintln!(
+                "Chunk ({}, {}) is stored malformed and will be replaced: {error}",
+                chunk.x, chunk.z
+            ),
+        }
+        region.write_chunk_nbt(local_x, local_z, &nbt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{Compression, write::ZlibEncoder};
+
+    use super::*;
+    use crate::world::light::LightKind;
+
+    fn long_array(entries: &[u32], bits_per_entry: u8) -> NbtTag {
+        let words = crate::world::palette::pack_data_array(entries, bits_per_entry);
+        NbtTag::LongArray(words.into_iter().map(|word| word as i64).collect())
+    }
+
+    fn test_chunk_nbt() -> NbtCompound {
+        let block_state = |name: &str, properties: &[(&str, &str)]| {
+            let mut entry =
+                NbtCompound::from([(String::from("Name"), NbtTag::String(name.to_string()))]);
+            if !properties.is_empty() {
+                let properties = properties
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), NbtTag::String(value.to_string())))
+                    .collect();
+                entry.insert(String::from("Properties"), NbtTag::Compound(properties));
+            }
+            NbtTag::Compound(entry)
+        };
+
+        // the bottom layer is stone, with stairs in the corner
+        let mut indices = vec![0; 4096];
+        indices[..256].fill(1);
+        indices[0] = 2;
+        let bottom_section = NbtCompound::from([
+            (String::from("Y"), NbtTag::Byte(-4)),
+            (
+                String::from("block_states"),
+                NbtTag::Compound(NbtCompound::from([
+                    (
+                        String::from("palette"),
+                        NbtTag::List(vec![
+                            block_state("minecraft:air", &[]),
+                            block_state("minecraft:stone", &[]),
+                            block_state(
+                                "minecraft:oak_stairs",
+                                &[("facing", "east"), ("half", "top")],
+                            ),
+                        ]),
+                    ),
+                    (String::from("data"), long_array(&indices, 4)),
+                ])),
+            ),
+            (
+                String::from("biomes"),
+                NbtTag::Compound(NbtCompound::from([(
+                    String::from("palette"),
+                    NbtTag::List(vec![NbtTag::String(String::from("minecraft:desert"))]),
+                )])),
+            ),
+        ]);
+
+        let chest = NbtCompound::from([
+            (
+                String::from("id"),
+                NbtTag::String(String::from("minecraft:chest")),
+            ),
+            (String::from("x"), NbtTag::Int(-30)),
+            (String::from("y"), NbtTag::Int(-63)),
+            (String::from("z"), NbtTag::Int(33)),
+            (String::from("Items"), NbtTag::List(vec![])),
+        ]);
+
+        NbtCompound::from([
+            (String::from("xPos"), NbtTag::Int(-2)),
+            (String::from("zPos"), NbtTag::Int(2)),
+            (
+                String::from("Status"),
+                NbtTag::String(String::from("minecraft:full")),
+            ),
+            (
+                String::from("sections"),
+                NbtTag::List(vec![NbtTag::Compound(bottom_section)]),
+            ),
+            (
+                String::from("block_entities"),
+                NbtTag::List(vec![NbtTag::Compound(chest)]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_chunk_from_nbt() {
+        let height = WorldHeight {
+            min_y: -64,
+            height: 384,
+        };
+        let stored = chunk_from_nbt(&test_chunk_nbt(), height, true)
+            .unwrap()
+            .unwrap();
+        assert!(!stored.is_lit);
+
+        let chunk = stored.chunk;
+        assert_eq!((chunk.x, chunk.z), (-2, 2));
+        assert_eq!(
+            chunk.get_block_at(0, 0, 0),
+            BlockState::parse("oak_stairs[facing=east,half=top]").unwrap()
+        );
+        assert_eq!(
+            chunk.get_block_at(5, 0, 9),
+            BlockState::new("stone").unwrap()
+        );
+        assert_eq!(chunk.get_block_at(5, 1, 9), BlockState::AIR);
+        assert_eq!(
+            chunk.sections[0].unwrap().biomes[0],
+            REGISTRY.biome_id("minecraft:desert").unwrap()
+        );
+
+        let block_entity = &chunk.block_entities[0];
+        assert_eq!((block_entity.x, block_entity.y, block_entity.z), (2, 1, 1));
+        assert_eq!(block_entity.id, "minecraft:chest");
+        assert_eq!(block_entity.data.keys().collect::<Vec<_>>(), ["Items"]);
+    }
+
+    #[test]
+    fn test_lossy_chunk_is_not_saved() {
+        let replace_palette_entry = |nbt: &mut NbtCompound, index: usize, name: &str| {
+            let Some(NbtTag::List(sections)) = nbt.get_mut("sections") else {
+                unreachable!();
+            };
+            let NbtTag::Compound(section) = &mut sections[0] else {
+                unreachable!();
+            };
+            let Some(NbtTag::Compound(block_states)) = section.get_mut("block_states") else {
+                unreachable!();
+            };
+            let Some(NbtTag::List(palette)) = block_states.get_mut("palette") else {
+                unreachable!();
+            };
+            palette[index] = NbtTag::Compound(NbtCompound::from([(
+                String::from("Name"),
+                NbtTag::String(name.to_string()),
+            )]));
+        };
+        let mut nbt = test_chunk_nbt();
+        replace_palette_entry(&mut nbt, 1, "minecraft:unknown_block");
+
+        let height = WorldHeight {
+            min_y: -64,
+            height: 384,
+        };
+        let chunk = chunk_from_nbt(&nbt, height, true).unwrap().unwrap().chunk;
+        assert!(chunk.is_lossy);
+        assert_eq!(
+            chunk.get_block_at(5, 0, 9),
+            BlockState::new("stone").unwrap()
+        );
+        assert_eq!(chunk.block_entities.len(), 1);
+
+        // the chest is in place of the replaced block, so it's dropped
+        replace_palette_entry(&mut nbt, 0, "minecraft:unknown_chest");
+        let replaced = chunk_from_nbt(&nbt, height, true).unwrap().unwrap().chunk;
+        assert!(replaced.block_entities.is_empty());
+
+        let directory = std::env::temp_dir().join(format!("kasumi-lossy-{}", std::process::id()));
+        RegionStorage::new(&directory, height, true)
+            .save_chunk(&chunk)
+            .unwrap();
+        assert!(!directory.exists());
+    }
+
+    #[test]
+    fn test_region_file() {
+        let mut nbt = test_chunk_nbt();
+        nbt.insert(String::from("isLightOn"), NbtTag::Byte(1));
+        let structures = NbtTag::Compound(NbtCompound::from([(
+            String::from("References"),
+            NbtTag::Compound(NbtCompound::new()),
+        )]));
+        nbt.insert(String::from("structures"), structures.clone());
+
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder
+            .write_all(&NbtTag::Compound(nbt).to_named_bytes(""))
+            .unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        // chunk (-2, 2) is at (30, 2) of the region (-1, 0)
+        let mut region = vec![0; SECTOR_SIZE * 2];
+        let index = (30 + 2 * 32) * 4;
+        let sectors = (compressed.len() + 5).div_ceil(SECTOR_SIZE);
+        region[index..index + 4].copy_from_slice(&[0, 0, 2, sectors as u8]);
+        region.extend_from_slice(&(compressed.len() as u32 + 1).to_be_bytes());
+        region.push(COMPRESSION_ZLIB);
+        region.extend_from_slice(&compressed);
+        region.resize((2 + sectors) * SECTOR_SIZE, 0);
+
+        let directory = std::env::temp_dir().join(format!("kasumi-anvil-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("region")).unwrap();
+        std::fs::write(directory.join("region/r.-1.0.mca"), region).unwrap();
+
+        let height = WorldHeight {
+            min_y: -64,
+            height: 384,
+        };
+        let mut storage = RegionStorage::new(&directory, height, true);
+        let stored = storage.load_chunk(-2, 2).unwrap().unwrap();
+        assert!(stored.is_lit);
+        assert_eq!(
+            stored.chunk.get_block_at(1, 0, 1),
+            BlockState::new("stone").unwrap()
+        );
+        // no sky light was stored, so it's full down from above the world
+        assert_eq!(stored.chunk.light.get(LightKind::Sky, 1, 0, 1), 15);
+        assert!(storage.load_chunk(-1, 2).unwrap().is_none());
+        assert!(storage.load_chunk(0, 0).unwrap().is_none());
+
+        // saving replaces the blocks but keeps what the server doesn't handle
+        let mut chunk = stored.chunk;
+        chunk.set_block_at(1, 0, 1, BlockState::new("bedrock").unwrap());
+        storage.save_chunk(&chunk).unwrap();
+        let saved = storage
+            .region(-2, 2, false)
+            .unwrap()
+            .unwrap()
+            .read_chunk_nbt(30, 2)
+            .unwrap()
+            .unwrap();
+        assert_eq!(saved.get("structures"), Some(&structures));
+        assert_eq!(saved.get("DataVersion"), Some(&NbtTag::Int(DATA_VERSION)));
+        assert_eq!(
+            storage
+                .load_chunk(-2, 2)
+                .unwrap()
+                .unwrap()
+                .chunk
+                .get_block_at(1, 0, 1),
+            BlockState::new("bedrock").unwrap()
+        );
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_save_and_load_chunk() {
+        let height = WorldHeight {
+            min_y: -64,
+            height: 384,
+        };
+        let mut chunk = height.empty_chunk(33, -1);
+        for x in 0..16 {
+            for z in 0..16 {
+                chunk.set_block_at(x, 0, z, BlockState::new("bedrock").unwrap());
+            }
+        }
+        chunk.set_block_at(
+            4,
+            200,
+            9,
+            BlockState::parse("oak_stairs[half=top]").unwrap(),
+        );
+        chunk.set_block_at(5, 1, 6, BlockState::new("chest").unwrap());
+        chunk.sections[3].as_mut().unwrap().biomes[5] =
+            REGISTRY.biome_id("minecraft:desert").unwrap();
+        chunk.block_entities.push(BlockEntity {
+            x: 5,
+            y: 1,
+            z: 6,
+            id: String::from("minecraft:chest"),
+            data: NbtCompound::from([(String::from("Lock"), NbtTag::String(String::from("a")))]),
+        });
+        chunk.light.set(LightKind::Block, 5, 2, 6, 14);
+
+        let directory = std::env::temp_dir().join(format!("kasumi-save-{}", std::process::id()));
+        let mut storage = RegionStorage::new(&directory, height, true);
+        // the sectors are freed only after the chunk is written elsewhere, so
+        // the third save reuses the ones of the first
+        let region_length = |storage: &mut RegionStorage| {
+            storage.save_chunk(&chunk).unwrap();
+            std::fs::metadata(directory.join("region/r.1.-1.mca"))
+                .unwrap()
+                .len() as usize
+        };
+        assert_eq!(region_length(&mut storage), 3 * SECTOR_SIZE);
+        assert_eq!(region_length(&mut storage), 4 * SECTOR_SIZE);
+        assert_eq!(region_length(&mut storage), 4 * SECTOR_SIZE);
+
+        let loaded = RegionStorage::new(&directory, height, true)
+            .load_chunk(33, -1)
+            .unwrap()
+            .unwrap();
+        assert!(loaded.is_lit);
+
+        let loaded = loaded.chunk;
+        for section in 0..chunk.sections.len() {
+            let (a, b) = (
+                chunk.sections[section].unwrap(),
+                loaded.sections[section].unwrap(),
+            );
+            assert_eq!(a.block_states, b.block_states);
+            assert_eq!(a.biomes, b.biomes);
+        }
+        assert_eq!(loaded.block_entities, chunk.block_entities);
+        assert_eq!(loaded.light.block, chunk.light.block);
+        assert_eq!(loaded.light.sky, chunk.light.sky);
+        for (a, b) in loaded.heightmaps.iter().zip(&chunk.heightmaps) {
+            assert_eq!(a.heights, b.heights);
+        }
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_lz4_blocks() {
+        let data = b"kasumi kasumi kasumi kasumi kasumi";
+        let compressed = lz4_flex::block::compress(data);
+
+        let mut stream = vec![];
+        for (method, block, length) in [
+            (LZ4_METHOD_LZ4, compressed.as_slice(), data.len()),
+            (LZ4_METHOD_RAW, b"!".as_slice(), 1),
+            (LZ4_METHOD_RAW, b"".as_slice(), 0),
+        ] {
+            stream.extend_from_slice(LZ4_MAGIC);
+            stream.push(method);
+            stream.extend_from_slice(&(block.len() as u32).to_le_bytes());
+            stream.extend_from_slice(&(length as u32).to_le_bytes());
+            stream.extend_from_slice(&[0; 4]);
+            stream.extend_from_slice(block);
+        }
+
+        let output = decompress(&stream, COMPRESSION_LZ4).unwrap();
+        assert_eq!(output, b"kasumi kasumi kasumi kasumi kasumi!");
+    }
+}
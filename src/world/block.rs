@@ -0,0 +1,709 @@
+use std::{collections::HashMap, fmt::Display, sync::LazyLock};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+    protocol::{ReadError, Readable, WriteError, Writeable},
+    varint::VarInt,
+};
+
+/// The global block registry, generated from the vanilla `blocks.json` report
+/// (see `generator.py`).
+pub static BLOCKS: LazyLock<BlockRegistry> = LazyLock::new(|| {
+    let raw_blocks_json = include_str!("../../blocks.json");
+    BlockRegistry::from_json(raw_blocks_json).unwrap()
+});
+
+/// Namespace that is used for the block names that were provided without one.
+const DEFAULT_NAMESPACE: &str = "minecraft";
+
+/// Errors that can occur while working with block states.
+#[derive(Debug, Error)]
+pub enum BlockStateError {
+    /// Indicates that there is no block with the provided name.
+    #[error("unknown block: {0}")]
+    UnknownBlock(String),
+    /// Indicates that there is no block state with the provided ID.
+    #[error("unknown block state ID: {0}")]
+    UnknownStateId(i32),
+    /// Indicates that the block doesn't have the provided property.
+    #[error("block {block} doesn't have property {property}")]
+    UnknownProperty { block: String, property: String },
+    /// Indicates that the property of the block can't take the provided value.
+    #[error("property {property} of block {block} can't be {value}")]
+    InvalidPropertyValue {
+        block: String,
+        property: String,
+        value: String,
+    },
+    /// Indicates that the block state string is malformed, i.e.
+    /// `minecraft:oak_stairs[facing`.
+    #[error("malformed block state: {0}")]
+    Malformed(String),
+    /// Indicates that the provided registry JSON couldn't be parsed.
+    #[error("failed to parse the block registry: {0}")]
+    JsonError(#[from] serde_json::Error),
+}
+
+/// Blocks (without the namespace) that don't block the movement, other than
+/// the ones matched by `NON_SOLID_SUFFIXES`. Those are the blocks without a
+/// collision, and the ones whose collision is much smaller than the block.
+const NON_SOLID_BLOCKS: &[&str] = &[
+    "air",
+    "cave_air",
+    "void_air",
+    "water",
+    "lava",
+    "bubble_column",
+    "short_grass",
+    "tall_grass",
+    "fern",
+    "large_fern",
+    "dead_bush",
+    "seagrass",
+    "tall_seagrass",
+    "kelp",
+    "kelp_plant",
+    "sugar_cane",
+    "cobweb",
+    "vine",
+    "fire",
+    "soul_fire",
+    "snow",
+    "redstone_wire",
+    "lever",
+    "tripwire",
+    "tripwire_hook",
+    "nether_portal",
+    "end_portal",
+    "structure_void",
+    "light",
+    "dandelion",
+    "poppy",
+    "blue_orchid",
+    "allium",
+    "azure_bluet",
+    "oxeye_daisy",
+    "cornflower",
+    "lily_of_the_valley",
+    "wither_rose",
+    "sunflower",
+    "lilac",
+    "rose_bush",
+    "peony",
+    "brown_mushroom",
+    "red_mushroom",
+    "wheat",
+    "carrots",
+    "potatoes",
+    "beetroots",
+    "lantern",
+    "soul_lantern",
+    "player_head",
+    "player_wall_head",
+    "zombie_head",
+    "zombie_wall_head",
+    "creeper_head",
+    "creeper_wall_head",
+    "dragon_head",
+    "dragon_wall_head",
+    "piglin_head",
+    "piglin_wall_head",
+];
+
+/// Suffixes of blocks (without the namespace) that don't block the movement.
+/// Signs, banners and pressure plates have no collision, but they count as
+/// blocking the movement in vanilla.
+const NON_SOLID_SUFFIXES: &[&str] = &["torch", "_sapling", "_tulip", "_button", "_skull", "rail"];
+
+/// Blocks (without the namespace) that are always filled with a fluid.
+const FLUID_BLOCKS: &[&str] = &[
+    "water",
+    "lava",
+    "bubble_column",
+    "seagrass",
+    "tall_seagrass",
+    "kelp",
+    "kelp_plant",
+];
+
+/// Blocks (without the namespace) that let the light through, other than the
+/// non-solid ones. Mostly blocks that aren't full cubes.
+const TRANSPARENT_BLOCKS: &[&str] = &[
+    "glass",
+    "glass_pane",
+    "ice",
+    "chest",
+    "ender_chest",
+    "end_rod",
+];
+
+/// Suffixes of blocks (without the namespace) that let the light through.
+const TRANSPARENT_SUFFIXES: &[&str] = &[
+    "_stairs",
+    "_slab",
+    "_fence",
+    "_wall",
+    "_stained_glass",
+    "_head",
+    "_sign",
+    "_banner",
+    "_pressure_plate",
+];
+
+/// Blocks (without the namespace) that reduce the light passing through them
+/// by one level instead of blocking it.
+const DIMMING_BLOCKS: &[&str] = &[
+    "water",
+    "lava",
+    "bubble_column",
+    "seagrass",
+    "tall_seagrass",
+    "kelp",
+    "kelp_plant",
+    "ice",
+    "cobweb",
+];
+
+/// Blocks (without the namespace) that emit the light, with their levels.
+const LIGHT_SOURCES: &[(&str, u8)] = &[
+    ("torch", 14),
+    ("wall_torch", 14),
+    ("soul_torch", 10),
+    ("soul_wall_torch", 10),
+    ("redstone_torch", 7),
+    ("redstone_wall_torch", 7),
+    ("lava", 15),
+    ("fire", 15),
+    ("soul_fire", 10),
+    ("glowstone", 15),
+    ("jack_o_lantern", 15),
+    ("sea_lantern", 15),
+    ("lantern", 15),
+    ("soul_lantern", 10),
+    ("shroomlight", 15),
+    ("end_rod", 14),
+    ("beacon", 15),
+    ("magma_block", 3),
+];
+
+/// Physical properties of a block that the server needs to know about, i.e.
+/// for heightmaps. The vanilla report doesn't include them, so they're derived
+/// from the name of the block.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BlockBehaviour {
+    /// Whether the block is one of the air blocks.
+    pub is_air: bool,
+    /// Whether the block stops entities, which is mostly the case for the
+    /// blocks with a collision.
+    pub blocks_motion: bool,
+    /// Whether the block is always filled with a fluid. Waterloggable blocks
+    /// depend on their `waterlogged` property instead.
+    pub is_fluid: bool,
+    /// Whether the block is one of the leaves.
+    pub is_leaves: bool,
+    /// Level of the light emitted by the block. It doesn't depend on the
+    /// state, so i.e. furnaces never emit the light.
+    pub light_emission: u8,
+    /// By how many levels the light is reduced when passing through the
+    /// block, from 0 for the transparent blocks to 15 for the opaque ones.
+    pub light_opacity: u8,
+}
+
+impl BlockBehaviour {
+    /// Derives the behaviour of the block with the provided namespaced name.
+    fn from_name(name: &str) -> Self {
+        let path = name.split_once(':').map_or(name, |(_, path)| path);
+        let blocks_motion = !NON_SOLID_BLOCKS.contains(&path)
+            && !NON_SOLID_SUFFIXES
+                .iter()
+                .any(|suffix| path.ends_with(suffix));
+        let is_leaves = path.ends_with("_leaves");
+
+        let light_opacity = if DIMMING_BLOCKS.contains(&path) || is_leaves {
+            1
+        } else if !blocks_motion
+            || TRANSPARENT_BLOCKS.contains(&path)
+            || TRANSPARENT_SUFFIXES
+                .iter()
+                .any(|suffix| path.ends_with(suffix))
+        {
+            0
+        } else {
+            15
+        };
+
+        Self {
+            is_air: matches!(path, "air" | "cave_air" | "void_air"),
+            blocks_motion,
+            is_fluid: FLUID_BLOCKS.contains(&path),
+            is_leaves,
+            light_emission: LIGHT_SOURCES
+                .iter()
+                .find(|(source, _)| *source == path)
+                .map_or(0, |(_, level)| *level),
+            light_opacity,
+        }
+    }
+}
+
+/// Represents a single property of a block (i.e. `facing` of stairs) with all
+/// of its possible values in the vanilla order.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlockProperty {
+    /// Name of this property.
+    pub name: String,
+    /// All possible values of this property.
+    pub values: Vec<String>,
+}
+
+/// Represents a single block type with all of its properties. All states of
+/// the block occupy a contiguous range of IDs, where the last property changes
+/// the fastest.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Block {
+    /// Namespaced name of the block, i.e. `minecraft:stone`.
+    pub name: String,
+    /// The ID of the first state of this block.
+    pub min_state_id: u16,
+    /// The ID of the state which is used when no properties are specified.
+    pub default_state_id: u16,
+    /// Properties of the block in the vanilla order.
+    pub properties: Vec<BlockProperty>,
+    /// Physical properties of the block. See `BlockBehaviour`.
+    #[serde(skip)]
+    pub behaviour: BlockBehaviour,
+}
+
+impl Block {
+    /// Returns the amount of states this block has.
+    pub fn state_count(&self) -> usize {
+        self.properties.iter().map(|p| p.values.len()).product()
+    }
+
+    /// Returns the default state of this block.
+    pub fn default_state(&self) -> BlockState {
+        BlockState(self.default_state_id)
+    }
+
+    /// Returns the index of the property with the provided name, if present.
+    fn property_index(&self, name: &str) -> Option<usize> {
+        self.properties.iter().position(|p| p.name == name)
+    }
+
+    /// Returns the amount of states between two subsequent values of the
+    /// property at the provided index.
+    fn property_stride(&self, index: usize) -> usize {
+        self.properties[index + 1..]
+            .iter()
+            .map(|p| p.values.len())
+            .product()
+    }
+}
+
+/// The registry of all known blocks and their states.
+#[derive(Debug)]
+pub struct BlockRegistry {
+    blocks: Vec<Block>,
+    by_name: HashMap<String, usize>,
+    by_state: Vec<Option<u16>>,
+}
+
+impl BlockRegistry {
+    /// Parses the registry from the JSON generated by `generator.py`.
+    pub fn from_json(json: &str) -> Result<Self, BlockStateError> {
+        let mut blocks: Vec<Block> = serde_json::from_str(json)?;
+        for block in &mut blocks {
+            block.behaviour = BlockBehaviour::from_name(&block.name);
+        }
+
+        let state_count = blocks
+            .iter()
+            .map(|b| b.min_state_id as usize + b.state_count())
+            .max()
+            .unwrap_or(0);
+
+        let mut by_name = HashMap::with_capacity(blocks.len());
+        let mut by_state = vec![None; state_count];
+        for (index, block) in blocks.iter().enumerate() {
+            by_name.insert(block.name.clone(), index);
+
+            let min_state_id = block.min_state_id as usize;
+            for state in &mut by_state[min_state_id..min_state_id + block.state_count()] {
+                *state = Some(index as u16);
+            }
+        }
+
+        Ok(Self {
+            blocks,
+            by_name,
+            by_state,
+        })
+    }
+
+    /// Returns the block by its name. The `minecraft` namespace is assumed if
+    /// the name doesn't have one.
+    pub fn block(&self, name: &str) -> Option<&Block> {
+        let index = if name.contains(':') {
+            self.by_name.get(name)
+        } else {
+            self.by_name.get(&format!("{DEFAULT_NAMESPACE}:{name}"))
+        };
+        index.map(|&index| &self.blocks[index])
+    }
+
+    /// Returns the block which the state with the provided ID belongs to.
+    pub fn block_by_state(&self, id: u16) -> Option<&Block> {
+        let index = (*self.by_state.get(id as usize)?)?;
+        Some(&self.blocks[index as usize])
+    }
+
+    /// Returns all blocks of this registry.
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    /// Returns the total amount of block states, used i.e. for calculating
+    /// the amount of bits the direct palette takes.
+    pub fn state_count(&self) -> usize {
+        self.by_state.len()
+    }
+}
+
+/// Representation of a single block state - the block with a specific set of
+/// its properties' values, i.e. `minecraft:oak_stairs[facing=north,...]`.
+/// Internally, it is the protocol ID of the state.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockState(u16);
+
+impl BlockState {
+    /// The `minecraft:air` block state.
+    pub const AIR: BlockState = BlockState(0);
+
+    /// Returns the default state of the block with the provided name.
+    pub fn new(name: &str) -> Result<Self, BlockStateError> {
+        let block = BLOCKS
+            .block(name)
+            .ok_or_else(|| BlockStateError::UnknownBlock(name.to_string()))?;
+        Ok(block.default_state())
+    }
+
+    /// Returns the state of the block with the provided name and the property
+    /// values. Properties that weren't provided take their default values.
+    pub fn with_properties(
+        name: &str,
+        properties: &[(&str, &str)],
+    ) -> Result<Self, BlockStateError> {
+        properties
+            .iter()
+            .try_fold(Self::new(name)?, |state, (property, value)| {
+                state.with(property, value)
+            })
+    }
+
+    /// Returns the state of the block with the provided name and property
+    /// values the same way as `with_properties`, but falls back to the closest
+    /// known state. Blocks that aren't in the registry are replaced with stone
+    /// or air, depending on whether they're solid, and unknown properties are
+    /// ignored. Used for the data from outside, i.e. the world files, where
+    /// the substitute is returned along with the error so it can be reported.
+    pub fn with_properties_lossy(
+        name: &str,
+        properties: &[(&str, &str)],
+    ) -> Result<Self, (Self, BlockStateError)> {
+        let state = match Self::new(name) {
+            Ok(state) => state,
+            Err(e) => {
+                let substitute = if BlockBehaviour::from_name(name).blocks_motion {
+                    Self::new("minecraft:stone").unwrap()
+                } else {
+                    Self::AIR
+                };
+                return Err((substitute, e));
+            }
+        };
+
+        let mut error = None;
+        let state = properties.iter().fold(state, |state, (property, value)| {
+            state.with(property, value).unwrap_or_else(|e| {
+                error.get_or_insert(e);
+                state
+            })
+        });
+        match error {
+            Some(e) => Err((state, e)),
+            None => Ok(state),
+        }
+    }
+
+    /// Returns the block state by its protocol ID.
+    pub fn from_id(id: i32) -> Result<Self, BlockStateError> {
+        u16::try_from(id)
+            .ok()
+            .filter(|&id| BLOCKS.block_by_state(id).is_some())
+            .map(Self)
+            .ok_or(BlockStateError::UnknownStateId(id))
+    }
+
+    /// Parses the block state from its string representation, i.e.
+    /// `minecraft:oak_stairs[facing=north,half=top]`.
+    pub fn parse(value: &str) -> Result<Self, BlockStateError> {
+        let Some((name, properties)) = value.split_once('[') else {
+            return Self::new(value);
+        };
+
+        let properties = properties
+            .strip_suffix(']')
+            .ok_or_else(|| BlockStateError::Malformed(value.to_string()))?;
+
+        let mut state = Self::new(name)?;
+        for property in properties.split(',').filter(|p| !p.is_empty()) {
+            let (property, property_value) = property
+                .split_once('=')
+                .ok_or_else(|| BlockStateError::Malformed(value.to_string()))?;
+            state = state.with(property.trim(), property_value.trim())?;
+        }
+        Ok(state)
+    }
+
+    /// Returns the protocol ID of this block state.
+    pub fn id(&self) -> u16 {
+        self.0
+    }
+
+    /// Returns the block this state belongs to.
+    pub fn block(&self) -> &'static Block {
+        BLOCKS
+            .block_by_state(self.0)
+            .expect("block states are only constructed from the registry")
+    }
+
+    /// Returns the namespaced name of the block this state belongs to.
+    pub fn name(&self) -> &'static str {
+        &self.block().name
+    }
+
+    /// Returns whether this state is one of the air blocks.
+    pub fn is_air(&self) -> bool {
+        self.block().behaviour.is_air
+    }
+
+    /// Returns whether this state stops the movement of entities.
+    pub fn blocks_motion(&self) -> bool {
+        self.block().behaviour.blocks_motion
+    }
+
+    /// Returns whether this state is known to be a full solid cube. Shapes of
+    /// the blocks aren't known, so it's approximated by the opaque solid
+    /// blocks, which leaves out a few transparent full ones (i.e. glass).
+    pub fn is_full_cube(&self) -> bool {
+        self.blocks_motion() && self.light_opacity() == 15
+    }
+
+    /// Returns whether this state contains a fluid, either by being the fluid
+    /// itself or by being waterlogged.
+    pub fn has_fluid(&self) -> bool {
+        self.block().behaviour.is_fluid || self.get("waterlogged") == Some("true")
+    }
+
+    /// Returns whether this state is one of the leaves.
+    pub fn is_leaves(&self) -> bool {
+        self.block().behaviour.is_leaves
+    }
+
+    /// Returns the level of the light emitted by this state.
+    pub fn light_emission(&self) -> u8 {
+        self.block().behaviour.light_emission
+    }
+
+    /// Returns by how many levels this state reduces the passing light.
+    pub fn light_opacity(&self) -> u8 {
+        self.block().behaviour.light_opacity
+    }
+
+    /// Returns whether this state is the default state of its block.
+    pub fn is_default(&self) -> bool {
+        self.block().default_state_id == self.0
+    }
+
+    /// Returns the value of the property, if the block has it.
+    pub fn get(&self, property: &str) -> Option<&'static str> {
+        let block = self.block();
+        let index = block.property_index(property)?;
+        let stride = block.property_stride(index);
+        let values = &block.properties[index].values;
+        let value_index = (self.0 - block.min_state_id) as usize / stride % values.len();
+        Some(&values[value_index])
+    }
+
+    /// Returns the state of the same block with the property set to the
+    /// provided value.
+    pub fn with(&self, property: &str, value: &str) -> Result<Self, BlockStateError> {
+        let block = self.block();
+        let index =
+            block
+                .property_index(property)
+                .ok_or_else(|| BlockStateError::UnknownProperty {
+                    block: block.name.clone(),
+                    property: property.to_string(),
+                })?;
+
+        let values = &block.properties[index].values;
+        let new_value_index = values.iter().position(|v| v == value).ok_or_else(|| {
+            BlockStateError::InvalidPropertyValue {
+                block: block.name.clone(),
+                property: property.to_string(),
+                value: value.to_string(),
+            }
+        })?;
+
+        let stride = block.property_stride(index);
+        let offset = (self.0 - block.min_state_id) as usize;
+        let current_value_index = offset / stride % values.len();
+        let new_offset = offset - current_value_index * stride + new_value_index * stride;
+        Ok(Self(block.min_state_id + new_offset as u16))
+    }
+
+    /// Returns all properties of this state with their values in the vanilla
+    /// order.
+    pub fn properties(&self) -> impl Iterator<Item = (&'static str, &'static str)> {
+        let block = self.block();
+        block
+            .properties
+            .iter()
+            .map(|property| (property.name.as_str(), self.get(&property.name).unwrap()))
+    }
+}
+
+impl Display for BlockState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())?;
+        if self.block().properties.is_empty() {
+            return Ok(());
+        }
+
+        let properties: Vec<String> = self
+            .properties()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect();
+        write!(f, "[{}]", properties.join(","))
+    }
+}
+
+impl Readable for BlockState {
+    fn read(buffer: &[u8]) -> Result<(Self, usize), ReadError> {
+        let (id, read_length) = VarInt::read(buffer)?;
+        let state = Self::from_id(id.0).map_err(|_| ReadError::UnknownBlockState(id.0))?;
+        Ok((state, read_length))
+    }
+}
+
+impl Writeable for BlockState {
+    fn write(&self) -> Result<bytes::Bytes, WriteError> {
+        VarInt(self.0 as i32).write()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_state() {
+        let state = BlockState::new("minecraft:grass_block").unwrap();
+        assert!(state.is_default());
+        assert_eq!(state.get("snowy"), Some("false"));
+        assert_eq!(BlockState::new("stone").unwrap().name(), "minecraft:stone");
+    }
+
+    #[test]
+    fn test_with_property() {
+        let stairs = BlockState::new("minecraft:oak_stairs").unwrap();
+        let east = stairs.with("facing", "east").unwrap();
+        assert_ne!(stairs, east);
+        assert_eq!(east.get("facing"), Some("east"));
+        assert_eq!(east.get("half"), stairs.get("half"));
+        assert_eq!(east.get("shape"), stairs.get("shape"));
+        assert_eq!(east.with("facing", "north").unwrap(), stairs);
+        assert!(stairs.with("facing", "up").is_err());
+        assert!(stairs.with("color", "red").is_err());
+    }
+
+    #[test]
+    fn test_lossy_states() {
+        let stairs = BlockState::with_properties_lossy("oak_stairs", &[("facing", "east")]);
+        assert_eq!(stairs.unwrap().get("facing"), Some("east"));
+
+        let (substitute, error) =
+            BlockState::with_properties_lossy("oak_stairs", &[("facing", "east"), ("age", "1")])
+                .unwrap_err();
+        assert_eq!(substitute.get("facing"), Some("east"));
+        assert!(matches!(error, BlockStateError::UnknownProperty { .. }));
+
+        let (substitute, error) =
+            BlockState::with_properties_lossy("minecraft:unknown_block", &[]).unwrap_err();
+        assert_eq!(substitute, BlockState::new("stone").unwrap());
+        assert!(matches!(error, BlockStateError::UnknownBlock(_)));
+        let (substitute, _) =
+            BlockState::with_properties_lossy("minecraft:unknown_torch", &[]).unwrap_err();
+        assert_eq!(substitute, BlockState::AIR);
+    }
+
+    #[test]
+    fn test_behaviour_of_vanilla_blocks() {
+        // name, blocks motion, light opacity and emission as in vanilla
+        let blocks = [
+            ("air", false, 0, 0),
+            ("stone", true, 15, 0),
+            ("grass_block", true, 15, 0),
+            ("water", false, 1, 0),
+            ("lava", false, 1, 15),
+            ("oak_leaves", true, 1, 0),
+            ("glass", true, 0, 0),
+            ("white_stained_glass", true, 0, 0),
+            ("ice", true, 1, 0),
+            ("cobweb", false, 1, 0),
+            ("short_grass", false, 0, 0),
+            ("snow", false, 0, 0),
+            ("torch", false, 0, 14),
+            ("wall_torch", false, 0, 14),
+            ("redstone_torch", false, 0, 7),
+            ("glowstone", true, 15, 15),
+            ("lantern", false, 0, 15),
+            ("end_rod", true, 0, 14),
+            ("oak_stairs", true, 0, 0),
+            ("stone_slab", true, 0, 0),
+            ("oak_fence", true, 0, 0),
+            ("chest", true, 0, 0),
+            ("oak_sign", true, 0, 0),
+            ("oak_wall_sign", true, 0, 0),
+            ("white_banner", true, 0, 0),
+            ("stone_pressure_plate", true, 0, 0),
+            ("oak_button", false, 0, 0),
+            ("player_head", false, 0, 0),
+            ("skeleton_wall_skull", false, 0, 0),
+            ("piston_head", true, 0, 0),
+            ("rail", false, 0, 0),
+        ];
+        for (name, blocks_motion, light_opacity, light_emission) in blocks {
+            let behaviour = BlockBehaviour::from_name(&format!("minecraft:{name}"));
+            assert_eq!(behaviour.blocks_motion, blocks_motion, "{name}");
+            assert_eq!(behaviour.light_opacity, light_opacity, "{name}");
+            assert_eq!(behaviour.light_emission, light_emission, "{name}");
+        }
+        assert!(BlockBehaviour::from_name("minecraft:cave_air").is_air);
+        assert!(BlockBehaviour::from_name("minecraft:kelp").is_fluid);
+        assert!(BlockBehaviour::from_name("minecraft:oak_leaves").is_leaves);
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        let value = "minecraft:oak_stairs[facing=west,half=top,shape=outer_left,waterlogged=true]";
+        let state = BlockState::parse(value).unwrap();
+        assert_eq!(state.to_string(), value);
+        assert_eq!(BlockState::from_id(state.id() as i32).unwrap(), state);
+        assert_eq!(BlockState::parse("minecraft:air").unwrap(), BlockState::AIR);
+        assert!(BlockState::parse("minecraft:oak_stairs[facing").is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::LazyLock;
+
+use crate::{
+    protocol::nbt::{NbtCompound, NbtTag},
+    varint::VarInt,
+};
+
+/// Types of the block entities from the `minecraft:block_entity_type`
+/// registry in the order of their protocol IDs, generated from the vanilla
+/// `registries.json` report (see `generator.py`).
+static BLOCK_ENTITY_TYPES: LazyLock<Vec<String>> = LazyLock::new(|| {
+    let raw_block_entity_types_json = include_str!("../../block_entity_types.json");
+    serde_json::from_str(raw_block_entity_types_json).unwrap()
+});
+
+/// Keys of the data that only the server needs, which aren't sent to the
+/// clients, i.e. the items of a chest.
+const SERVER_ONLY_KEYS: &[&str] = &["Items", "LootTable", "LootTableSeed", "Lock"];
+
+/// Keys of the block entity compound that describe the block entity itself
+/// rather than its data.
+const HEADER_KEYS: &[&str] = &["id", "x", "y", "z", "keepPacked"];
+
+/// Additional data of a block that doesn't fit into its state, i.e. the items
+/// of a chest or the text of a sign.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockEntity {
+    /// Coordinates of the block within its chunk. Y counts from the bottom of
+    /// the world, the same way as for `Chunk`.
+    pub x: usize,
+    pub y: usize,
+    pub z: usize,
+    /// Namespaced type of the block entity, i.e. `minecraft:chest`.
+    pub id: String,
+    /// Data of the block entity without the type and the coordinates.
+    pub data: NbtCompound,
+}
+
+impl BlockEntity {
+    /// Creates the block entity of the provided type at the coordinates
+    /// within its chunk, where Y counts from the bottom of the world.
+    pub fn new(x: usize, y: usize, z: usize, id: &str, data: NbtCompound) -> Self {
+        Self {
+            x,
+            y,
+            z,
+            id: if id.contains(':') {
+                id.to_string()
+            } else {
+                format!("minecraft:{id}")
+            },
+            data,
+        }
+    }
+
+    /// Returns the protocol ID of the type of the block entity, if it's a
+    /// known one.
+    pub fn type_id(&self) -> Option<VarInt> {
+        let index = BLOCK_ENTITY_TYPES.iter().position(|id| *id == self.id)?;
+        Some(VarInt(index as i32))
+    }
+
+    /// Returns the data the clients need to render the block entity, i.e.
+    /// the text of a sign or the owner of a player head.
+    pub fn client_data(&self) -> NbtCompound {
+        self.data
+            .iter()
+            .filter(|(key, _)| !SERVER_ONLY_KEYS.contains(&key.as_str()))
+            .map(|(key, tag)| (key.clone(), tag.clone()))
+            .collect()
+    }
+
+    /// Creates the block entity from its compound in the world files, where
+    /// the coordinates are absolute. `min_y` is the lowest Y coordinate of the
+    /// world. Returns `None` if the compound misses the type or coordinates,
+    /// or the block is below the world.
+    pub fn from_nbt(compound: &NbtCompound, min_y: i32) -> Option<Self> {
+        let coordinate = |key| compound.get(key).and_then(NbtTag::as_i64);
+        let (x, y, z) = (coordinate("x")?, coordinate("y")?, coordinate("z")?);
+
+        Some(Self {
+            x: (x & 15) as usize,
+            y: usize::try_from(y - min_y as i64).ok()?,
+            z: (z & 15) as usize,
+            id: compound.get("id")?.as_str()?.to_string(),
+            data: compound
+                .iter()
+                .filter(|(key, _)| !HEADER_KEYS.contains(&key.as_str()))
+                .map(|(key, tag)| (key.clone(), tag.clone()))
+                .collect(),
+        })
+    }
+
+    /// Converts the block entity into its compound in the world files, with
+    /// the absolute coordinates. It's the reverse of `from_nbt`.
+    pub fn to_nbt(&self, chunk_x: i32, chunk_z: i32, min_y: i32) -> NbtCompound {
+        let mut compound = self.data.clone();
+        compound.insert(String::from("id"), NbtTag::String(self.id.clone()));
+        compound.insert(String::from("x"), NbtTag::Int(chunk_x * 16 + self.x as i32));
+        compound.insert(String::from("y"), NbtTag::Int(min_y + self.y as i32));
+        compound.insert(String::from("z"), NbtTag::Int(chunk_z * 16 + self.z as i32));
+        compound.insert(String::from("keepPacked"), NbtTag::Byte(0));
+        compound
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_entity_types() {
+        let entity = |id| BlockEntity::new(0, 0, 0, id, NbtCompound::new());
+        assert_eq!(entity("chest").type_id(), Some(VarInt(1)));
+        assert_eq!(entity("minecraft:sign").type_id(), Some(VarInt(7)));
+        assert_eq!(entity("skull").type_id(), Some(VarInt(16)));
+        assert_eq!(entity("banner").type_id(), Some(VarInt(20)));
+        assert_eq!(entity("minecraft:unknown").type_id(), None);
+
+        let mut chest = entity("chest");
+        chest
+            .data
+            .insert(String::from("Items"), NbtTag::List(vec![]));
+        chest.data.insert(
+            String::from("CustomName"),
+            NbtTag::String(String::from("\"Loot\"")),
+        );
+        assert_eq!(chest.client_data().len(), 1);
+    }
+}
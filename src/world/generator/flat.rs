@@ -0,0 +1,146 @@
+use crate::{
+    registry::REGISTRY,
+    varint::VarInt,
+    world::{
+        Chunk, DEFAULT_BIOME,
+        block::BlockState,
+        generator::{ChunkGenerator, GeneratorError, WorldHeight},
+    },
+};
+
+/// Generator of the superflat worlds. Layers are stacked from the bottom of
+/// the world, the same way as vanilla does.
+#[derive(Debug, Clone)]
+pub struct FlatGenerator {
+    /// Block state of each layer, starting from the bottom of the world.
+    layers: Vec<BlockState>,
+    biome: VarInt,
+    height: WorldHeight,
+}
+
+impl FlatGenerator {
+    /// Creates the generator with the provided layers from the bottom to the
+    /// top, each with its thickness.
+    pub fn new(
+        layers: &[(usize, BlockState)],
+        biome: VarInt,
+        height: WorldHeight,
+    ) -> Result<Self, GeneratorError> {
+        let layers: Vec<BlockState> = layers
+            .iter()
+            .flat_map(|&(count, block_state)| std::iter::repeat_n(block_state, count))
+            .collect();
+        if layers.len() > height.height {
+            return Err(GeneratorError::TooManyLayers(layers.len()));
+        }
+
+        Ok(Self {
+            layers,
+            biome,
+            height,
+        })
+    }
+
+    /// Creates the generator from the vanilla preset string, i.e.
+    /// `minecraft:bedrock,2*minecraft:dirt,minecraft:grass_block;minecraft:plains`.
+    /// The biome is optional and defaults to plains.
+    pub fn from_preset(preset: &str, height: WorldHeight) -> Result<Self, GeneratorError> {
+        let (layers, biome) = match preset.split_once(';') {
+            // anything after the biome (i.e. structures) is ignored
+            Some((layers, rest)) => (layers, rest.split(';').next().unwrap().trim()),
+            None => (preset, DEFAULT_BIOME),
+        };
+
+        let mut parsed_layers = vec![];
+        for layer in layers.split(',').map(str::trim).filter(|l| !l.is_empty()) {
+            let (count, block) = match layer.split_once('*') {
+                Some((count, block)) => {
+                    let count = count
+                        .trim()
+                        .parse()
+                        .map_err(|_| GeneratorError::MalformedLayer(layer.to_string()))?;
+                    (count, block.trim())
+                }
+                None => (1, layer),
+            };
+            parsed_layers.push((count, BlockState::parse(block)?));
+        }
+
+        let biome = if biome.contains(':') {
+            biome.to_string()
+        } else {
+            format!("minecraft:{biome}")
+        };
+        let biome_id = REGISTRY
+            .biome_id(&biome)
+            .ok_or(GeneratorError::UnknownBiome(biome))?;
+
+        Self::new(&parsed_layers, biome_id, height)
+    }
+}
+
+impl ChunkGenerator for FlatGenerator {
+    fn generate(&self, chunk_x: i32, chunk_z: i32) -> Chunk {
+        let mut chunk = self.height.empty_chunk(chunk_x, chunk_z);
+        for section in chunk.sections.iter_mut().flatten() {
+            section.biomes.fill(self.biome);
+        }
+
+        for (y, &block_state) in self.layers.iter().enumerate() {
+            if block_state.is_air() {
+                continue;
+            }
+            for x in 0..16 {
+                for z in 0..16 {
+                    chunk.set_block_at(x, y, z, block_state);
+                }
+            }
+        }
+
+        chunk
+    }
+
+    fn spawn_position(&self) -> (i32, i32, i32) {
+        (0, self.height.min_y + self.layers.len() as i32, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_FLAT_PRESET;
+
+    const HEIGHT: WorldHeight = WorldHeight {
+        min_y: -64,
+        height: 384,
+    };
+
+    #[test]
+    fn test_default_preset() {
+        let generator = FlatGenerator::from_preset(DEFAULT_FLAT_PRESET, HEIGHT).unwrap();
+        assert_eq!(generator.spawn_position(), (0, -60, 0));
+
+        let chunk = generator.generate(3, -2);
+        assert_eq!((chunk.x, chunk.z), (3, -2));
+        assert_eq!(chunk.get_block_at(5, 0, 5).name(), "minecraft:bedrock");
+        assert_eq!(chunk.get_block_at(5, 2, 5).name(), "minecraft:dirt");
+        assert_eq!(chunk.get_block_at(5, 3, 5).name(), "minecraft:grass_block");
+        assert!(chunk.get_block_at(5, 4, 5).is_air());
+    }
+
+    #[test]
+    fn test_malformed_presets() {
+        assert!(matches!(
+            FlatGenerator::from_preset("x*minecraft:stone", HEIGHT),
+            Err(GeneratorError::MalformedLayer(_))
+        ));
+        assert!(matches!(
+            FlatGenerator::from_preset("minecraft:stone;minecraft:nowhere", HEIGHT),
+            Err(GeneratorError::UnknownBiome(_))
+        ));
+        assert!(matches!(
+            FlatGenerator::from_preset("400*minecraft:stone", HEIGHT),
+            Err(GeneratorError::TooManyLayers(400))
+        ));
+    }
+}
@@ -0,0 +1,97 @@
+use thiserror::Error;
+
+use crate::{
+    config::{DimensionConfig, GeneratorConfig},
+    registry::RegistryDimensionType,
+    world::{
+        Chunk,
+        block::{BlockState, BlockStateError},
+        generator::{flat::FlatGenerator, noise::NoiseGenerator, void::VoidGenerator},
+    },
+};
+
+pub mod flat;
+pub mod noise;
+pub mod perlin;
+pub mod void;
+
+/// Errors that can occur while creating a chunk generator.
+#[derive(Debug, Error)]
+pub enum GeneratorError {
+    /// Indicates that a layer of the superflat preset is malformed.
+    #[error("malformed superflat layer: {0}")]
+    MalformedLayer(String),
+    /// Indicates that the superflat layers don't fit into the world.
+    #[error("superflat layers are {0} blocks high, which doesn't fit into the world")]
+    TooManyLayers(usize),
+    /// Indicates that there is no biome with the provided name.
+    #[error("unknown biome: {0}")]
+    UnknownBiome(String),
+    /// Indicates that a block of the generator is invalid.
+    #[error(transparent)]
+    BlockStateError(#[from] BlockStateError),
+}
+
+/// Vertical bounds of the dimension the chunks are generated for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorldHeight {
+    /// The lowest Y coordinate of the world.
+    pub min_y: i32,
+    /// Height of the world in blocks, a multiple of 16.
+    pub height: usize,
+}
+
+impl WorldHeight {
+    pub fn from_dimension_type(dimension_type: &RegistryDimensionType) -> Self {
+        Self {
+            min_y: dimension_type.min_y,
+            height: dimension_type.height as usize,
+        }
+    }
+
+    /// Converts the absolute Y coordinate into the one counted from the bottom
+    /// of the world, as `Chunk` expects. Returns `None` if it's out of bounds.
+    pub fn relative_y(&self, y: i32) -> Option<usize> {
+        let relative = usize::try_from(y - self.min_y).ok()?;
+        (relative < self.height).then_some(relative)
+    }
+
+    /// Creates an empty chunk of this height.
+    pub fn empty_chunk(&self, chunk_x: i32, chunk_z: i32) -> Chunk {
+        Chunk::with_height(chunk_x, chunk_z, self.height)
+    }
+}
+
+/// Produces the contents of the chunks of a world. Generators must be
+/// deterministic, as the chunks may be generated again.
+pub trait ChunkGenerator: Send + Sync {
+    /// Generates the chunk at the provided chunk coordinates. Light of the
+    /// chunk is calculated by the caller.
+    fn generate(&self, chunk_x: i32, chunk_z: i32) -> Chunk;
+
+    /// Returns the absolute coordinates of the block the players spawn at.
+    fn spawn_position(&self) -> (i32, i32, i32);
+}
+
+/// Creates the generator of the dimension from its configuration for the world
+/// of the provided height and seed.
+pub fn create_generator(
+    config: &DimensionConfig,
+    height: WorldHeight,
+    seed: i64,
+) -> Result<Box<dyn ChunkGenerator>, GeneratorError> {
+    Ok(match &config.generator {
+        GeneratorConfig::Flat { preset } => Box::new(FlatGenerator::from_preset(preset, height)?),
+        GeneratorConfig::Noise => Box::new(NoiseGenerator::new(seed, config.sea_level, height)?),
+        GeneratorConfig::Void {
+            platform_block,
+            platform_y,
+            platform_radius,
+        } => Box::new(VoidGenerator::new(
+            BlockState::parse(platform_block)?,
+            *platform_y,
+            *platform_radius,
+            height,
+        )),
+    })
+}
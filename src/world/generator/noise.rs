@@ -0,0 +1,367 @@
+use crate::{
+    registry::REGISTRY,
+    varint::VarInt,
+    world::{
+        Chunk,
+        block::{BlockState, BlockStateError},
+        generator::{
+            ChunkGenerator, WorldHeight,
+            perlin::{OctaveNoise, Random, position_hash},
+        },
+        heightmap::HeightmapKind,
+    },
+};
+
+/// How far (in blocks) the terrain can deviate from its base height because of
+/// the 3D noise. Outside of this band the density isn't sampled at all.
+const DETAIL_BAND: f64 = 24.0;
+
+/// How many layers of the filler block (i.e. dirt) are under the top one.
+const FILLER_DEPTH: usize = 3;
+
+/// How many of the lowest layers may have bedrock in them.
+const BEDROCK_LAYERS: usize = 5;
+
+/// Biomes that are assigned by the noise generator, which also define the
+/// surface of the terrain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Biome {
+    Plains,
+    Forest,
+    Desert,
+    SnowyPlains,
+    Taiga,
+    Beach,
+    SnowyBeach,
+    Ocean,
+    ColdOcean,
+    WarmOcean,
+}
+
+impl Biome {
+    fn name(&self) -> &'static str {
+        match self {
+            Biome::Plains => "minecraft:plains",
+            Biome::Forest => "minecraft:forest",
+            Biome::Desert => "minecraft:desert",
+            Biome::SnowyPlains => "minecraft:snowy_plains",
+            Biome::Taiga => "minecraft:taiga",
+            Biome::Beach => "minecraft:beach",
+            Biome::SnowyBeach => "minecraft:snowy_beach",
+            Biome::Ocean => "minecraft:ocean",
+            Biome::ColdOcean => "minecraft:cold_ocean",
+            Biome::WarmOcean => "minecraft:warm_ocean",
+        }
+    }
+
+    fn is_sandy(&self) -> bool {
+        matches!(
+            self,
+            Biome::Desert | Biome::Beach | Biome::SnowyBeach | Biome::WarmOcean
+        )
+    }
+
+    fn is_snowy(&self) -> bool {
+        matches!(self, Biome::SnowyPlains | Biome::SnowyBeach | Biome::Taiga)
+    }
+
+    fn is_ocean(&self) -> bool {
+        matches!(self, Biome::Ocean | Biome::ColdOcean | Biome::WarmOcean)
+    }
+}
+
+/// Block states the terrain is built from.
+#[derive(Debug, Clone, Copy)]
+struct TerrainBlocks {
+    stone: BlockState,
+    dirt: BlockState,
+    grass: BlockState,
+    snowy_grass: BlockState,
+    snow: BlockState,
+    sand: BlockState,
+    sandstone: BlockState,
+    gravel: BlockState,
+    water: BlockState,
+    bedrock: BlockState,
+}
+
+impl TerrainBlocks {
+    fn new() -> Result<Self, BlockStateError> {
+        Ok(Self {
+            stone: BlockState::new("stone")?,
+            dirt: BlockState::new("dirt")?,
+            grass: BlockState::new("grass_block")?,
+            snowy_grass: BlockState::new("grass_block")?.with("snowy", "true")?,
+            snow: BlockState::new("snow")?,
+            sand: BlockState::new("sand")?,
+            sandstone: BlockState::new("sandstone")?,
+            gravel: BlockState::new("gravel")?,
+            water: BlockState::new("water")?,
+            bedrock: BlockState::new("bedrock")?,
+        })
+    }
+}
+
+/// Generator of the natural-looking terrain, which is shaped by the density
+/// made of 2D and 3D Perlin noises. Everything below the sea level is filled
+/// with water, and the surface is covered according to the biome. The output
+/// depends only on the seed and the coordinates.
+#[derive(Debug, Clone)]
+pub struct NoiseGenerator {
+    seed: i64,
+    height: WorldHeight,
+    sea_level: i32,
+    /// Large-scale noise, which decides between the oceans and the land.
+    continentalness: OctaveNoise,
+    /// Smaller-scale noise, which adds hills to the land.
+    hills: OctaveNoise,
+    /// 3D noise, which adds overhangs and cliffs.
+    detail: OctaveNoise,
+    temperature: OctaveNoise,
+    humidity: OctaveNoise,
+    blocks: TerrainBlocks,
+}
+
+impl NoiseGenerator {
+    pub fn new(seed: i64, sea_level: i32, height: WorldHeight) -> Result<Self, BlockStateError> {
+        let mut random = Random::new(seed);
+        Ok(Self {
+            seed,
+            height,
+            sea_level,
+            continentalness: OctaveNoise::new(&mut random, 4, 512.0),
+            hills: OctaveNoise::new(&mut random, 4, 96.0),
+            detail: OctaveNoise::new(&mut random, 3, 40.0),
+            temperature: OctaveNoise::new(&mut random, 2, 768.0),
+            humidity: OctaveNoise::new(&mut random, 2, 768.0),
+            blocks: TerrainBlocks::new()?,
+        })
+    }
+
+    /// Returns the absolute height the terrain of the column is centered
+    /// around, before the 3D noise is applied.
+    fn base_height(&self, x: i32, z: i32) -> f64 {
+        let (x, z) = (x as f64, z as f64);
+        let continentalness = self.continentalness.sample_2d(x, z);
+        let hills = self.hills.sample_2d(x, z).abs();
+
+        let land = (continentalness + 0.1).max(0.0);
+        self.sea_level as f64 + continentalness * 48.0 + hills * 40.0 * land + 2.0
+    }
+
+    /// Returns whether the block at the provided absolute coordinates is a
+    /// part of the terrain.
+    fn is_solid(&self, x: i32, y: i32, z: i32, base_height: f64) -> bool {
+        let offset = base_height - y as f64;
+        if offset > DETAIL_BAND {
+            return true;
+        } else if offset < -DETAIL_BAND {
+            return false;
+        }
+
+        let detail = self.detail.sample(x as f64, y as f64 * 1.5, z as f64);
+        offset / DETAIL_BAND + detail * 0.8 > 0.0
+    }
+
+    /// Returns the biome of the column with the provided terrain height.
+    fn biome_at(&self, x: i32, z: i32, base_height: f64) -> Biome {
+        let temperature = self.temperature.sample_2d(x as f64, z as f64);
+        let humidity = self.humidity.sample_2d(x as f64, z as f64);
+        let sea_level = self.sea_level as f64;
+
+        if base_height < sea_level - 4.0 {
+            match temperature {
+                t if t < -0.25 => Biome::ColdOcean,
+                t if t > 0.25 => Biome::WarmOcean,
+                _ => Biome::Ocean,
+            }
+        } else if base_height < sea_level + 2.0 {
+            if temperature < -0.25 {
+                Biome::SnowyBeach
+            } else {
+                Biome::Beach
+            }
+        } else if temperature > 0.25 && humidity < 0.0 {
+            Biome::Desert
+        } else if temperature < -0.25 {
+            if humidity > 0.0 {
+                Biome::Taiga
+            } else {
+                Biome::SnowyPlains
+            }
+        } else if humidity > 0.15 {
+            Biome::Forest
+        } else {
+            Biome::Plains
+        }
+    }
+
+    /// Returns the block of the surface at the provided depth below the top of
+    /// the terrain, or `None` if it's just stone.
+    fn surface_block(&self, biome: Biome, depth: usize, underwater: bool) -> Option<BlockState> {
+        let blocks = &self.blocks;
+        match depth {
+            0 if biome.is_ocean() && biome != Biome::WarmOcean => Some(blocks.gravel),
+            0 if biome.is_sandy() => Some(blocks.sand),
+            0 if underwater => Some(blocks.dirt),
+            0 if biome.is_snowy() => Some(blocks.snowy_grass),
+            0 => Some(blocks.grass),
+            depth if depth <= FILLER_DEPTH => Some(match biome {
+                biome if biome.is_ocean() && biome != Biome::WarmOcean => blocks.gravel,
+                Biome::Desert if depth == FILLER_DEPTH => blocks.sandstone,
+                biome if biome.is_sandy() => blocks.sand,
+                _ => blocks.dirt,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Builds the column of the chunk at the provided local coordinates.
+    fn generate_column(&self, chunk: &mut Chunk, local_x: usize, local_z: usize, biome: Biome) {
+        let (x, z) = (chunk.x * 16 + local_x as i32, chunk.z * 16 + local_z as i32);
+        let base_height = self.base_height(x, z);
+
+        // the surface is placed from the top down, so the depth of each block
+        // below the nearest air or water above it is known
+        let mut depth = None;
+        for relative_y in (0..self.height.height).rev() {
+            let y = self.height.min_y + relative_y as i32;
+
+            let block_state = if relative_y < BEDROCK_LAYERS
+                && position_hash(self.seed, x, y, z) % BEDROCK_LAYERS as u64 >= relative_y as u64
+            {
+                Some(self.blocks.bedrock)
+            } else if self.is_solid(x, y, z, base_height) {
+                let current_depth = depth.map_or(0, |depth| depth + 1);
+                depth = Some(current_depth);
+                Some(
+                    self.surface_block(biome, current_depth, y < self.sea_level - 1)
+                        .unwrap_or(self.blocks.stone),
+                )
+            } else {
+                depth = None;
+                (y < self.sea_level).then_some(self.blocks.water)
+            };
+
+            if let Some(block_state) = block_state {
+                chunk.set_block_at(local_x, relative_y, local_z, block_state);
+            }
+        }
+
+        // snow covers the snowy biomes on the land
+        let top = chunk
+            .heightmap(HeightmapKind::WorldSurface)
+            .unwrap()
+            .get(local_x, local_z) as usize;
+        if biome.is_snowy()
+            && top < self.height.height
+            && top > 0
+            && chunk.get_block_at(local_x, top - 1, local_z) == self.blocks.snowy_grass
+        {
+            chunk.set_block_at(local_x, top, local_z, self.blocks.snow);
+        }
+    }
+}
+
+impl ChunkGenerator for NoiseGenerator {
+    fn generate(&self, chunk_x: i32, chunk_z: i32) -> Chunk {
+        let mut chunk = self.height.empty_chunk(chunk_x, chunk_z);
+
+        // biomes are stored per 4x4x4 cell, so they're sampled at the centers
+        // of the columns of cells
+        let mut biomes = [Biome::Plains; 16];
+        for cell_x in 0..4 {
+            for cell_z in 0..4 {
+                let (x, z) = (chunk_x * 16 + cell_x * 4 + 2, chunk_z * 16 + cell_z * 4 + 2);
+                let biome = self.biome_at(x, z, self.base_height(x, z));
+                biomes[(cell_z * 4 + cell_x) as usize] = biome;
+            }
+        }
+
+        let biome_ids: Vec<VarInt> = biomes
+            .iter()
+            .map(|biome| REGISTRY.biome_id(biome.name()).unwrap())
+            .collect();
+        for section in chunk.sections.iter_mut().flatten() {
+            for (index, biome) in section.biomes.iter_mut().enumerate() {
+                // biomes of a section are ordered by Y, then Z, then X
+                *biome = biome_ids[index % 16];
+            }
+        }
+
+        for x in 0..16 {
+            for z in 0..16 {
+                let biome = biomes[(z / 4) * 4 + x / 4];
+                self.generate_column(&mut chunk, x, z, biome);
+            }
+        }
+
+        chunk
+    }
+
+    fn spawn_position(&self) -> (i32, i32, i32) {
+        // the highest block of the origin column, but never under the water
+        let base_height = self.base_height(0, 0);
+        let top = (self.height.min_y..self.height.min_y + self.height.height as i32)
+            .rev()
+            .find(|&y| self.is_solid(0, y, 0, base_height))
+            .unwrap_or(self.height.min_y);
+        (0, (top + 1).max(self.sea_level), 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEIGHT: WorldHeight = WorldHeight {
+        min_y: -64,
+        height: 384,
+    };
+
+    #[test]
+    fn test_generation_is_deterministic() {
+        let generator = NoiseGenerator::new(12345, 63, HEIGHT).unwrap();
+        let first = generator.generate(4, -7);
+        let second = NoiseGenerator::new(12345, 63, HEIGHT)
+            .unwrap()
+            .generate(4, -7);
+        let other = NoiseGenerator::new(54321, 63, HEIGHT)
+            .unwrap()
+            .generate(4, -7);
+
+        let blocks = |chunk: &Chunk| -> Vec<BlockState> {
+            chunk
+                .sections
+                .iter()
+                .flatten()
+                .flat_map(|section| section.block_states)
+                .collect()
+        };
+        assert_eq!(blocks(&first), blocks(&second));
+        assert_ne!(blocks(&first), blocks(&other));
+    }
+
+    #[test]
+    fn test_terrain_layers() {
+        let generator = NoiseGenerator::new(7, 63, HEIGHT).unwrap();
+        let chunk = generator.generate(0, 0);
+        let water = BlockState::new("water").unwrap();
+
+        for x in 0..16 {
+            for z in 0..16 {
+                assert_eq!(chunk.get_block_at(x, 0, z).name(), "minecraft:bedrock");
+                assert!(!chunk.get_block_at(x, 10, z).is_air());
+
+                // nothing but water may be between the terrain and the sea level
+                let top = chunk
+                    .heightmap(HeightmapKind::OceanFloor)
+                    .unwrap()
+                    .get(x, z) as usize;
+                for y in top..(63 - HEIGHT.min_y) as usize {
+                    assert_eq!(chunk.get_block_at(x, y, z), water);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,204 @@
+/// Small deterministic random number generator (SplitMix64), used to set up
+/// the noises from the world seed.
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: i64) -> Self {
+        Self { state: seed as u64 }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut value = self.state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        value ^ (value >> 31)
+    }
+
+    /// Returns a random number in the `[0, 1)` range.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a random number in the `[0, bound)` range.
+    pub fn next_bounded(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}
+
+/// Returns the pseudo-random value of the provided block position, which is
+/// stable for the seed. Used for the details that don't need the noise, i.e.
+/// the bedrock pattern.
+pub fn position_hash(seed: i64, x: i32, y: i32, z: i32) -> u64 {
+    let mut random = Random::new(
+        seed ^ (x as i64).wrapping_mul(3_129_871)
+            ^ (z as i64).wrapping_mul(116_129_781)
+            ^ (y as i64).wrapping_mul(42_317_861),
+    );
+    random.next_u64()
+}
+
+/// Improved Perlin noise with a seeded permutation table and origin.
+#[derive(Debug, Clone)]
+pub struct PerlinNoise {
+    permutation: [u8; 512],
+    origin: (f64, f64, f64),
+}
+
+impl PerlinNoise {
+    pub fn new(random: &mut Random) -> Self {
+        let origin = (
+            random.next_f64() * 256.0,
+            random.next_f64() * 256.0,
+            random.next_f64() * 256.0,
+        );
+
+        let mut permutation = [0u8; 512];
+        for (index, value) in permutation.iter_mut().take(256).enumerate() {
+            *value = index as u8;
+        }
+        for index in (1..256).rev() {
+            let other = random.next_bounded(index as u64 + 1) as usize;
+            permutation.swap(index, other);
+        }
+        permutation.copy_within(0..256, 256);
+
+        Self {
+            permutation,
+            origin,
+        }
+    }
+
+    fn hash(&self, index: i32) -> usize {
+        self.permutation[(index & 0xFF) as usize] as usize
+    }
+
+    /// Returns the noise value at the provided point, roughly in the
+    /// `[-1, 1]` range.
+    pub fn sample(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (x, y, z) = (x + self.origin.0, y + self.origin.1, z + self.origin.2);
+        let (floor_x, floor_y, floor_z) = (x.floor(), y.floor(), z.floor());
+        let (cell_x, cell_y, cell_z) = (floor_x as i32, floor_y as i32, floor_z as i32);
+        let (x, y, z) = (x - floor_x, y - floor_y, z - floor_z);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let a = self.hash(cell_x) + (cell_y & 0xFF) as usize;
+        let aa = self.permutation[a] as usize + (cell_z & 0xFF) as usize;
+        let ab = self.permutation[a + 1] as usize + (cell_z & 0xFF) as usize;
+        let b = self.hash(cell_x + 1) + (cell_y & 0xFF) as usize;
+        let ba = self.permutation[b] as usize + (cell_z & 0xFF) as usize;
+        let bb = self.permutation[b + 1] as usize + (cell_z & 0xFF) as usize;
+
+        let p = &self.permutation;
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, grad(p[aa], x, y, z), grad(p[ba], x - 1.0, y, z)),
+                lerp(
+                    u,
+                    grad(p[ab], x, y - 1.0, z),
+                    grad(p[bb], x - 1.0, y - 1.0, z),
+                ),
+            ),
+            lerp(
+                v,
+                lerp(
+                    u,
+                    grad(p[aa + 1], x, y, z - 1.0),
+                    grad(p[ba + 1], x - 1.0, y, z - 1.0),
+                ),
+                lerp(
+                    u,
+                    grad(p[ab + 1], x, y - 1.0, z - 1.0),
+                    grad(p[bb + 1], x - 1.0, y - 1.0, z - 1.0),
+                ),
+            ),
+        )
+    }
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+fn grad(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+    let hash = hash & 15;
+    let u = if hash < 8 { x } else { y };
+    let v = match hash {
+        0..4 => y,
+        12 | 14 => x,
+        _ => z,
+    };
+    (if hash & 1 == 0 { u } else { -u }) + (if hash & 2 == 0 { v } else { -v })
+}
+
+/// Sum of several Perlin noises, where each next octave has the doubled
+/// frequency and the halved amplitude.
+#[derive(Debug, Clone)]
+pub struct OctaveNoise {
+    octaves: Vec<PerlinNoise>,
+    /// Scale of the coordinates of the first octave. The bigger it is, the
+    /// smoother the noise is.
+    scale: f64,
+}
+
+impl OctaveNoise {
+    pub fn new(random: &mut Random, octave_count: usize, scale: f64) -> Self {
+        Self {
+            octaves: (0..octave_count)
+                .map(|_| PerlinNoise::new(random))
+                .collect(),
+            scale,
+        }
+    }
+
+    /// Returns the noise value at the provided point, roughly in the
+    /// `[-1, 1]` range.
+    pub fn sample(&self, x: f64, y: f64, z: f64) -> f64 {
+        let mut value = 0.0;
+        let mut total_amplitude = 0.0;
+        let (mut frequency, mut amplitude) = (1.0 / self.scale, 1.0);
+        for octave in &self.octaves {
+            value += octave.sample(x * frequency, y * frequency, z * frequency) * amplitude;
+            total_amplitude += amplitude;
+            frequency *= 2.0;
+            amplitude /= 2.0;
+        }
+        value / total_amplitude
+    }
+
+    /// Returns the two-dimensional noise value at the provided point.
+    pub fn sample_2d(&self, x: f64, z: f64) -> f64 {
+        self.sample(x, 0.0, z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_noise_is_deterministic_and_bounded() {
+        let first = OctaveNoise::new(&mut Random::new(42), 4, 32.0);
+        let second = OctaveNoise::new(&mut Random::new(42), 4, 32.0);
+        let other = OctaveNoise::new(&mut Random::new(43), 4, 32.0);
+
+        let mut differs = false;
+        for i in 0..1000 {
+            let (x, y, z) = (i as f64 * 1.7, i as f64 * 0.3, i as f64 * -2.9);
+            let value = first.sample(x, y, z);
+            assert_eq!(value, second.sample(x, y, z));
+            assert!((-1.5..=1.5).contains(&value));
+            differs |= value != other.sample(x, y, z);
+        }
+        assert!(differs);
+    }
+}
@@ -0,0 +1,55 @@
+use crate::world::{
+    Chunk,
+    block::BlockState,
+    generator::{ChunkGenerator, WorldHeight},
+};
+
+/// Generator of the empty worlds with a single square platform centered at
+/// the origin, which is where the players spawn.
+#[derive(Debug, Clone)]
+pub struct VoidGenerator {
+    platform_block: BlockState,
+    platform_y: i32,
+    platform_radius: i32,
+    height: WorldHeight,
+}
+
+impl VoidGenerator {
+    pub fn new(
+        platform_block: BlockState,
+        platform_y: i32,
+        platform_radius: i32,
+        height: WorldHeight,
+    ) -> Self {
+        Self {
+            platform_block,
+            platform_y,
+            platform_radius,
+            height,
+        }
+    }
+}
+
+impl ChunkGenerator for VoidGenerator {
+    fn generate(&self, chunk_x: i32, chunk_z: i32) -> Chunk {
+        let mut chunk = self.height.empty_chunk(chunk_x, chunk_z);
+        let Some(y) = self.height.relative_y(self.platform_y) else {
+            return chunk;
+        };
+
+        for x in 0..16 {
+            for z in 0..16 {
+                let (block_x, block_z) = (chunk_x * 16 + x, chunk_z * 16 + z);
+                if block_x.abs() <= self.platform_radius && block_z.abs() <= self.platform_radius {
+                    chunk.set_block_at(x as usize, y, z as usize, self.platform_block);
+                }
+            }
+        }
+
+        chunk
+    }
+
+    fn spawn_position(&self) -> (i32, i32, i32) {
+        (0, self.platform_y + 1, 0)
+    }
+}
@@ -0,0 +1,244 @@
+use bytes::{Bytes, BytesMut};
+
+use crate::{
+    define_varint_enum,
+    network::BufferReader,
+    protocol::{PrefixedArray, ReadError, Readable, WriteError, Writeable},
+    varint::VarInt,
+    world::{
+        block::BlockState,
+        palette::{pack_data_array, unpack_data_array},
+    },
+};
+
+define_varint_enum!(HeightmapKind, {
+    WorldSurfaceWorldgen = 0x00,
+    WorldSurface = 0x01,
+    OceanFloorWorldgen = 0x02,
+    OceanFloor = 0x03,
+    MotionBlocking = 0x04,
+    MotionBlockingNoLeaves = 0x05,
+});
+
+impl HeightmapKind {
+    /// Kinds of the heightmaps that are maintained for the live chunks.
+    pub const LIVE: [HeightmapKind; 4] = [
+        HeightmapKind::WorldSurface,
+        HeightmapKind::OceanFloor,
+        HeightmapKind::MotionBlocking,
+        HeightmapKind::MotionBlockingNoLeaves,
+    ];
+
+    /// Returns whether the heightmap of this kind is needed by the client.
+    /// Others are used only by the server.
+    pub fn is_sent_to_client(&self) -> bool {
+        matches!(
+            self,
+            HeightmapKind::WorldSurface
+                | HeightmapKind::MotionBlocking
+                | HeightmapKind::MotionBlockingNoLeaves
+        )
+    }
+
+    /// Returns the name of the heightmap of this kind in the world files.
+    pub fn nbt_name(&self) -> &'static str {
+        match self {
+            HeightmapKind::WorldSurfaceWorldgen => "WORLD_SURFACE_WG",
+            HeightmapKind::WorldSurface => "WORLD_SURFACE",
+            HeightmapKind::OceanFloorWorldgen => "OCEAN_FLOOR_WG",
+            HeightmapKind::OceanFloor => "OCEAN_FLOOR",
+            HeightmapKind::MotionBlocking => "MOTION_BLOCKING",
+            HeightmapKind::MotionBlockingNoLeaves => "MOTION_BLOCKING_NO_LEAVES",
+        }
+    }
+
+    /// Returns whether the provided block state counts as the surface for
+    /// the heightmap of this kind.
+    pub fn is_opaque(&self, block_state: BlockState) -> bool {
+        match self {
+            HeightmapKind::WorldSurfaceWorldgen | HeightmapKind::WorldSurface => {
+                !block_state.is_air()
+            }
+            HeightmapKind::OceanFloorWorldgen | HeightmapKind::OceanFloor => {
+                block_state.blocks_motion()
+            }
+            HeightmapKind::MotionBlocking => block_state.blocks_motion() || block_state.has_fluid(),
+            HeightmapKind::MotionBlockingNoLeaves => {
+                (block_state.blocks_motion() || block_state.has_fluid()) && !block_state.is_leaves()
+            }
+        }
+    }
+}
+
+/// Representation of a single heightmap of the chunk. Each value is the
+/// position right above the highest block of the column counting from the
+/// bottom of the world, or zero if there are no such blocks.
+#[derive(Debug, Clone)]
+pub struct Heightmap {
+    pub kind: HeightmapKind,
+    pub bits_per_entry: u8,
+    pub heights: [u16; 256],
+}
+
+impl Heightmap {
+    /// Creates an empty heightmap of the provided kind for the world of the
+    /// provided height.
+    pub fn new(kind: HeightmapKind, world_height: usize) -> Self {
+        // values go from 0 to `world_height` inclusively
+        let bits_per_entry = ((usize::BITS - world_height.leading_zeros()) as u8).max(1);
+        Self {
+            kind,
+            bits_per_entry,
+            heights: [0; 256],
+        }
+    }
+
+    /// Returns the height of the column at the provided local coordinates.
+    pub fn get(&self, x: usize, z: usize) -> u16 {
+        self.heights[(z << 4) | x]
+    }
+
+    /// Sets the height of the column at the provided local coordinates.
+    pub fn set(&mut self, x: usize, z: usize, height: u16) {
+        self.heights[(z << 4) | x] = height;
+    }
+
+    /// Recalculates the height of the column at the provided local coordinates
+    /// by scanning it down from the provided height. `get_block` returns the
+    /// block state of the column at the provided height.
+    pub fn recalculate_column(
+        &mut self,
+        x: usize,
+        z: usize,
+        top: usize,
+        get_block: impl Fn(usize) -> BlockState,
+    ) {
+        let height = (0..top)
+            .rev()
+            .find(|&y| self.kind.is_opaque(get_block(y)))
+            .map_or(0, |y| y + 1);
+        self.set(x, z, height as u16);
+    }
+
+    /// Updates the heightmap after the block at the provided local coordinates
+    /// was changed to the provided state, the same way as vanilla does. The
+    /// column is rescanned only if its highest block was removed. Returns
+    /// whether the heightmap was changed.
+    pub fn update(
+        &mut self,
+        x: usize,
+        y: usize,
+        z: usize,
+        block_state: BlockState,
+        get_block: impl Fn(usize) -> BlockState,
+    ) -> bool {
+        let height = self.get(x, z) as usize;
+        if y + 2 <= height {
+            // the block is under the surface, so it can't affect it
+            return false;
+        }
+
+        if self.kind.is_opaque(block_state) {
+            if y >= height {
+                self.set(x, z, (y + 1) as u16);
+                return true;
+            }
+        } else if y + 1 == height {
+            self.recalculate_column(x, z, y, get_block);
+            return true;
+        }
+
+        false
+    }
+
+    /// Packs the heights into the data array sent over the network.
+    pub fn pack(&self) -> Vec<u64> {
+        let heights: Vec<u32> = self.heights.iter().map(|&height| height as u32).collect();
+        pack_data_array(&heights, self.bits_per_entry)
+    }
+}
+
+/// Heightmap as it's sent over the wire. The amount of bits per entry isn't
+/// sent, as it depends on the height of the world, so it's unpacked once the
+/// height is known.
+#[derive(Debug, Clone)]
+pub struct PackedHeightmap {
+    pub kind: HeightmapKind,
+    pub data: PrefixedArray<i64>,
+}
+
+impl PackedHeightmap {
+    /// Unpacks the heights of the heightmap of the world of the provided
+    /// height.
+    pub fn unpack(&self, world_height: usize) -> Heightmap {
+        let mut heightmap = Heightmap::new(self.kind, world_height);
+        let words: Vec<u64> = self.data.0.iter().map(|&word| word as u64).collect();
+        let heights = unpack_data_array(&words, heightmap.bits_per_entry, heightmap.heights.len());
+        for (height, value) in heightmap.heights.iter_mut().zip(heights) {
+            *height = value as u16;
+        }
+        heightmap
+    }
+}
+
+impl Readable for PackedHeightmap {
+    fn read(buffer: &[u8]) -> Result<(Self, usize), ReadError> {
+        let mut reader = BufferReader::new(buffer);
+        let kind = reader.read(HeightmapKind::read)?;
+        let data = reader.read(PrefixedArray::read)?;
+        Ok((Self { kind, data }, reader.consumed()))
+    }
+}
+
+impl Writeable for PackedHeightmap {
+    fn write(&self) -> Result<Bytes, WriteError> {
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&self.kind.write()?);
+        buffer.extend_from_slice(&self.data.write()?);
+        Ok(buffer.freeze())
+    }
+}
+
+impl Writeable for Heightmap {
+    fn write(&self) -> Result<Bytes, WriteError> {
+        let mut buffer = BytesMut::new();
+
+        let packed = self.pack();
+        buffer.extend_from_slice(&self.kind.write()?);
+        buffer.extend_from_slice(&VarInt(packed.len() as i32).write()?);
+
+        for word in packed {
+            buffer.extend_from_slice(&word.to_be_bytes());
+        }
+
+        Ok(buffer.freeze())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packed_heightmap() {
+        let mut heightmap = Heightmap::new(HeightmapKind::MotionBlocking, 384);
+        heightmap.set(3, 7, 384);
+        heightmap.set(15, 15, 70);
+
+        let buffer = heightmap.write().unwrap();
+        let (packed, read_length) = PackedHeightmap::read(&buffer).unwrap();
+        assert_eq!(read_length, buffer.len());
+        let unpacked = packed.unpack(384);
+        assert_eq!(unpacked.bits_per_entry, 9);
+        assert_eq!(unpacked.heights, heightmap.heights);
+
+        // an empty array is all zeros, and still has the bits of the height
+        let empty = PackedHeightmap {
+            kind: HeightmapKind::WorldSurface,
+            data: PrefixedArray(vec![]),
+        };
+        let unpacked = empty.unpack(256);
+        assert_eq!(unpacked.bits_per_entry, 9);
+        assert_eq!(unpacked.pack().len(), 256usize.div_ceil(64 / 9));
+    }
+}
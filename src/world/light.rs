@@ -0,0 +1,613 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{
+    protocol::{BitSet, PrefixedArray, packets::play::LightData},
+    world::{Chunk, block::BlockState, heightmap::HeightmapKind},
+};
+
+/// The highest possible light level.
+pub const MAX_LIGHT_LEVEL: u8 = 15;
+
+/// Offsets to the neighbours of a block: down, up, and then the horizontal
+/// ones.
+const DIRECTIONS: [(i32, i32, i32); 6] = [
+    (0, -1, 0),
+    (0, 1, 0),
+    (-1, 0, 0),
+    (1, 0, 0),
+    (0, 0, -1),
+    (0, 0, 1),
+];
+
+/// Position of a block in the world. X and Z are absolute, while Y counts from
+/// the bottom of the chunk.
+type Position = (i32, i32, i32);
+
+/// Kinds of the light, which are stored and propagated separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightKind {
+    /// The light coming from the sky.
+    Sky,
+    /// The light emitted by the blocks.
+    Block,
+}
+
+impl LightKind {
+    /// Returns the level of the light after it moved from a block with the
+    /// provided level to the provided state in the provided direction. The
+    /// sky light goes straight down without losing any level.
+    fn propagate(&self, level: u8, target: BlockState, direction: (i32, i32, i32)) -> u8 {
+        let opacity = target.light_opacity();
+        if *self == LightKind::Sky && level == MAX_LIGHT_LEVEL && direction.1 < 0 && opacity == 0 {
+            return MAX_LIGHT_LEVEL;
+        }
+        level.saturating_sub(opacity.max(1))
+    }
+}
+
+/// Light levels of a single section, packed as nibbles in the same order as
+/// the block states of the section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LightArray([u8; 2048]);
+
+impl LightArray {
+    /// Creates the array with all levels set to the provided one.
+    pub fn filled(level: u8) -> Self {
+        Self([(level << 4) | level; 2048])
+    }
+
+    /// Creates the array from the raw nibbles, i.e. the ones stored in the
+    /// world files. Returns `None` if there are not exactly 2048 of them.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(Self(bytes.try_into().ok()?))
+    }
+
+    /// Returns the level at the provided local coordinates.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u8 {
+        let index = (y << 8) | (z << 4) | x;
+        (self.0[index >> 1] >> ((index & 1) * 4)) & 0xF
+    }
+
+    /// Sets the level at the provided local coordinates.
+    pub fn set(&mut self, x: usize, y: usize, z: usize, level: u8) {
+        let index = (y << 8) | (z << 4) | x;
+        let shift = (index & 1) * 4;
+        self.0[index >> 1] = (self.0[index >> 1] & !(0xF << shift)) | ((level & 0xF) << shift);
+    }
+
+    /// Returns whether all levels of the array are zero.
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&byte| byte == 0)
+    }
+
+    /// Returns the raw nibbles as they're sent over the network.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Light of a whole chunk. There is an array per section, as well as an extra
+/// one below and above the world, as the client expects.
+#[derive(Debug, Clone)]
+pub struct ChunkLight {
+    /// Whether the dimension has the sky light at all, i.e. it's not the case
+    /// for the nether.
+    pub has_sky_light: bool,
+    pub sky: Vec<LightArray>,
+    pub block: Vec<LightArray>,
+}
+
+impl ChunkLight {
+    /// Creates the unlit light of a chunk with the provided amount of
+    /// sections.
+    pub fn new(section_count: usize, has_sky_light: bool) -> Self {
+        let mut light = Self {
+            has_sky_light,
+            sky: vec![LightArray::filled(0); section_count + 2],
+            block: vec![LightArray::filled(0); section_count + 2],
+        };
+        light.reset();
+        light
+    }
+
+    /// Removes all light, except the sky light above the world.
+    pub fn reset(&mut self) {
+        self.sky.fill(LightArray::filled(0));
+        self.block.fill(LightArray::filled(0));
+        if self.has_sky_light {
+            *self.sky.last_mut().unwrap() = LightArray::filled(MAX_LIGHT_LEVEL);
+        }
+    }
+
+    fn arrays(&self, kind: LightKind) -> &[LightArray] {
+        match kind {
+            LightKind::Sky => &self.sky,
+            LightKind::Block => &self.block,
+        }
+    }
+
+    /// Returns the level of the light at the provided local coordinates, where
+    /// Y counts from the bottom of the chunk.
+    pub fn get(&self, kind: LightKind, x: usize, y: usize, z: usize) -> u8 {
+        self.arrays(kind)[y / 16 + 1].get(x, y % 16, z)
+    }
+
+    /// Sets the level of the light at the provided local coordinates, where
+    /// Y counts from the bottom of the chunk.
+    pub fn set(&mut self, kind: LightKind, x: usize, y: usize, z: usize, level: u8) {
+        let arrays = match kind {
+            LightKind::Sky => &mut self.sky,
+            LightKind::Block => &mut self.block,
+        };
+        arrays[y / 16 + 1].set(x, y % 16, z, level);
+    }
+
+    /// Builds the light data for the chunk packets. Sections without any
+    /// light are sent as empty ones.
+    pub fn light_data(&self) -> LightData {
+        let mut light_data = LightData {
+            sky_light_mask: BitSet::empty(),
+            block_light_mask: BitSet::empty(),
+            empty_sky_light_mask: BitSet::empty(),
+            empty_block_light_mask: BitSet::empty(),
+            sky_lights: PrefixedArray(vec![]),
+            block_lights: PrefixedArray(vec![]),
+        };
+
+        if self.has_sky_light {
+            for (index, array) in self.sky.iter().enumerate() {
+                if array.is_empty() {
+                    light_data.empty_sky_light_mask.set(index);
+                } else {
+                    light_data.sky_light_mask.set(index);
+                    light_data
+                        .sky_lights
+                        .0
+                        .push(PrefixedArray(array.as_bytes().to_vec()));
+                }
+            }
+        }
+
+        for (index, array) in self.block.iter().enumerate() {
+            if array.is_empty() {
+                light_data.empty_block_light_mask.set(index);
+            } else {
+                light_data.block_light_mask.set(index);
+                light_data
+                    .block_lights
+                    .0
+                    .push(PrefixedArray(array.as_bytes().to_vec()));
+            }
+        }
+
+        light_data
+    }
+}
+
+/// Propagates the light over a set of chunks. The light crosses borders
+/// between all of the provided chunks, and stops at the missing ones.
+pub struct LightEngine<'a> {
+    chunks: HashMap<(i32, i32), &'a mut Chunk>,
+    /// Chunks whose light was changed.
+    changed: HashSet<(i32, i32)>,
+}
+
+impl<'a> LightEngine<'a> {
+    pub fn new(chunks: impl IntoIterator<Item = &'a mut Chunk>) -> Self {
+        Self {
+            chunks: chunks
+                .into_iter()
+                .map(|chunk| ((chunk.x, chunk.z), chunk))
+                .collect(),
+            changed: HashSet::new(),
+        }
+    }
+
+    /// Returns the chunks whose light was changed by the engine.
+    pub fn changed_chunks(&self) -> &HashSet<(i32, i32)> {
+        &self.changed
+    }
+
+    fn chunk_at(&self, x: i32, z: i32) -> Option<&Chunk> {
+        self.chunks.get(&(x >> 4, z >> 4)).map(|chunk| &**chunk)
+    }
+
+    fn block_at(&self, (x, y, z): Position) -> Option<BlockState> {
+        let chunk = self.chunk_at(x, z)?;
+        if y < 0 || y as usize >= chunk.height() {
+            return None;
+        }
+        Some(chunk.get_block_at((x & 15) as usize, y as usize, (z & 15) as usize))
+    }
+
+    fn light_at(&self, kind: LightKind, (x, y, z): Position) -> Option<u8> {
+        let chunk = self.chunk_at(x, z)?;
+        if y < 0 || y as usize >= chunk.height() {
+            return None;
+        }
+        Some(
+            chunk
+                .light
+                .get(kind, (x & 15) as usize, y as usize, (z & 15) as usize),
+        )
+    }
+
+    fn set_light(&mut self, kind: LightKind, (x, y, z): Position, level: u8) {
+        let position = (x >> 4, z >> 4);
+        let Some(chunk) = self.chunks.get_mut(&position) else {
+            return;
+        };
+        let (x, y, z) = ((x & 15) as usize, y as usize, (z & 15) as usize);
+        if chunk.light.get(kind, x, y, z) != level {
+            chunk.light.set(kind, x, y, z, level);
+            self.changed.insert(position);
+        }
+    }
+
+    /// Returns the height of the world surface at the provided column, or
+    /// `None` if its chunk isn't present.
+    fn surface_at(&self, x: i32, z: i32) -> Option<usize> {
+        let heightmap = self
+            .chunk_at(x, z)?
+            .heightmap(HeightmapKind::WorldSurface)?;
+        Some(heightmap.get((x & 15) as usize, (z & 15) as usize) as usize)
+    }
+
+    /// Returns the level of the light emitted at the provided position
+    /// regardless of its neighbours. For the sky light, that's only the top
+    /// layer of the world, which is lit from above.
+    fn source_level(&self, kind: LightKind, position: Position, state: BlockState) -> u8 {
+        match kind {
+            LightKind::Block => state.light_emission(),
+            LightKind::Sky => {
+                let Some(chunk) = self.chunk_at(position.0, position.2) else {
+                    return 0;
+                };
+                if chunk.light.has_sky_light && position.1 as usize + 1 == chunk.height() {
+                    LightKind::Sky.propagate(MAX_LIGHT_LEVEL, state, (0, -1, 0))
+                } else {
+                    0
+                }
+            }
+        }
+    }
+
+    /// Spreads the light from the queued positions until it can't raise the
+    /// level of any other block.
+    fn propagate(&mut self, kind: LightKind, queue: &mut VecDeque<Position>) {
+        while let Some(position) = queue.pop_front() {
+            let Some(level) = self.light_at(kind, position) else {
+                continue;
+            };
+            if level <= 1 {
+                continue;
+            }
+
+            for direction in DIRECTIONS {
+                let next = (
+                    position.0 + direction.0,
+                    position.1 + direction.1,
+                    position.2 + direction.2,
+                );
+                let (Some(state), Some(next_level)) =
+                    (self.block_at(next), self.light_at(kind, next))
+                else {
+                    continue;
+                };
+
+                let new_level = kind.propagate(level, state, direction);
+                if new_level > next_level {
+                    self.set_light(kind, next, new_level);
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+
+    /// Lights the provided chunk from scratch. The light of the neighbouring
+    /// chunks flows into it, and its own light flows into them.
+    pub fn light_chunk(&mut self, chunk_x: i32, chunk_z: i32) {
+        let Some(chunk) = self.chunks.get_mut(&(chunk_x, chunk_z)) else {
+            return;
+        };
+        chunk.light.reset();
+
+        let has_sky_light = chunk.light.has_sky_light;
+        let height = chunk.height();
+        let (base_x, base_z) = (chunk_x * 16, chunk_z * 16);
+
+        if has_sky_light {
+            let mut queue = VecDeque::new();
+            for x in base_x..base_x + 16 {
+                for z in base_z..base_z + 16 {
+                    self.seed_sky_column(x, z, height, &mut queue);
+                }
+            }
+            self.queue_borders(LightKind::Sky, chunk_x, chunk_z, &mut queue);
+            self.propagate(LightKind::Sky, &mut queue);
+        }
+
+        let mut queue = VecDeque::new();
+        for y in 0..height as i32 {
+            for x in base_x..base_x + 16 {
+                for z in base_z..base_z + 16 {
+                    let emission = self.block_at((x, y, z)).unwrap().light_emission();
+                    if emission > 0 {
+                        self.set_light(LightKind::Block, (x, y, z), emission);
+                        queue.push_back((x, y, z));
+                    }
+                }
+            }
+        }
+        self.queue_borders(LightKind::Block, chunk_x, chunk_z, &mut queue);
+        self.propagate(LightKind::Block, &mut queue);
+    }
+
+    /// Lights all chunks of the engine from scratch.
+    pub fn light_all(&mut self) {
+        let positions: Vec<(i32, i32)> = self.chunks.keys().copied().collect();
+        for (chunk_x, chunk_z) in positions {
+            self.light_chunk(chunk_x, chunk_z);
+        }
+    }
+
+    /// Fills the column with the full sky light down to the world surface
+    /// from the heightmap, and queues the positions that can spread it
+    /// sideways or further down.
+    fn seed_sky_column(&mut self, x: i32, z: i32, height: usize, queue: &mut VecDeque<Position>) {
+        let surface = self.surface_at(x, z).unwrap();
+        if surface == height {
+            // the column is filled up to the top, so only the top block is lit
+            let top = (x, height as i32 - 1, z);
+            let state = self.block_at(top).unwrap();
+            let level = self.source_level(LightKind::Sky, top, state);
+            if level > 0 {
+                self.set_light(LightKind::Sky, top, level);
+                queue.push_back(top);
+            }
+            return;
+        }
+
+        for y in surface..height {
+            self.set_light(LightKind::Sky, (x, y as i32, z), MAX_LIGHT_LEVEL);
+        }
+
+        // the light goes sideways only where the neighbouring columns are
+        // higher, as the others are fully lit anyway
+        let highest_neighbour = DIRECTIONS[2..]
+            .iter()
+            .filter_map(|direction| self.surface_at(x + direction.0, z + direction.2))
+            .max()
+            .unwrap_or(0);
+        for y in surface..highest_neighbour.max(surface + 1).min(height) {
+            queue.push_back((x, y as i32, z));
+        }
+    }
+
+    /// Queues the lit blocks of the neighbouring chunks that touch the
+    /// provided chunk.
+    fn queue_borders(
+        &self,
+        kind: LightKind,
+        chunk_x: i32,
+        chunk_z: i32,
+        queue: &mut VecDeque<Position>,
+    ) {
+        let (base_x, base_z) = (chunk_x * 16, chunk_z * 16);
+        let borders = (0..16).flat_map(|i| {
+            [
+                (base_x - 1, base_z + i),
+                (base_x + 16, base_z + i),
+                (base_x + i, base_z - 1),
+                (base_x + i, base_z + 16),
+            ]
+        });
+
+        for (x, z) in borders {
+            let Some(chunk) = self.chunk_at(x, z) else {
+                continue;
+            };
+            for y in 0..chunk.height() as i32 {
+                if self.light_at(kind, (x, y, z)).unwrap() > 1 {
+                    queue.push_back((x, y, z));
+                }
+            }
+        }
+    }
+
+    /// Updates the light after the block at the provided position was
+    /// changed. The light that came through the block is removed first, and
+    /// then the remaining light is spread back into the darkened area.
+    pub fn update_block(&mut self, x: i32, y: usize, z: i32) {
+        let position = (x, y as i32, z);
+        let Some(state) = self.block_at(position) else {
+            return;
+        };
+        let has_sky_light = self.chunk_at(x, z).unwrap().light.has_sky_light;
+
+        for kind in [LightKind::Sky, LightKind::Block] {
+            if kind == LightKind::Sky && !has_sky_light {
+                continue;
+            }
+
+            let mut queue = VecDeque::new();
+            let mut removal = VecDeque::from([(position, self.light_at(kind, position).unwrap())]);
+            self.set_light(kind, position, 0);
+
+            while let Some((position, level)) = removal.pop_front() {
+                for direction in DIRECTIONS {
+                    let next = (
+                        position.0 + direction.0,
+                        position.1 + direction.1,
+                        position.2 + direction.2,
+                    );
+                    let (Some(next_state), Some(next_level)) =
+                        (self.block_at(next), self.light_at(kind, next))
+                    else {
+                        continue;
+                    };
+                    if next_level == 0 {
+                        continue;
+                    }
+
+                    let is_sky_column = kind == LightKind::Sky
+                        && direction.1 < 0
+                        && level == MAX_LIGHT_LEVEL
+                        && next_level == MAX_LIGHT_LEVEL;
+                    if next_level < level || is_sky_column {
+                        // the light could've come from the removed one
+                        self.set_light(kind, next, 0);
+                        removal.push_back((next, next_level));
+
+                        let source = self.source_level(kind, next, next_state);
+                        if source > 0 {
+                            self.set_light(kind, next, source);
+                            queue.push_back(next);
+                        }
+                    } else {
+                        // the light comes from elsewhere, so it can fill the
+                        // darkened area back
+                        queue.push_back(next);
+                    }
+                }
+            }
+
+            let source = self.source_level(kind, position, state);
+            if source > self.light_at(kind, position).unwrap() {
+                self.set_light(kind, position, source);
+                queue.push_back(position);
+            }
+            self.propagate(kind, &mut queue);
+        }
+    }
+}
+
+/// Returns how far horizontally the light can change after the block at the
+/// provided coordinates within the chunk was changed, which is as far as the
+/// brightest light around the block can spread. Blocks on the borders of the
+/// chunk are lit by the neighbours too, so the light around them is assumed
+/// to be the brightest one.
+pub fn update_reach(chunk: &Chunk, x: usize, y: usize, z: usize) -> u8 {
+    if x == 0 || x == 15 || z == 0 || z == 15 {
+        return MAX_LIGHT_LEVEL;
+    }
+
+    let mut reach = chunk.get_block_at(x, y, z).light_emission();
+    for (dx, dy, dz) in [(0, 0, 0)].into_iter().chain(DIRECTIONS) {
+        let Some(y) = y
+            .checked_add_signed(dy as isize)
+            .filter(|&y| y < chunk.height())
+        else {
+            continue;
+        };
+        let (x, z) = ((x as i32 + dx) as usize, (z as i32 + dz) as usize);
+        for kind in [LightKind::Sky, LightKind::Block] {
+            reach = reach.max(chunk.light.get(kind, x, y, z));
+        }
+    }
+    reach
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_light_array_nibbles() {
+        let mut array = LightArray::filled(0);
+        array.set(1, 0, 0, 7);
+        array.set(2, 0, 0, 15);
+        assert_eq!(array.as_bytes()[0], 0x70);
+        assert_eq!(array.as_bytes()[1], 0x0F);
+        assert_eq!(array.get(1, 0, 0), 7);
+        assert_eq!(array.get(3, 0, 0), 0);
+    }
+
+    #[test]
+    fn test_sky_and_block_light() {
+        let stone = BlockState::new("stone").unwrap();
+        let mut chunk = Chunk::new(0, 0);
+        for x in 0..16 {
+            for z in 0..16 {
+                chunk.set_block_at(x, 100, z, stone);
+            }
+        }
+        chunk.set_block_at(8, 50, 8, BlockState::new("torch").unwrap());
+        LightEngine::new([&mut chunk]).light_chunk(0, 0);
+
+        assert_eq!(chunk.light.get(LightKind::Sky, 3, 383, 3), 15);
+        assert_eq!(chunk.light.get(LightKind::Sky, 3, 101, 3), 15);
+        assert_eq!(chunk.light.get(LightKind::Sky, 3, 100, 3), 0);
+        assert_eq!(chunk.light.get(LightKind::Sky, 3, 99, 3), 0);
+        assert_eq!(chunk.light.get(LightKind::Block, 8, 50, 8), 14);
+        assert_eq!(chunk.light.get(LightKind::Block, 8, 52, 9), 11);
+
+        let light_data = chunk.light.light_data();
+        assert!(light_data.sky_light_mask.get(25));
+        assert!(light_data.empty_sky_light_mask.get(0));
+        assert!(light_data.block_light_mask.get(50 / 16 + 1));
+        assert_eq!(light_data.block_lights.0.len(), 2);
+        assert_eq!(light_data.sky_lights.0[0].0.len(), 2048);
+    }
+
+    #[test]
+    fn test_light_crosses_chunk_borders() {
+        let mut first = Chunk::new(0, 0);
+        let mut second = Chunk::new(1, 0);
+        first.set_block_at(15, 10, 0, BlockState::new("glowstone").unwrap());
+        let mut engine = LightEngine::new([&mut first, &mut second]);
+        engine.light_all();
+
+        assert_eq!(second.light.get(LightKind::Block, 0, 10, 0), 14);
+        assert_eq!(second.light.get(LightKind::Block, 4, 10, 0), 10);
+    }
+
+    #[test]
+    fn test_update_reach() {
+        let stone = BlockState::new("stone").unwrap();
+        let mut chunk = Chunk::new(0, 0);
+        for x in 0..16 {
+            for z in 0..16 {
+                chunk.set_block_at(x, 100, z, stone);
+            }
+        }
+        chunk.set_block_at(8, 50, 8, BlockState::new("torch").unwrap());
+        LightEngine::new([&mut chunk]).light_chunk(0, 0);
+
+        assert_eq!(update_reach(&chunk, 8, 10, 8), 0);
+        assert_eq!(update_reach(&chunk, 8, 51, 8), 14);
+        assert_eq!(update_reach(&chunk, 8, 101, 8), 15);
+        assert_eq!(update_reach(&chunk, 0, 10, 8), 15);
+    }
+
+    #[test]
+    fn test_incremental_update_matches_full_relight() {
+        let stone = BlockState::new("stone").unwrap();
+        let torch = BlockState::new("torch").unwrap();
+        let mut chunk = Chunk::new(0, 0);
+        for x in 0..16 {
+            for z in 0..16 {
+                chunk.set_block_at(x, 60, z, stone);
+            }
+        }
+        LightEngine::new([&mut chunk]).light_chunk(0, 0);
+
+        let changes = [
+            (4, 70, 4, stone),
+            (4, 61, 5, torch),
+            (5, 60, 5, BlockState::AIR),
+            (4, 61, 5, BlockState::AIR),
+            (4, 70, 4, BlockState::AIR),
+            (7, 59, 7, torch),
+        ];
+        for (x, y, z, state) in changes {
+            chunk.set_block_at(x, y, z, state);
+            LightEngine::new([&mut chunk]).update_block(x as i32, y, z as i32);
+
+            let mut relit = chunk.clone();
+            LightEngine::new([&mut relit]).light_chunk(0, 0);
+            assert_eq!(chunk.light.sky, relit.light.sky);
+            assert_eq!(chunk.light.block, relit.light.block);
+        }
+    }
+}
@@ -0,0 +1,1184 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, mem,
+    path::Path,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicI64, Ordering},
+    },
+    thread,
+};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::{
+    config::{Config, DimensionConfig, GeneratorConfig, SchematicConfig},
+    network::{BufferReader, PacketWriter},
+    protocol::{
+        PrefixedArray, ReadError, Readable, Writeable,
+        nbt::{NbtCompound, NbtTag, NetworkNbt},
+        packets::{
+            Packet,
+            play::{
+                ClientboundBlockEntityDataPacket, ClientboundBlockUpdatePacket,
+                ClientboundUpdateLightPacket, ClientboundUpdateSectionBlocksPacket,
+                ClientboundUpdateTimePacket,
+            },
+        },
+    },
+    registry::REGISTRY,
+    tick::TICKS_PER_SECOND,
+    varint::{VarInt, VarLong},
+    world::{
+        anvil::{AnvilError, RegionStorage, StoredChunk},
+        block::BlockState,
+        block_entity::BlockEntity,
+        generator::{ChunkGenerator, GeneratorError, WorldHeight, create_generator},
+        heightmap::{Heightmap, HeightmapKind},
+        light::ChunkLight,
+        palette::{PaletteKind, PalettedContainer},
+        position::{BlockPos, ChunkPos, SectionPos},
+        schematic::{Schematic, SchematicError},
+        store::{ChunkSource, ChunkStore},
+    },
+};
+
+pub mod anvil;
+pub mod block;
+pub mod block_entity;
+pub mod generator;
+pub mod heightmap;
+pub mod light;
+pub mod palette;
+pub mod position;
+pub mod schematic;
+pub mod store;
+pub mod tracker;
+
+/// Height of the overworld, which is used for the chunks created by `Chunk::new`.
+const DEFAULT_WORLD_HEIGHT: usize = 384;
+
+/// The biome that is used for the newly created sections.
+const DEFAULT_BIOME: &str = "minecraft:plains";
+
+/// File in the world directory that lists the schematics that were pasted
+/// into the world, so they aren't pasted again.
+const PASTED_SCHEMATICS_FILE: &str = "pasted_schematics.json";
+
+/// Errors that can occur while creating a world.
+#[derive(Debug, Error)]
+pub enum WorldError {
+    /// Indicates that there is no dimension type with the provided name.
+    #[error("unknown dimension type: {0}")]
+    UnknownDimensionType(String),
+    /// Indicates that the generator of the world couldn't be created.
+    #[error(transparent)]
+    GeneratorError(#[from] GeneratorError),
+    /// Indicates that a configured schematic couldn't be loaded.
+    #[error("failed to load schematic {0}: {1}")]
+    SchematicError(String, SchematicError),
+    /// Indicates that the world couldn't be saved after the schematics were
+    /// pasted into it.
+    #[error("failed to save the pasted schematics: {0}")]
+    PastedSchematicsError(#[from] AnvilError),
+}
+
+/// A single dimension hosted by the server. Its chunks are loaded from the
+/// world directory, if it's configured, or produced by the generator.
+pub struct World {
+    /// Name of the dimension, i.e. `minecraft:overworld`.
+    pub name: String,
+    /// Name of the dimension type from the registry.
+    pub dimension_type: String,
+    /// Protocol ID of the dimension type.
+    pub dimension_type_id: VarInt,
+    pub height: WorldHeight,
+    pub has_sky_light: bool,
+    pub sea_level: i32,
+    pub seed: i64,
+    /// Whether the world is a superflat one, which changes the horizon on the
+    /// client.
+    pub is_flat: bool,
+    /// Chunks that are loaded by the clients.
+    pub chunks: ChunkStore,
+    source: Arc<DimensionSource>,
+    /// The amount of the ticks the world has existed for.
+    game_time: AtomicI64,
+    /// Time of the day in ticks, where 24000 ticks are a full day.
+    day_time: AtomicI64,
+    /// Changes of the blocks that weren't sent to the clients yet.
+    block_changes: Mutex<BlockChanges>,
+}
+
+/// Changes of the blocks collected during a tick, which are sent together.
+#[derive(Default)]
+struct BlockChanges {
+    /// Changed blocks by their sections, and by their local coordinates in
+    /// the section (X << 8 | Z << 4 | Y).
+    sections: BTreeMap<SectionPos, BTreeMap<u16, BlockState>>,
+    /// Chunks whose light may have changed.
+    lit_chunks: BTreeSet<(i32, i32)>,
+    /// Positions of the changed block entities.
+    block_entities: BTreeSet<BlockPos>,
+}
+
+impl World {
+    /// Creates the world with the provided name and seed from its
+    /// configuration.
+    pub fn from_config(
+        name: &str,
+        config: &DimensionConfig,
+        seed: i64,
+    ) -> Result<Self, WorldError> {
+        let dimension_type = REGISTRY
+            .dimension_type
+            .get(&config.dimension_type)
+            .ok_or_else(|| WorldError::UnknownDimensionType(config.dimension_type.clone()))?;
+        let height = WorldHeight::from_dimension_type(dimension_type);
+        let storage = config.world_directory.as_ref().map(|directory| {
+            Mutex::new(RegionStorage::new(
+                directory,
+                height,
+                dimension_type.has_skylight,
+            ))
+        });
+
+        let source = Arc::new(DimensionSource {
+            name: name.to_string(),
+            height,
+            has_sky_light: dimension_type.has_skylight,
+            generator: create_generator(config, height, seed)?,
+            storage,
+        });
+        let worker_count = thread::available_parallelism().map_or(2, |count| count.get());
+
+        let world = Self {
+            name: name.to_string(),
+            dimension_type: config.dimension_type.clone(),
+            dimension_type_id: REGISTRY.dimension_type_id(&config.dimension_type).unwrap(),
+            height,
+            has_sky_light: dimension_type.has_skylight,
+            sea_level: config.sea_level,
+            seed,
+            is_flat: matches!(config.generator, GeneratorConfig::Flat { .. }),
+            chunks: ChunkStore::new(source.clone(), worker_count),
+            source,
+            game_time: AtomicI64::new(0),
+            day_time: AtomicI64::new(0),
+            block_changes: Mutex::new(BlockChanges::default()),
+        };
+
+        world.paste_schematics(config)?;
+        Ok(world)
+    }
+
+    /// Pastes the configured schematics into the world. Worlds with a
+    /// directory keep the schematics, so they're saved right away and each
+    /// one is pasted only once.
+    fn paste_schematics(&self, config: &DimensionConfig) -> Result<(), WorldError> {
+        let pasted_path = config
+            .world_directory
+            .as_ref()
+            .map(|directory| Path::new(directory).join(PASTED_SCHEMATICS_FILE));
+        let mut pasted: Vec<SchematicConfig> = pasted_path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        let pasted_count = pasted.len();
+
+        for schematic_config in &config.schematics {
+            if pasted.contains(schematic_config) {
+                continue;
+            }
+            let schematic = Schematic::load(&schematic_config.path)
+                .map_err(|e| WorldError::SchematicError(schematic_config.path.clone(), e))?;
+            let [x, y, z] = schematic_config.position;
+            let changed = schematic.paste(
+                self,
+                BlockPos::new(x, y, z),
+                schematic_config.rotation,
+                schematic_config.mirror,
+            );
+            println!(
+                "Pasted {changed} blocks of {} into {}",
+                schematic_config.path, self.name
+            );
+            pasted.push(schematic_config.clone());
+        }
+
+        if let Some(path) = pasted_path
+            && pasted.len() > pasted_count
+        {
+            self.save()?;
+            let json = serde_json::to_string_pretty(&pasted).unwrap();
+            fs::create_dir_all(path.parent().unwrap())
+                .and_then(|()| fs::write(path, json))
+                .map_err(AnvilError::IoError)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the hashed seed that is sent to the client, which uses it for
+    /// the biome blending. It's the first 8 bytes of the SHA-256 of the seed,
+    /// both in the little-endian order, as vanilla does.
+    pub fn hashed_seed(&self) -> i64 {
+        let hash = Sha256::digest(self.seed.to_le_bytes());
+        i64::from_le_bytes(hash[..8].try_into().unwrap())
+    }
+
+    /// Returns the absolute coordinates of the block the players spawn at.
+    pub fn spawn_position(&self) -> (i32, i32, i32) {
+        self.source.generator.spawn_position()
+    }
+
+    /// Returns the block at the provided position, if it's within the world
+    /// height and its chunk is loaded.
+    pub fn get_block(&self, position: BlockPos) -> Option<BlockState> {
+        let relative_y = self.height.relative_y(position.y)?;
+        let (x, _, z) = position.local();
+        let ChunkPos {
+            x: chunk_x,
+            z: chunk_z,
+        } = position.chunk();
+        self.chunks.with_chunk(chunk_x, chunk_z, |chunk| {
+            chunk.get_block_at(x, relative_y, z)
+        })
+    }
+
+    /// Returns whether the box of an entity with the provided size, standing
+    /// at the coordinates, intersects any full solid block. Blocks of the
+    /// chunks that aren't loaded are considered empty.
+    pub fn collides(&self, x: f64, y: f64, z: f64, width: f64, height: f64) -> bool {
+        // boxes that only touch the blocks don't collide with them
+        const EPSILON: f64 = 1e-5;
+        let half_width = width / 2.0 - EPSILON;
+        let min = BlockPos::from_coordinates(x - half_width, y + EPSILON, z - half_width);
+        let max = BlockPos::from_coordinates(x + half_width, y + height - EPSILON, z + half_width);
+
+        (min.y..=max.y).any(|block_y| {
+            (min.z..=max.z).any(|block_z| {
+                (min.x..=max.x).any(|block_x| {
+                    self.get_block(BlockPos::new(block_x, block_y, block_z))
+                        .is_some_and(|block| block.is_full_cube())
+                })
+            })
+        })
+    }
+
+    /// Changes the block at the provided position. The chunk is updated right
+    /// away, while the clients viewing it receive the change with the next
+    /// `flush_block_changes`. Returns `false` if the block is outside of the
+    /// world height.
+    pub fn set_block(&self, position: BlockPos, block_state: BlockState) -> bool {
+        self.set_blocks([(position, block_state)]) == 1
+    }
+
+    /// Changes multiple blocks the same way as `set_block`, but updates each
+    /// chunk only once. Returns the amount of the blocks within the world
+    /// height, which were changed.
+    pub fn set_blocks(&self, blocks: impl IntoIterator<Item = (BlockPos, BlockState)>) -> usize {
+        let mut chunks: BTreeMap<ChunkPos, Vec<(BlockPos, usize, BlockState)>> = BTreeMap::new();
+        for (position, block_state) in blocks {
+            if let Some(relative_y) = self.height.relative_y(position.y) {
+                chunks.entry(position.chunk()).or_default().push((
+                    position,
+                    relative_y,
+                    block_state,
+                ));
+            }
+        }
+
+        let mut changed = 0;
+        for (chunk, blocks) in chunks {
+            self.keep_changes(chunk);
+            let local_blocks: Vec<(usize, usize, usize, BlockState)> = blocks
+                .iter()
+                .map(|&(position, relative_y, block_state)| {
+                    let (x, _, z) = position.local();
+                    (x, relative_y, z, block_state)
+                })
+                .collect();
+            let lit_chunks = self.chunks.set_blocks(chunk.x, chunk.z, &local_blocks);
+
+            let mut changes = self.block_changes.lock().unwrap();
+            for (position, _, block_state) in blocks {
+                let (x, y, z) = position.local();
+                changes
+                    .sections
+                    .entry(position.section())
+                    .or_default()
+                    .insert((x << 8 | z << 4 | y) as u16, block_state);
+                changed += 1;
+            }
+            changes.lit_chunks.extend(lit_chunks);
+        }
+        changed
+    }
+
+    /// Pins the chunk that is about to be changed if the world has no
+    /// storage, as the changes would be lost once the chunk is dropped.
+    fn keep_changes(&self, chunk: ChunkPos) {
+        if self.source.storage.is_none() {
+            self.chunks.pin(&[chunk.into()]);
+        }
+    }
+
+    /// Returns the block entity at the provided position, if its chunk is
+    /// loaded.
+    pub fn get_block_entity(&self, position: BlockPos) -> Option<BlockEntity> {
+        let relative_y = self.height.relative_y(position.y)?;
+        let (x, _, z) = position.local();
+        let chunk = position.chunk();
+        self.chunks.with_chunk(chunk.x, chunk.z, |chunk| {
+            chunk.block_entity(x, relative_y, z).cloned()
+        })?
+    }
+
+    /// Places the block entity of the provided type and data at the position,
+    /// replacing the existing one. It's sent to the clients viewing the chunk
+    /// with the next `flush_block_changes`, so it should be placed after its
+    /// block. Returns `false` if the block is outside of the world height.
+    pub fn set_block_entity(&self, position: BlockPos, id: &str, data: NbtCompound) -> bool {
+        let Some(relative_y) = self.height.relative_y(position.y) else {
+            return false;
+        };
+
+        let (x, _, z) = position.local();
+        let block_entity = BlockEntity::new(x, relative_y, z, id, data);
+        let chunk = position.chunk();
+        self.keep_changes(chunk);
+        self.chunks.update_chunk(chunk.x, chunk.z, |chunk| {
+            chunk.set_block_entity(block_entity)
+        });
+
+        let mut changes = self.block_changes.lock().unwrap();
+        changes.block_entities.insert(position);
+        true
+    }
+
+    /// Advances the time of the world by a tick. The time is sent to the
+    /// clients once per second, which keep it running in between.
+    pub fn tick(&self) {
+        let game_time = self.game_time.fetch_add(1, Ordering::Relaxed) + 1;
+        self.day_time.fetch_add(1, Ordering::Relaxed);
+
+        if game_time % TICKS_PER_SECOND as i64 == 0 {
+            let viewers = self.chunks.all_viewers();
+            broadcast(&viewers, &self.time_packet());
+        }
+    }
+
+    /// Returns the packet with the current time of the world.
+    pub fn time_packet(&self) -> ClientboundUpdateTimePacket {
+        ClientboundUpdateTimePacket {
+            world_age: self.game_time.load(Ordering::Relaxed),
+            time_of_day: self.day_time.load(Ordering::Relaxed),
+            time_of_day_increasing: true,
+        }
+    }
+
+    /// Sends the changes of the blocks since the previous call to the
+    /// clients viewing the changed chunks. A single change in a section is
+    /// sent as the block update, and multiple ones as the section update.
+    /// The changed light is sent afterwards, including the light spread into
+    /// the chunks by the ones loaded next to them. It's called once per tick.
+    pub fn flush_block_changes(&self) {
+        let mut changes = mem::take(&mut *self.block_changes.lock().unwrap());
+        changes.lit_chunks.extend(self.chunks.take_lit());
+
+        for (section, blocks) in changes.sections {
+            let viewers = self.chunks.viewers(section.x, section.z);
+            if viewers.is_empty() {
+                continue;
+            }
+
+            let packet: Box<dyn Packet> = if blocks.len() == 1 {
+                let (&local, block_state) = blocks.iter().next().unwrap();
+                let (x, y, z) = (local >> 8, local & 15, local >> 4 & 15);
+                Box::new(ClientboundBlockUpdatePacket {
+                    position: section.block(x as usize, y as usize, z as usize),
+                    block_id: VarInt(block_state.id() as i32),
+                })
+            } else {
+                Box::new(ClientboundUpdateSectionBlocksPacket {
+                    section_position: section,
+                    blocks: PrefixedArray(
+                        blocks
+                            .iter()
+                            .map(|(&local, state)| {
+                                VarLong((state.id() as i64) << 12 | local as i64)
+                            })
+                            .collect(),
+                    ),
+                })
+            };
+            broadcast(&viewers, packet.as_ref());
+        }
+
+        for position in changes.block_entities {
+            let viewers = self.chunks.viewers(position.chunk().x, position.chunk().z);
+            if viewers.is_empty() {
+                continue;
+            }
+            let Some(block_entity) = self.get_block_entity(position) else {
+                continue;
+            };
+            let Some(type_id) = block_entity.type_id() else {
+                continue;
+            };
+
+            let packet = ClientboundBlockEntityDataPacket {
+                position,
+                type_id,
+                data: NetworkNbt(NbtTag::Compound(block_entity.client_data()).to_nameless_bytes()),
+            };
+            broadcast(&viewers, &packet);
+        }
+
+        for (chunk_x, chunk_z) in changes.lit_chunks {
+            let viewers = self.chunks.viewers(chunk_x, chunk_z);
+            if viewers.is_empty() {
+                continue;
+            }
+            let Some(light_data) = self
+                .chunks
+                .with_chunk(chunk_x, chunk_z, |chunk| chunk.light.light_data())
+            else {
+                continue;
+            };
+
+            let packet = ClientboundUpdateLightPacket {
+                chunk_x: VarInt(chunk_x),
+                chunk_z: VarInt(chunk_z),
+                light_data,
+            };
+            broadcast(&viewers, &packet);
+        }
+    }
+
+    /// Saves the chunks that were changed since they were loaded or saved
+    /// into the world directory, if it's configured. Returns the amount of
+    /// saved chunks.
+    pub fn save(&self) -> Result<usize, AnvilError> {
+        if self.source.storage.is_none() {
+            return Ok(0);
+        }
+        self.chunks.save_all()
+    }
+
+    /// Copies the chunks that were changed since they were loaded or saved,
+    /// if the world directory is configured. The copies are saved with
+    /// `save_chunks`, which can be done on another thread.
+    pub fn take_dirty_chunks(&self) -> Vec<Chunk> {
+        if self.source.storage.is_none() {
+            return vec![];
+        }
+        self.chunks.take_dirty()
+    }
+
+    /// Copies the changed chunks that were dropped from the store since they
+    /// were loaded or saved, the same way as `take_dirty_chunks`, so they're
+    /// saved soon after no one views them.
+    pub fn take_evicted_chunks(&self) -> Vec<Chunk> {
+        if self.source.storage.is_none() {
+            return vec![];
+        }
+        self.chunks.take_evicted()
+    }
+
+    /// Saves the chunks copied by `take_dirty_chunks` or `take_evicted_chunks`
+    /// into the world directory. Returns the amount of saved chunks.
+    pub fn save_chunks(&self, chunks: Vec<Chunk>) -> Result<usize, AnvilError> {
+        self.chunks.save_chunks(chunks)
+    }
+}
+
+/// Writes the packet to all provided clients. Failures are ignored, as the
+/// disconnected clients are handled by their own connections.
+fn broadcast(viewers: &[Arc<PacketWriter>], packet: &dyn Packet) {
+    for viewer in viewers {
+        let _ = viewer.write_packet(packet);
+    }
+}
+
+/// All worlds hosted by the server, by their names.
+pub struct Worlds {
+    worlds: BTreeMap<String, Arc<World>>,
+    /// Name of the world the players join into.
+    spawn_world: String,
+}
+
+impl Worlds {
+    /// Creates all worlds from the configuration.
+    pub fn from_config(config: &Config) -> Result<Self, WorldError> {
+        let worlds = config
+            .dimensions
+            .iter()
+            .map(|(name, dimension)| {
+                let world = World::from_config(name, dimension, config.seed)?;
+                Ok((name.clone(), Arc::new(world)))
+            })
+            .collect::<Result<_, WorldError>>()?;
+
+        Ok(Self {
+            worlds,
+            spawn_world: config.spawn_dimension.clone(),
+        })
+    }
+
+    /// Returns the world with the provided name.
+    pub fn get(&self, name: &str) -> Option<&Arc<World>> {
+        self.worlds.get(name)
+    }
+
+    /// Returns the world the players join into.
+    pub fn spawn_world(&self) -> &Arc<World> {
+        &self.worlds[&self.spawn_world]
+    }
+
+    /// Returns the names of all worlds, which are sent to the clients.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.worlds.keys().map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<World>> {
+        self.worlds.values()
+    }
+}
+
+/// Source of the chunks of a world: the world directory, with a fallback to
+/// the generator.
+struct DimensionSource {
+    name: String,
+    height: WorldHeight,
+    has_sky_light: bool,
+    generator: Box<dyn ChunkGenerator>,
+    /// Region files of the world directory.
+    storage: Option<Mutex<RegionStorage>>,
+}
+
+impl ChunkSource for DimensionSource {
+    fn load(&self, chunk_x: i32, chunk_z: i32) -> StoredChunk {
+        let stored = self.storage.as_ref().and_then(|storage| {
+            match storage.lock().unwrap().load_chunk(chunk_x, chunk_z) {
+                Ok(stored) => stored,
+                Err(e) => {
+                    eprintln!(
+                        "Failed to load chunk ({chunk_x}, {chunk_z}) of {}: {e}",
+                        self.name
+                    );
+                    None
+                }
+            }
+        });
+        if let Some(stored) = stored {
+            return stored;
+        }
+
+        let mut chunk = self.generator.generate(chunk_x, chunk_z);
+        chunk.light = ChunkLight::new(chunk.sections.len(), self.has_sky_light);
+        // generated chunks aren't stored yet, and without the world
+        // directory there's nowhere to store them
+        chunk.is_dirty = self.storage.is_some();
+        StoredChunk {
+            chunk,
+            is_lit: false,
+        }
+    }
+
+    fn save(&self, chunk: &Chunk) -> Result<(), AnvilError> {
+        match &self.storage {
+            Some(storage) => storage.lock().unwrap().save_chunk(chunk),
+            None => Ok(()),
+        }
+    }
+
+    fn height(&self) -> WorldHeight {
+        self.height
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkSection {
+    pub block_states: [BlockState; 16 * 16 * 16],
+    pub biomes: [VarInt; 64],
+}
+
+impl Default for ChunkSection {
+    fn default() -> Self {
+        Self {
+            block_states: [BlockState::AIR; 4096],
+            biomes: [REGISTRY.biome_id(DEFAULT_BIOME).unwrap(); 64],
+        }
+    }
+}
+
+impl ChunkSection {
+    pub fn set_block_at(&mut self, x: usize, y: usize, z: usize, block_state: BlockState) {
+        let block_index = (y << 8) | (z << 4) | x;
+        self.block_states[block_index] = block_state;
+    }
+
+    pub fn get_block_at(&self, x: usize, y: usize, z: usize) -> BlockState {
+        self.block_states[(y << 8) | (z << 4) | x]
+    }
+
+    /// Encodes the block states of this section into the smallest possible
+    /// paletted container.
+    pub fn block_states_container(&self) -> PalettedContainer {
+        let block_ids: Vec<u32> = self
+            .block_states
+            .iter()
+            .map(|state| state.id() as u32)
+            .collect();
+        PalettedContainer::from_values(&block_ids, PaletteKind::BlockStates)
+    }
+
+    /// Encodes the biomes of this section into the smallest possible
+    /// paletted container.
+    pub fn biomes_container(&self) -> PalettedContainer {
+        let biome_ids: Vec<u32> = self.biomes.iter().map(|biome| biome.0 as u32).collect();
+        PalettedContainer::from_values(&biome_ids, PaletteKind::Biomes)
+    }
+
+    pub fn non_air_block_count(&self) -> u16 {
+        self.block_states
+            .iter()
+            .filter(|b| !b.is_air())
+            .count()
+            .min(u16::MAX as usize) as u16
+    }
+}
+
+impl Readable for ChunkSection {
+    fn read(buffer: &[u8]) -> Result<(Self, usize), ReadError> {
+        let mut reader = BufferReader::new(buffer);
+        let _ = reader.read(u16::read)?; // block count, calculated from the block states
+        let block_states =
+            reader.read(|buffer| PalettedContainer::read(buffer, PaletteKind::BlockStates))?;
+        let biomes = reader.read(|buffer| PalettedContainer::read(buffer, PaletteKind::Biomes))?;
+
+        let mut section = ChunkSection::default();
+        let block_ids = block_states.values(PaletteKind::BlockStates)?;
+        for (block_state, id) in section.block_states.iter_mut().zip(block_ids) {
+            *block_state = BlockState::from_id(id as i32)
+                .map_err(|_| ReadError::UnknownBlockState(id as i32))?;
+        }
+
+        let biome_ids = biomes.values(PaletteKind::Biomes)?;
+        for (biome, id) in section.biomes.iter_mut().zip(biome_ids) {
+            *biome = VarInt(id as i32);
+        }
+
+        Ok((section, reader.consumed()))
+    }
+}
+
+impl Writeable for ChunkSection {
+    fn write(&self) -> Result<bytes::Bytes, crate::protocol::WriteError> {
+        let mut buffer = bytes::BytesMut::new();
+        buffer.put_u16(self.non_air_block_count()); // block count (non-air)
+        buffer.extend_from_slice(&self.block_states_container().write()?);
+        buffer.extend_from_slice(&self.biomes_container().write()?);
+        Ok(buffer.freeze())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    pub x: i32,
+    pub z: i32,
+    pub sections: Vec<Option<ChunkSection>>,
+    /// Heightmaps of all kinds from `HeightmapKind::LIVE`, in the same order.
+    /// They're kept up to date by `set_block_at`.
+    pub heightmaps: Vec<Heightmap>,
+    /// Light of the chunk, which is calculated by `light::LightEngine`.
+    pub light: ChunkLight,
+    pub block_entities: Vec<BlockEntity>,
+    /// Whether the chunk was changed since it was loaded or saved. It's set
+    /// by `set_block_at`.
+    pub is_dirty: bool,
+    /// Whether some blocks were replaced when the chunk was loaded, as they
+    /// aren't in the block registry. Such chunks are never saved.
+    pub is_lossy: bool,
+}
+
+impl Chunk {
+    pub fn new(x: i32, z: i32) -> Self {
+        Self::with_height(x, z, DEFAULT_WORLD_HEIGHT)
+    }
+
+    /// Creates an empty chunk for the dimension of the provided height, which
+    /// must be a multiple of 16.
+    pub fn with_height(x: i32, z: i32, height: usize) -> Self {
+        Self {
+            x,
+            z,
+            sections: vec![Some(ChunkSection::default()); height / 16],
+            heightmaps: HeightmapKind::LIVE
+                .iter()
+                .map(|&kind| Heightmap::new(kind, height))
+                .collect(),
+            light: ChunkLight::new(height / 16, true),
+            block_entities: vec![],
+            is_dirty: false,
+            is_lossy: false,
+        }
+    }
+
+    /// Returns the height of the chunk in blocks.
+    pub fn height(&self) -> usize {
+        self.sections.len() * 16
+    }
+
+    /// Returns the heightmap of the provided kind, if it's maintained.
+    pub fn heightmap(&self, kind: HeightmapKind) -> Option<&Heightmap> {
+        self.heightmaps
+            .iter()
+            .find(|heightmap| heightmap.kind == kind)
+    }
+
+    /// Returns copies of the heightmaps that are sent to the client in the
+    /// chunk data packet.
+    pub fn client_heightmaps(&self) -> Vec<Heightmap> {
+        self.heightmaps
+            .iter()
+            .filter(|heightmap| heightmap.kind.is_sent_to_client())
+            .cloned()
+            .collect()
+    }
+
+    /// Recalculates all heightmaps from scratch, i.e. after the sections were
+    /// replaced.
+    pub fn recalculate_heightmaps(&mut self) {
+        let top = self.height();
+        for heightmap in &mut self.heightmaps {
+            *heightmap = Heightmap::new(heightmap.kind, top);
+            for x in 0..16 {
+                for z in 0..16 {
+                    heightmap
+                        .recalculate_column(x, z, top, |y| Self::block_in(&self.sections, x, y, z));
+                }
+            }
+        }
+    }
+
+    pub fn set_block_at(&mut self, x: usize, y: usize, z: usize, block_state: BlockState) {
+        self.set_section_block_at(x, y, z, block_state);
+        self.is_dirty = true;
+
+        let (x, z) = (x.rem_euclid(16), z.rem_euclid(16));
+        for heightmap in &mut self.heightmaps {
+            heightmap.update(x, y, z, block_state, |y| {
+                Self::block_in(&self.sections, x, y, z)
+            });
+        }
+    }
+
+    fn set_section_block_at(&mut self, x: usize, y: usize, z: usize, block_state: BlockState) {
+        let section_index = y.div_euclid(16);
+
+        let local_section_x = x.rem_euclid(16);
+        let local_section_y = y.rem_euclid(16);
+        let local_section_z = z.rem_euclid(16);
+
+        if self.sections[section_index].is_none() {
+            self.sections[section_index] = Some(ChunkSection::default());
+        }
+
+        let section = self.sections[section_index].as_mut().unwrap();
+        section.set_block_at(
+            local_section_x,
+            local_section_y,
+            local_section_z,
+            block_state,
+        );
+    }
+
+    pub fn get_block_at(&self, x: usize, y: usize, z: usize) -> BlockState {
+        Self::block_in(&self.sections, x, y, z)
+    }
+
+    /// Returns the block entity at the provided coordinates within the chunk.
+    pub fn block_entity(&self, x: usize, y: usize, z: usize) -> Option<&BlockEntity> {
+        self.block_entities
+            .iter()
+            .find(|block_entity| (block_entity.x, block_entity.y, block_entity.z) == (x, y, z))
+    }
+
+    /// Adds the block entity, replacing the one at the same coordinates.
+    pub fn set_block_entity(&mut self, block_entity: BlockEntity) {
+        self.remove_block_entity(block_entity.x, block_entity.y, block_entity.z);
+        self.block_entities.push(block_entity);
+        self.is_dirty = true;
+    }
+
+    /// Removes the block entity at the provided coordinates within the chunk.
+    pub fn remove_block_entity(&mut self, x: usize, y: usize, z: usize) -> Option<BlockEntity> {
+        let index = self.block_entities.iter().position(|block_entity| {
+            (block_entity.x, block_entity.y, block_entity.z) == (x, y, z)
+        })?;
+        self.is_dirty = true;
+        Some(self.block_entities.swap_remove(index))
+    }
+
+    /// Returns the block state at the provided coordinates of the provided
+    /// sections. Used where `self` is already borrowed mutably.
+    fn block_in(sections: &[Option<ChunkSection>], x: usize, y: usize, z: usize) -> BlockState {
+        match &sections[y.div_euclid(16)] {
+            Some(section) => {
+                section.get_block_at(x.rem_euclid(16), y.rem_euclid(16), z.rem_euclid(16))
+            }
+            None => BlockState::AIR,
+        }
+    }
+}
+
+impl Readable for Chunk {
+    /// Reads the sections of the chunk. Coordinates of the chunk aren't a part
+    /// of its data, so they're left as zero and should be set by the caller.
+    fn read(buffer: &[u8]) -> Result<(Self, usize), ReadError> {
+        let mut reader = BufferReader::new(buffer);
+        let data_length = reader.read(VarInt::read)?;
+        if data_length.0 < 0 {
+            return Err(ReadError::MalformedBuffer);
+        }
+
+        let data_start = reader.consumed();
+        let data = buffer
+            .get(data_start..data_start + data_length.0 as usize)
+            .ok_or(ReadError::Incomplete)?;
+
+        let mut section_reader = BufferReader::new(data);
+        let mut sections = vec![];
+        while section_reader.consumed() < data.len() {
+            sections.push(Some(section_reader.read(ChunkSection::read)?));
+        }
+
+        let mut chunk = Self::with_height(0, 0, sections.len() * 16);
+        chunk.sections = sections;
+        chunk.recalculate_heightmaps();
+
+        Ok((chunk, data_start + data.len()))
+    }
+}
+
+impl Writeable for Chunk {
+    fn write(&self) -> Result<Bytes, crate::protocol::WriteError> {
+        let mut buffer = BytesMut::new();
+
+        let mut section_data = BytesMut::new();
+        for section in self.sections.iter().flatten() {
+            section_data.extend_from_slice(&section.write()?);
+        }
+
+        buffer.extend_from_slice(&VarInt(section_data.len() as i32).write()?); // section data length
+        buffer.extend_from_slice(&section_data); // section data itself
+
+        Ok(buffer.freeze())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::PacketReader;
+    use crate::protocol::{
+        BitSet, PrefixedArray,
+        packets::play::{ChunkData, ClientboundChunkDataAndLightPacket, LightData},
+    };
+
+    #[test]
+    fn test_chunk_packet_round_trip() {
+        let mut chunk = Chunk::new(0, 0);
+        for x in 0..16 {
+            for z in 0..16 {
+                chunk.set_block_at(x, 0, z, BlockState::new("minecraft:bedrock").unwrap());
+                chunk.set_block_at(x, 1, z, BlockState::new("minecraft:dirt").unwrap());
+            }
+        }
+        chunk.set_block_at(3, 2, 7, BlockState::new("minecraft:oak_log").unwrap());
+
+        let packet = ClientboundChunkDataAndLightPacket {
+            chunk_x: 0,
+            chunk_z: 0,
+            chunk_data: ChunkData {
+                heightmap: chunk.client_heightmaps(),
+                data: chunk,
+                block_entities: PrefixedArray(vec![]),
+            },
+            light_data: LightData {
+                sky_light_mask: BitSet::empty(),
+                block_light_mask: BitSet::empty(),
+                empty_sky_light_mask: BitSet::empty(),
+                empty_block_light_mask: BitSet::empty(),
+                sky_lights: PrefixedArray(vec![PrefixedArray(vec![0xFF; 2048])]),
+                block_lights: PrefixedArray(vec![]),
+            },
+        };
+
+        let buffer = packet.write().unwrap();
+        let (read_packet, read_length) = ClientboundChunkDataAndLightPacket::read(&buffer).unwrap();
+        assert_eq!(read_length, buffer.len());
+        assert_eq!(read_packet.write().unwrap(), buffer);
+
+        let read_chunk = read_packet.chunk_data.data;
+        assert_eq!(read_chunk.sections.len(), 24);
+        assert_eq!(
+            read_chunk.get_block_at(3, 2, 7),
+            BlockState::new("minecraft:oak_log").unwrap()
+        );
+        assert_eq!(read_chunk.get_block_at(3, 3, 7), BlockState::AIR);
+
+        let read_heightmap = &read_packet.chunk_data.heightmap[0];
+        assert_eq!(read_heightmap.kind, HeightmapKind::WorldSurface);
+        assert_eq!(read_heightmap.get(3, 7), 3);
+        assert_eq!(read_heightmap.get(4, 7), 2);
+    }
+
+    #[test]
+    fn test_unknown_block_state() {
+        let unknown_id = crate::world::block::BLOCKS.state_count() as i32;
+        // a single-valued section of the unknown state, with a single biome
+        let mut buffer = vec![0x10, 0x00, 0];
+        buffer.extend_from_slice(&VarInt(unknown_id).write().unwrap());
+        buffer.extend_from_slice(&[0, 0]);
+        assert!(matches!(
+            ChunkSection::read(&buffer),
+            Err(ReadError::UnknownBlockState(id)) if id == unknown_id
+        ));
+    }
+
+    #[test]
+    fn test_hashed_seed() {
+        let config = DimensionConfig::test_flat("minecraft:stone");
+        let world = World::from_config("minecraft:overworld", &config, 0).unwrap();
+        assert_eq!(world.hashed_seed(), 8794265229978523055);
+    }
+
+    #[test]
+    fn test_worlds_from_config() {
+        let mut config = Config::default();
+        config.dimensions.insert(
+            String::from("minecraft:the_nether"),
+            DimensionConfig {
+                dimension_type: String::from("minecraft:the_nether"),
+                generator: GeneratorConfig::Noise,
+                sea_level: 32,
+                ..DimensionConfig::test_flat("minecraft:netherrack")
+            },
+        );
+        config.spawn_dimension = String::from("minecraft:the_nether");
+
+        let worlds = Worlds::from_config(&config).unwrap();
+        assert_eq!(
+            worlds.names().collect::<Vec<_>>(),
+            ["minecraft:overworld", "minecraft:the_nether"]
+        );
+
+        let nether = worlds.spawn_world();
+        assert_eq!(nether.name, "minecraft:the_nether");
+        assert!(!nether.has_sky_light);
+        assert_eq!(nether.height.height, 256);
+        assert!(worlds.get("minecraft:the_end").is_none());
+    }
+
+    /// Buffer the tests can read the packets written to the clients from.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_block_changes_are_batched() {
+        let config = DimensionConfig::test_flat("minecraft:stone");
+        let world = World::from_config("minecraft:overworld", &config, 0).unwrap();
+        let buffer = SharedBuffer::default();
+        let viewer = Arc::new(PacketWriter::new(buffer.clone()));
+        world.chunks.acquire(&[(0, 0), (1, 0)], &viewer);
+
+        let stone = BlockState::new("stone").unwrap();
+        assert!(!world.set_block(BlockPos::new(0, -65, 0), stone));
+        assert!(!world.set_block(BlockPos::new(0, 320, 0), stone));
+        assert!(world.set_block(BlockPos::new(1, 70, 1), stone));
+        assert!(world.set_block(BlockPos::new(2, 70, 1), stone));
+        assert!(world.set_block(BlockPos::new(17, -64, 1), BlockState::AIR));
+        assert_eq!(
+            world
+                .chunks
+                .with_chunk(1, 0, |chunk| chunk.get_block_at(1, 0, 1)),
+            Some(BlockState::AIR)
+        );
+        assert_eq!(world.get_block(BlockPos::new(2, 70, 1)), Some(stone));
+        assert_eq!(world.get_block(BlockPos::new(-1, 70, 1)), None);
+        world.flush_block_changes();
+        viewer.flush().unwrap();
+
+        let mut reader = PacketReader::default();
+        reader.extend_from_slice(&buffer.0.lock().unwrap());
+        let mut ids = vec![];
+        while let Some((id, _)) = reader.try_next_packet().unwrap() {
+            ids.push(id);
+        }
+        // the chunks always precede their changes
+        assert_eq!(
+            ids[..4],
+            [
+                ClientboundChunkDataAndLightPacket::PACKET_ID,
+                ClientboundChunkDataAndLightPacket::PACKET_ID,
+                ClientboundUpdateSectionBlocksPacket::PACKET_ID,
+                ClientboundBlockUpdatePacket::PACKET_ID
+            ]
+        );
+        assert!(
+            ids[4..]
+                .iter()
+                .all(|&id| id == ClientboundUpdateLightPacket::PACKET_ID)
+        );
+    }
+
+    #[test]
+    fn test_schematics_are_pasted_once() {
+        let directory =
+            std::env::temp_dir().join(format!("kasumi-schematics-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let structure = NbtCompound::from([
+            (String::from("size"), NbtTag::List(vec![NbtTag::Int(1); 3])),
+            (
+                String::from("palette"),
+                NbtTag::List(vec![NbtTag::Compound(NbtCompound::from([(
+                    String::from("Name"),
+                    NbtTag::String(String::from("minecraft:bedrock")),
+                )]))]),
+            ),
+            (
+                String::from("blocks"),
+                NbtTag::List(vec![NbtTag::Compound(NbtCompound::from([
+                    (String::from("pos"), NbtTag::List(vec![NbtTag::Int(0); 3])),
+                    (String::from("state"), NbtTag::Int(0)),
+                ]))]),
+            ),
+        ]);
+        let structure_path = directory.join("block.nbt");
+        fs::write(
+            &structure_path,
+            NbtTag::Compound(structure).to_named_bytes(""),
+        )
+        .unwrap();
+
+        let config = DimensionConfig {
+            world_directory: Some(directory.join("world").to_string_lossy().into_owned()),
+            schematics: vec![SchematicConfig {
+                path: structure_path.to_string_lossy().into_owned(),
+                position: [3, 70, 5],
+                rotation: Default::default(),
+                mirror: Default::default(),
+            }],
+            ..DimensionConfig::test_flat("minecraft:air")
+        };
+        let position = BlockPos::new(3, 70, 5);
+        let viewer = Arc::new(PacketWriter::new(std::io::sink()));
+        let world = World::from_config("minecraft:overworld", &config, 0).unwrap();
+        world.chunks.acquire(&[(0, 0)], &viewer);
+        assert_eq!(
+            world.get_block(position),
+            Some(BlockState::new("bedrock").unwrap())
+        );
+        world.set_block(position, BlockState::AIR);
+        world.save().unwrap();
+        drop(world);
+
+        // the removed block isn't pasted again
+        let world = World::from_config("minecraft:overworld", &config, 0).unwrap();
+        world.chunks.acquire(&[(0, 0)], &viewer);
+        assert_eq!(world.get_block(position), Some(BlockState::AIR));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_block_entities_are_sent() {
+        let config = DimensionConfig::test_flat("minecraft:stone");
+        let world = World::from_config("minecraft:overworld", &config, 0).unwrap();
+        let sign = BlockPos::new(-3, -60, 5);
+        let text = NbtCompound::from([(
+            String::from("messages"),
+            NbtTag::List(vec![NbtTag::String(String::from("\"Hello\"")); 4]),
+        )]);
+        let data = NbtCompound::from([(String::from("front_text"), NbtTag::Compound(text))]);
+
+        let buffer = SharedBuffer::default();
+        let viewer = Arc::new(PacketWriter::new(buffer.clone()));
+        world.chunks.acquire(&[(-1, 0)], &viewer);
+
+        assert!(world.set_block(sign, BlockState::new("oak_sign").unwrap()));
+        assert!(world.set_block_entity(sign, "sign", data));
+        assert_eq!(world.get_block_entity(sign).unwrap().id, "minecraft:sign");
+        world.flush_block_changes();
+        viewer.flush().unwrap();
+
+        let mut reader = PacketReader::default();
+        reader.extend_from_slice(&buffer.0.lock().unwrap());
+        let (id, _) = reader.try_next_packet().unwrap().unwrap();
+        assert_eq!(id, ClientboundChunkDataAndLightPacket::PACKET_ID);
+        let (id, _) = reader.try_next_packet().unwrap().unwrap();
+        assert_eq!(id, ClientboundBlockUpdatePacket::PACKET_ID);
+        let (id, body) = reader.try_next_packet().unwrap().unwrap();
+        assert_eq!(id, ClientboundBlockEntityDataPacket::PACKET_ID);
+        let (packet, _) = ClientboundBlockEntityDataPacket::read(&body).unwrap();
+        assert_eq!(packet.position, sign);
+        assert_eq!(packet.type_id, VarInt(7));
+
+        let packet = world
+            .chunks
+            .with_chunk(-1, 0, |chunk| {
+                ClientboundChunkDataAndLightPacket::from_chunk(chunk, world.height.min_y)
+            })
+            .unwrap();
+        let block_entity = &packet.chunk_data.block_entities.0[0];
+        assert_eq!(block_entity.packed_xz, 13 << 4 | 5);
+        assert_eq!(block_entity.y, -60);
+
+        // replacing the block removes its block entity
+        assert!(world.set_block(sign, BlockState::AIR));
+        assert!(world.get_block_entity(sign).is_none());
+    }
+
+    #[test]
+    fn test_heightmaps_follow_block_changes() {
+        let mut chunk = Chunk::new(0, 0);
+        let height = |chunk: &Chunk, kind| chunk.heightmap(kind).unwrap().get(1, 2);
+
+        chunk.set_block_at(1, 10, 2, BlockState::new("stone").unwrap());
+        chunk.set_block_at(1, 11, 2, BlockState::new("water").unwrap());
+        chunk.set_block_at(1, 12, 2, BlockState::new("oak_leaves").unwrap());
+        chunk.set_block_at(1, 13, 2, BlockState::new("torch").unwrap());
+        assert_eq!(height(&chunk, HeightmapKind::WorldSurface), 14);
+        assert_eq!(height(&chunk, HeightmapKind::MotionBlocking), 13);
+        assert_eq!(height(&chunk, HeightmapKind::MotionBlockingNoLeaves), 12);
+        assert_eq!(height(&chunk, HeightmapKind::OceanFloor), 13);
+
+        chunk.set_block_at(1, 13, 2, BlockState::AIR);
+        chunk.set_block_at(1, 12, 2, BlockState::AIR);
+        assert_eq!(height(&chunk, HeightmapKind::WorldSurface), 12);
+        assert_eq!(height(&chunk, HeightmapKind::OceanFloor), 11);
+
+        let mut recalculated = chunk.clone();
+        recalculated.recalculate_heightmaps();
+        for (a, b) in chunk.heightmaps.iter().zip(&recalculated.heightmaps) {
+            assert_eq!(a.heights, b.heights);
+        }
+    }
+}
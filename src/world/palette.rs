@@ -0,0 +1,321 @@
+use bytes::{BufMut, Bytes, BytesMut};
+
+use crate::{
+    network::BufferReader,
+    protocol::{ReadError, Readable, WriteError, Writeable},
+    registry::REGISTRY,
+    varint::VarInt,
+    world::block::BLOCKS,
+};
+
+/// Returns the amount of bits needed to represent the provided amount of
+/// distinct values.
+fn bits_needed(count: usize) -> u8 {
+    if count <= 1 {
+        return 0;
+    }
+    (usize::BITS - (count - 1).leading_zeros()) as u8
+}
+
+/// Packs the provided entries into the data array with the specified bits per
+/// entry size. Entries never span across multiple longs, so the remaining
+/// bits of each long are left as padding.
+pub fn pack_data_array(entries: &[u32], bits_per_entry: u8) -> Vec<u64> {
+    let entries_per_long = 64 / bits_per_entry as usize;
+    let mut output = Vec::with_capacity(entries.len().div_ceil(entries_per_long));
+
+    for chunk in entries.chunks(entries_per_long) {
+        let mut word = 0u64;
+        for (index, &entry) in chunk.iter().enumerate() {
+            word |= (entry as u64) << (index * bits_per_entry as usize);
+        }
+        output.push(word);
+    }
+
+    output
+}
+
+/// Unpacks the provided amount of entries from the data array with the
+/// specified bits per entry size. See `pack_data_array` for the layout.
+pub fn unpack_data_array(data: &[u64], bits_per_entry: u8, count: usize) -> Vec<u32> {
+    let entries_per_long = 64 / bits_per_entry as usize;
+    let mask = (1u64 << bits_per_entry) - 1;
+
+    (0..count)
+        .map(|index| {
+            let word = data.get(index / entries_per_long).copied().unwrap_or(0);
+            let shift = (index % entries_per_long) * bits_per_entry as usize;
+            ((word >> shift) & mask) as u32
+        })
+        .collect()
+}
+
+/// Kinds of the paletted containers that are sent in chunk sections. They
+/// differ in the amount of entries and the supported palette sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteKind {
+    /// 16x16x16 block states of a section.
+    BlockStates,
+    /// 4x4x4 biomes of a section.
+    Biomes,
+}
+
+impl PaletteKind {
+    /// Returns the amount of entries the container of this kind holds.
+    pub fn entries(&self) -> usize {
+        match self {
+            PaletteKind::BlockStates => 16 * 16 * 16,
+            PaletteKind::Biomes => 4 * 4 * 4,
+        }
+    }
+
+    /// Returns the minimum amount of bits per entry for the indirect palette.
+    /// Smaller palettes are still encoded with this amount of bits.
+    pub fn min_indirect_bits(&self) -> u8 {
+        match self {
+            PaletteKind::BlockStates => 4,
+            PaletteKind::Biomes => 1,
+        }
+    }
+
+    /// Returns the maximum amount of bits per entry for the indirect palette.
+    /// Anything above switches to the direct palette.
+    pub fn max_indirect_bits(&self) -> u8 {
+        match self {
+            PaletteKind::BlockStates => 8,
+            PaletteKind::Biomes => 3,
+        }
+    }
+
+    /// Returns the amount of bits per entry for the direct palette, which is
+    /// based on the size of the corresponding registry.
+    pub fn direct_bits(&self) -> u8 {
+        match self {
+            PaletteKind::BlockStates => bits_needed(BLOCKS.state_count()),
+            PaletteKind::Biomes => bits_needed(REGISTRY.biome.len()),
+        }
+    }
+}
+
+/// Representation of the palette of a paletted container.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Palette {
+    /// The whole container is filled with the single value, and there is no
+    /// data array.
+    SingleValued(VarInt),
+    /// The data array holds indices into this list of values.
+    Indirect(Vec<VarInt>),
+    /// The data array holds the registry IDs themselves.
+    Direct,
+}
+
+/// Representation of a paletted container - the compact format of block
+/// states and biomes in the chunk sections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PalettedContainer {
+    pub bits_per_entry: u8,
+    pub palette: Palette,
+    pub data: Vec<u64>,
+}
+
+impl PalettedContainer {
+    /// Builds the smallest possible container of the provided kind that
+    /// contains the provided values (registry IDs).
+    pub fn from_values(values: &[u32], kind: PaletteKind) -> Self {
+        let mut palette: Vec<u32> = Vec::new();
+        for value in values {
+            if !palette.contains(value) {
+                palette.push(*value);
+            }
+            // there is no point to look further if we're going to be direct
+            if bits_needed(palette.len()) > kind.max_indirect_bits() {
+                break;
+            }
+        }
+
+        if palette.len() == 1 {
+            return Self {
+                bits_per_entry: 0,
+                palette: Palette::SingleValued(VarInt(palette[0] as i32)),
+                data: vec![],
+            };
+        }
+
+        let bits_per_entry = bits_needed(palette.len()).max(kind.min_indirect_bits());
+        if bits_per_entry > kind.max_indirect_bits() {
+            let bits_per_entry = kind.direct_bits();
+            return Self {
+                bits_per_entry,
+                palette: Palette::Direct,
+                data: pack_data_array(values, bits_per_entry),
+            };
+        }
+
+        let indices: Vec<u32> = values
+            .iter()
+            .map(|value| palette.iter().position(|v| v == value).unwrap() as u32)
+            .collect();
+        Self {
+            bits_per_entry,
+            palette: Palette::Indirect(palette.iter().map(|&v| VarInt(v as i32)).collect()),
+            data: pack_data_array(&indices, bits_per_entry),
+        }
+    }
+
+    /// Reads the container of the provided kind from the buffer. The kind is
+    /// needed, because the amount of entries isn't sent over the wire.
+    pub fn read(buffer: &[u8], kind: PaletteKind) -> Result<(Self, usize), ReadError> {
+        let mut reader = BufferReader::new(buffer);
+        let sent_bits_per_entry = reader.read(u8::read)?;
+
+        let (bits_per_entry, palette) = match sent_bits_per_entry {
+            0 => (0, Palette::SingleValued(reader.read(VarInt::read)?)),
+            bits if bits <= kind.max_indirect_bits() => {
+                let length = reader.read(VarInt::read)?;
+                if length.0 < 0 {
+                    return Err(ReadError::MalformedBuffer);
+                }
+
+                let mut values = Vec::with_capacity(length.0 as usize);
+                for _ in 0..length.0 {
+                    values.push(reader.read(VarInt::read)?);
+                }
+                (
+                    bits.max(kind.min_indirect_bits()),
+                    Palette::Indirect(values),
+                )
+            }
+            _ => (kind.direct_bits(), Palette::Direct),
+        };
+
+        let data_length = match bits_per_entry {
+            0 => 0,
+            bits => kind.entries().div_ceil(64 / bits as usize),
+        };
+        let mut data = Vec::with_capacity(data_length);
+        for _ in 0..data_length {
+            data.push(reader.read(i64::read)? as u64);
+        }
+
+        Ok((
+            Self {
+                bits_per_entry,
+                palette,
+                data,
+            },
+            reader.consumed(),
+        ))
+    }
+
+    /// Returns all values (registry IDs) of this container of the provided
+    /// kind.
+    pub fn values(&self, kind: PaletteKind) -> Result<Vec<u32>, ReadError> {
+        match &self.palette {
+            Palette::SingleValued(value) => Ok(vec![value.0 as u32; kind.entries()]),
+            Palette::Indirect(palette) => {
+                unpack_data_array(&self.data, self.bits_per_entry, kind.entries())
+                    .into_iter()
+                    .map(|index| {
+                        palette
+                            .get(index as usize)
+                            .map(|value| value.0 as u32)
+                            .ok_or(ReadError::MalformedBuffer)
+                    })
+                    .collect()
+            }
+            Palette::Direct => Ok(unpack_data_array(
+                &self.data,
+                self.bits_per_entry,
+                kind.entries(),
+            )),
+        }
+    }
+}
+
+impl Writeable for PalettedContainer {
+    fn write(&self) -> Result<Bytes, WriteError> {
+        let mut buffer = BytesMut::new();
+        buffer.put_u8(self.bits_per_entry);
+
+        match &self.palette {
+            Palette::SingleValued(value) => buffer.extend_from_slice(&value.write()?),
+            Palette::Indirect(values) => {
+                buffer.extend_from_slice(&VarInt(values.len() as i32).write()?);
+                for value in values {
+                    buffer.extend_from_slice(&value.write()?);
+                }
+            }
+            Palette::Direct => {}
+        }
+
+        // the length of the data array isn't sent, as it's known from the
+        // amount of bits per entry
+        for word in &self.data {
+            buffer.put_u64(*word);
+        }
+
+        Ok(buffer.freeze())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_valued() {
+        let container = PalettedContainer::from_values(&[0; 4096], PaletteKind::BlockStates);
+        assert_eq!(container.palette, Palette::SingleValued(VarInt(0)));
+        assert!(container.data.is_empty());
+        assert_eq!(container.write().unwrap().as_ref(), &[0, 0]);
+    }
+
+    #[test]
+    fn test_indirect() {
+        let values: Vec<u32> = (0..4096).map(|i| [1, 9, 10][i % 3]).collect();
+        let container = PalettedContainer::from_values(&values, PaletteKind::BlockStates);
+        assert_eq!(container.bits_per_entry, 4);
+        assert_eq!(
+            container.palette,
+            Palette::Indirect(vec![VarInt(1), VarInt(9), VarInt(10)])
+        );
+        assert_eq!(container.data.len(), 4096 / 16);
+        assert_eq!(container.data[0] & 0xFFF, 0x210);
+
+        let biomes: Vec<u32> = (0..64).map(|i| (i % 5) as u32).collect();
+        let container = PalettedContainer::from_values(&biomes, PaletteKind::Biomes);
+        assert_eq!(container.bits_per_entry, 3);
+        assert_eq!(container.data.len(), 64usize.div_ceil(21));
+    }
+
+    #[test]
+    fn test_direct() {
+        let values: Vec<u32> = (0..4096).map(|i| (i % 300) as u32).collect();
+        let container = PalettedContainer::from_values(&values, PaletteKind::BlockStates);
+        assert_eq!(container.palette, Palette::Direct);
+        assert_eq!(
+            container.bits_per_entry,
+            PaletteKind::BlockStates.direct_bits()
+        );
+    }
+
+    #[test]
+    fn test_read_back() {
+        let values: Vec<u32> = (0..4096).map(|i| [1, 9, 10, 14, 15][i % 5]).collect();
+        let container = PalettedContainer::from_values(&values, PaletteKind::BlockStates);
+        let buffer = container.write().unwrap();
+
+        let (read, read_length) =
+            PalettedContainer::read(&buffer, PaletteKind::BlockStates).unwrap();
+        assert_eq!(read_length, buffer.len());
+        assert_eq!(read, container);
+        assert_eq!(read.values(PaletteKind::BlockStates).unwrap(), values);
+    }
+
+    #[test]
+    fn test_pack_data_array_padding() {
+        // 5 bits per entry leaves 4 bits of padding in each long
+        let packed = pack_data_array(&[31; 13], 5);
+        assert_eq!(packed, vec![(1 << 60) - 1, 31]);
+    }
+}
@@ -0,0 +1,208 @@
+use bytes::Bytes;
+
+use crate::protocol::{ReadError, Readable, WriteError, Writeable};
+
+/// Absolute coordinates of a block in the world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl BlockPos {
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Returns the position of the block containing the provided point.
+    pub fn from_coordinates(x: f64, y: f64, z: f64) -> Self {
+        Self::new(x.floor() as i32, y.floor() as i32, z.floor() as i32)
+    }
+
+    /// Returns the position moved by the provided offsets.
+    pub const fn offset(self, dx: i32, dy: i32, dz: i32) -> Self {
+        Self::new(self.x + dx, self.y + dy, self.z + dz)
+    }
+
+    pub const fn chunk(self) -> ChunkPos {
+        ChunkPos::new(self.x >> 4, self.z >> 4)
+    }
+
+    pub const fn section(self) -> SectionPos {
+        SectionPos::new(self.x >> 4, self.y >> 4, self.z >> 4)
+    }
+
+    /// Returns the coordinates of the block within its section.
+    pub const fn local(self) -> (usize, usize, usize) {
+        (
+            (self.x & 15) as usize,
+            (self.y & 15) as usize,
+            (self.z & 15) as usize,
+        )
+    }
+
+    /// Packs the position into a long the same way as the protocol does: 26
+    /// bits of X, 26 bits of Z and 12 bits of Y.
+    pub const fn pack(self) -> i64 {
+        (self.x as i64 & 0x3FFFFFF) << 38
+            | (self.z as i64 & 0x3FFFFFF) << 12
+            | self.y as i64 & 0xFFF
+    }
+
+    /// Unpacks the position from a long. It's the reverse of `pack`.
+    pub const fn unpack(packed: i64) -> Self {
+        Self::new(
+            (packed >> 38) as i32,
+            (packed << 52 >> 52) as i32,
+            (packed << 26 >> 38) as i32,
+        )
+    }
+}
+
+impl Readable for BlockPos {
+    fn read(buffer: &[u8]) -> Result<(Self, usize), ReadError> {
+        let (packed, read_length) = i64::read(buffer)?;
+        Ok((Self::unpack(packed), read_length))
+    }
+}
+
+impl Writeable for BlockPos {
+    fn write(&self) -> Result<Bytes, WriteError> {
+        self.pack().write()
+    }
+}
+
+/// Coordinates of a chunk, which are the block coordinates divided by 16.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl ChunkPos {
+    pub const fn new(x: i32, z: i32) -> Self {
+        Self { x, z }
+    }
+
+    /// Returns the chunk containing the provided point.
+    pub fn from_coordinates(x: f64, z: f64) -> Self {
+        Self::new((x.floor() as i32) >> 4, (z.floor() as i32) >> 4)
+    }
+
+    /// Returns the position of the block at the provided coordinates within
+    /// the chunk. Y is absolute.
+    pub const fn block(self, x: usize, y: i32, z: usize) -> BlockPos {
+        BlockPos::new(self.x * 16 + x as i32, y, self.z * 16 + z as i32)
+    }
+}
+
+impl From<(i32, i32)> for ChunkPos {
+    fn from((x, z): (i32, i32)) -> Self {
+        Self::new(x, z)
+    }
+}
+
+impl From<ChunkPos> for (i32, i32) {
+    fn from(position: ChunkPos) -> Self {
+        (position.x, position.z)
+    }
+}
+
+/// Coordinates of a 16x16x16 section, which are the block coordinates divided
+/// by 16.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SectionPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl SectionPos {
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    pub const fn chunk(self) -> ChunkPos {
+        ChunkPos::new(self.x, self.z)
+    }
+
+    /// Returns the position of the block at the provided coordinates within
+    /// the section.
+    pub const fn block(self, x: usize, y: usize, z: usize) -> BlockPos {
+        BlockPos::new(
+            self.x * 16 + x as i32,
+            self.y * 16 + y as i32,
+            self.z * 16 + z as i32,
+        )
+    }
+
+    /// Packs the position into a long the same way as the protocol does: 22
+    /// bits of X, 22 bits of Z and 20 bits of Y.
+    pub const fn pack(self) -> i64 {
+        (self.x as i64 & 0x3FFFFF) << 42
+            | (self.z as i64 & 0x3FFFFF) << 20
+            | self.y as i64 & 0xFFFFF
+    }
+
+    /// Unpacks the position from a long. It's the reverse of `pack`.
+    pub const fn unpack(packed: i64) -> Self {
+        Self::new(
+            (packed >> 42) as i32,
+            (packed << 44 >> 44) as i32,
+            (packed << 22 >> 42) as i32,
+        )
+    }
+}
+
+impl Readable for SectionPos {
+    fn read(buffer: &[u8]) -> Result<(Self, usize), ReadError> {
+        let (packed, read_length) = i64::read(buffer)?;
+        Ok((Self::unpack(packed), read_length))
+    }
+}
+
+impl Writeable for SectionPos {
+    fn write(&self) -> Result<Bytes, WriteError> {
+        self.pack().write()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_pos_packing() {
+        for position in [
+            BlockPos::new(0, 0, 0),
+            BlockPos::new(18357644, 831, -20882616),
+            BlockPos::new(-1, -64, -1),
+            BlockPos::new(-33554432, -2048, 33554431),
+        ] {
+            assert_eq!(BlockPos::unpack(position.pack()), position);
+            let buffer = position.write().unwrap();
+            assert_eq!(BlockPos::read(&buffer).unwrap(), (position, 8));
+        }
+        // the example from the protocol documentation
+        assert_eq!(
+            BlockPos::new(18357644, 831, -20882616).pack(),
+            0x4607_632C_15B4_833F
+        );
+    }
+
+    #[test]
+    fn test_section_pos_packing() {
+        let position = SectionPos::new(-3, -4, 2097151);
+        assert_eq!(SectionPos::unpack(position.pack()), position);
+        assert_eq!(
+            BlockPos::new(-1, -64, 35).section(),
+            SectionPos::new(-1, -4, 2)
+        );
+        assert_eq!(BlockPos::new(-1, -64, 35).local(), (15, 0, 3));
+        assert_eq!(
+            SectionPos::new(-1, -4, 2).block(15, 0, 3),
+            BlockPos::new(-1, -64, 35)
+        );
+    }
+}
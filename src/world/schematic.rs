@@ -0,0 +1,674 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    protocol::{
+        ReadError, Readable,
+        nbt::{NbtCompound, NbtTag},
+    },
+    varint::VarInt,
+    world::{World, block::BlockState, position::BlockPos},
+};
+
+/// The first bytes of the gzip-compressed files.
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+/// Horizontal directions in the clockwise order.
+const DIRECTIONS: [&str; 4] = ["north", "east", "south", "west"];
+
+/// Errors that can occur while loading a schematic.
+#[derive(Debug, Error)]
+pub enum SchematicError {
+    /// Indicates that the schematic file couldn't be read.
+    #[error("I/O error has occurred: {0}")]
+    IoError(#[from] io::Error),
+    /// Indicates that the NBT of the schematic is malformed.
+    #[error("failed to read the NBT: {0}")]
+    NbtError(#[from] ReadError),
+    /// Indicates that the schematic misses the required data or it's invalid.
+    #[error("malformed schematic: {0}")]
+    MalformedSchematic(&'static str),
+    /// Indicates that the version of the Sponge schematic isn't supported.
+    #[error("unsupported schematic version: {0}")]
+    UnsupportedVersion(i64),
+}
+
+/// Rotation of a schematic around the vertical axis when it's pasted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise90,
+    Clockwise180,
+    Counterclockwise90,
+}
+
+impl Rotation {
+    /// Returns the amount of clockwise quarter turns.
+    fn quarter_turns(self) -> usize {
+        match self {
+            Rotation::None => 0,
+            Rotation::Clockwise90 => 1,
+            Rotation::Clockwise180 => 2,
+            Rotation::Counterclockwise90 => 3,
+        }
+    }
+
+    /// Rotates the horizontal offset from the origin.
+    fn rotate(self, x: i32, z: i32) -> (i32, i32) {
+        match self {
+            Rotation::None => (x, z),
+            Rotation::Clockwise90 => (-z, x),
+            Rotation::Clockwise180 => (-x, -z),
+            Rotation::Counterclockwise90 => (z, -x),
+        }
+    }
+}
+
+/// Mirroring of a schematic when it's pasted, which is applied before the
+/// rotation, the same as vanilla does.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mirror {
+    #[default]
+    None,
+    /// Swaps north and south.
+    LeftRight,
+    /// Swaps east and west.
+    FrontBack,
+}
+
+impl Mirror {
+    fn mirror(self, x: i32, z: i32) -> (i32, i32) {
+        match self {
+            Mirror::None => (x, z),
+            Mirror::LeftRight => (x, -z),
+            Mirror::FrontBack => (-x, z),
+        }
+    }
+
+    fn mirror_direction(self, direction: &'static str) -> &'static str {
+        match (self, direction) {
+            (Mirror::LeftRight, "north") => "south",
+            (Mirror::LeftRight, "south") => "north",
+            (Mirror::FrontBack, "east") => "west",
+            (Mirror::FrontBack, "west") => "east",
+            _ => direction,
+        }
+    }
+}
+
+/// Rotates the horizontal direction clockwise by the provided amount of
+/// quarter turns. Other values are returned as they are.
+fn rotate_direction(direction: &'static str, quarter_turns: usize) -> &'static str {
+    match DIRECTIONS.iter().position(|other| *other == direction) {
+        Some(index) => DIRECTIONS[(index + quarter_turns) % 4],
+        None => direction,
+    }
+}
+
+/// Returns the block state as it's after the mirroring and the rotation, i.e.
+/// with the `facing` property turned accordingly.
+fn transform_state(state: BlockState, rotation: Rotation, mirror: Mirror) -> BlockState {
+    let turns = rotation.quarter_turns();
+    let transform = |direction: &'static str| -> &'static str {
+        rotate_direction(mirror.mirror_direction(direction), turns)
+    };
+    let mut transformed = state;
+
+    for property in ["facing", "horizontal_facing"] {
+        if let Some(facing) = state.get(property) {
+            transformed = transformed
+                .with(property, transform(facing))
+                .unwrap_or(transformed);
+        }
+    }
+
+    // connections of fences, walls, panes and such
+    if DIRECTIONS
+        .iter()
+        .all(|direction| state.get(direction).is_some())
+    {
+        for direction in DIRECTIONS {
+            let value = state.get(direction).unwrap();
+            transformed = transformed
+                .with(transform(direction), value)
+                .unwrap_or(transformed);
+        }
+    }
+
+    // 16 directions of signs, banners and skulls, with 0 facing south
+    if let Some(value) = state
+        .get("rotation")
+        .and_then(|value| value.parse::<i32>().ok())
+    {
+        let signed = if value > 8 { value - 16 } else { value };
+        let mirrored = match mirror {
+            Mirror::None => value,
+            Mirror::LeftRight => (8 - signed + 16) % 16,
+            Mirror::FrontBack => (16 - signed) % 16,
+        };
+        let rotated = (mirrored + turns as i32 * 4) % 16;
+        transformed = transformed
+            .with("rotation", &rotated.to_string())
+            .unwrap_or(transformed);
+    }
+
+    if turns % 2 == 1
+        && let Some(axis) = state.get("axis")
+    {
+        let axis = match axis {
+            "x" => "z",
+            "z" => "x",
+            axis => axis,
+        };
+        transformed = transformed.with("axis", axis).unwrap_or(transformed);
+    }
+
+    if mirror != Mirror::None {
+        // sides of stairs, doors and double chests swap in the mirror
+        for property in ["shape", "hinge", "type"] {
+            let Some(value) = state.get(property) else {
+                continue;
+            };
+            let swapped = if value.contains("left") {
+                value.replace("left", "right")
+            } else {
+                value.replace("right", "left")
+            };
+            transformed = transformed.with(property, &swapped).unwrap_or(transformed);
+        }
+    }
+
+    // rails, whose shapes are made of the directions
+    if state.name().ends_with("rail")
+        && let Some(shape) = state.get("shape")
+    {
+        let parts: Vec<&str> = shape.split('_').map(transform).collect();
+        let shape = parts.join("_");
+        let reversed = parts.iter().rev().copied().collect::<Vec<_>>().join("_");
+        transformed = transformed
+            .with("shape", &shape)
+            .or_else(|_| transformed.with("shape", &reversed))
+            .unwrap_or(transformed);
+    }
+
+    transformed
+}
+
+/// Returns the block state of the palette entry the same way as
+/// `BlockState::with_properties_lossy`, or `None` for the structure void.
+/// Substituted entries are reported.
+fn palette_state(name: &str, properties: &[(&str, &str)]) -> Option<BlockState> {
+    if name.trim_start_matches("minecraft:") == "structure_void" {
+        return None;
+    }
+    match BlockState::with_properties_lossy(name, properties) {
+        Ok(state) => Some(state),
+        Err((substitute, e)) => {
+            eprintln!("Schematic block {name} is replaced with {substitute}: {e}");
+            Some(substitute)
+        }
+    }
+}
+
+/// Parses the palette entry in the `name[property=value,...]` format.
+fn parse_palette_state(value: &str) -> Option<BlockState> {
+    let (name, properties) = match value.split_once('[') {
+        Some((name, properties)) => (name, properties.trim_end_matches(']')),
+        None => (value, ""),
+    };
+    let properties: Vec<(&str, &str)> = properties
+        .split(',')
+        .filter_map(|property| property.split_once('='))
+        .collect();
+    palette_state(name, &properties)
+}
+
+/// A block entity of a schematic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchematicBlockEntity {
+    /// Coordinates of the block within the schematic.
+    pub position: BlockPos,
+    /// Namespaced type of the block entity, i.e. `minecraft:sign`.
+    pub id: String,
+    pub data: NbtCompound,
+}
+
+/// A piece of a world saved in a file, i.e. a building, which can be pasted
+/// into the worlds.
+#[derive(Debug, Clone)]
+pub struct Schematic {
+    pub width: usize,
+    pub height: usize,
+    pub length: usize,
+    /// Blocks ordered by Y, then Z, and then X. `None` is the structure void,
+    /// which keeps the existing block when the schematic is pasted.
+    pub blocks: Vec<Option<BlockState>>,
+    pub block_entities: Vec<SchematicBlockEntity>,
+}
+
+impl Schematic {
+    /// Loads the schematic from the file in either Sponge (`.schem`) or the
+    /// vanilla structure (`.nbt`) format, compressed or not.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SchematicError> {
+        let mut data = fs::read(path)?;
+        if data.starts_with(&GZIP_MAGIC) {
+            let mut decompressed = vec![];
+            GzDecoder::new(&data[..]).read_to_end(&mut decompressed)?;
+            data = decompressed;
+        }
+
+        let (_, root, _) = NbtTag::read_named(&data)?;
+        let root = root
+            .as_compound()
+            .ok_or(SchematicError::MalformedSchematic("root isn't a compound"))?;
+        Self::from_nbt(root)
+    }
+
+    /// Reads the schematic from its root compound in any of the supported
+    /// formats.
+    pub fn from_nbt(root: &NbtCompound) -> Result<Self, SchematicError> {
+        if root.contains_key("blocks") && root.contains_key("size") {
+            return Self::from_structure(root);
+        }
+
+        // Sponge schematics of version 3 are wrapped into another compound
+        let schematic = match root.get("Schematic").and_then(NbtTag::as_compound) {
+            Some(schematic) => schematic,
+            None => root,
+        };
+        match schematic.get("Version").and_then(NbtTag::as_i64) {
+            Some(1 | 2) => Self::from_sponge(schematic, schematic),
+            Some(3) => {
+                let blocks = schematic
+                    .get("Blocks")
+                    .and_then(NbtTag::as_compound)
+                    .ok_or(SchematicError::MalformedSchematic("missing blocks"))?;
+                Self::from_sponge(schematic, blocks)
+            }
+            Some(version) => Err(SchematicError::UnsupportedVersion(version)),
+            None => Err(SchematicError::MalformedSchematic("missing version")),
+        }
+    }
+
+    /// Reads the Sponge schematic. Version 2 keeps the blocks in the root
+    /// compound, while version 3 keeps them in the `Blocks` one.
+    fn from_sponge(root: &NbtCompound, blocks: &NbtCompound) -> Result<Self, SchematicError> {
+        let dimension = |key| {
+            root.get(key)
+                .and_then(NbtTag::as_i64)
+                // the dimensions are unsigned shorts
+                .map(|value| value as u16 as usize)
+                .ok_or(SchematicError::MalformedSchematic("missing dimensions"))
+        };
+        let (width, height, length) = (
+            dimension("Width")?,
+            dimension("Height")?,
+            dimension("Length")?,
+        );
+
+        let palette = blocks
+            .get("Palette")
+            .and_then(NbtTag::as_compound)
+            .ok_or(SchematicError::MalformedSchematic("missing palette"))?;
+        let mut states = vec![None; palette.len()];
+        for (value, index) in palette {
+            let index = index
+                .as_i64()
+                .and_then(|index| usize::try_from(index).ok())
+                .filter(|&index| index < states.len())
+                .ok_or(SchematicError::MalformedSchematic("invalid palette index"))?;
+            states[index] = parse_palette_state(value);
+        }
+
+        let data_key = if blocks.contains_key("BlockData") {
+            "BlockData"
+        } else {
+            "Data"
+        };
+        let data: Vec<u8> = blocks
+            .get(data_key)
+            .and_then(NbtTag::as_byte_array)
+            .ok_or(SchematicError::MalformedSchematic("missing block data"))?
+            .iter()
+            .map(|&byte| byte as u8)
+            .collect();
+
+        let mut offset = 0;
+        let mut schematic_blocks = Vec::with_capacity(width * height * length);
+        while offset < data.len() {
+            let (VarInt(index), read_length) = VarInt::read(&data[offset..])?;
+            offset += read_length;
+
+            let state = usize::try_from(index)
+                .ok()
+                .and_then(|index| states.get(index))
+                .ok_or(SchematicError::MalformedSchematic("invalid palette index"))?;
+            schematic_blocks.push(*state);
+        }
+        if schematic_blocks.len() != width * height * length {
+            return Err(SchematicError::MalformedSchematic("wrong amount of blocks"));
+        }
+
+        let block_entities = blocks
+            .get("BlockEntities")
+            .or_else(|| root.get("TileEntities"))
+            .and_then(NbtTag::as_list)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(NbtTag::as_compound)
+            .filter_map(sponge_block_entity)
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            length,
+            blocks: schematic_blocks,
+            block_entities,
+        })
+    }
+
+    /// Reads the vanilla structure, which lists its blocks with their
+    /// positions. Missing blocks are the structure voids.
+    fn from_structure(root: &NbtCompound) -> Result<Self, SchematicError> {
+        let size =
+            int_list(root.get("size")).ok_or(SchematicError::MalformedSchematic("missing size"))?;
+        let [width, height, length] = size.map(|value| value.max(0) as usize);
+
+        // structures with several palettes pick one randomly, but the first
+        // one is fine here
+        let palette = root
+            .get("palette")
+            .or_else(|| {
+                root.get("palettes")
+                    .and_then(|palettes| palettes.as_list()?.first())
+            })
+            .and_then(NbtTag::as_list)
+            .ok_or(SchematicError::MalformedSchematic("missing palette"))?;
+        let states: Vec<Option<BlockState>> = palette
+            .iter()
+            .filter_map(NbtTag::as_compound)
+            .map(|entry| {
+                let name = entry.get("Name").and_then(NbtTag::as_str).unwrap_or("air");
+                let properties: Vec<(&str, &str)> = entry
+                    .get("Properties")
+                    .and_then(NbtTag::as_compound)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|(key, value)| Some((key.as_str(), value.as_str()?)))
+                    .collect();
+                palette_state(name, &properties)
+            })
+            .collect();
+
+        let mut schematic = Self {
+            width,
+            height,
+            length,
+            blocks: vec![None; width * height * length],
+            block_entities: vec![],
+        };
+
+        let blocks = root
+            .get("blocks")
+            .and_then(NbtTag::as_list)
+            .ok_or(SchematicError::MalformedSchematic("missing blocks"))?;
+        for block in blocks.iter().filter_map(NbtTag::as_compound) {
+            let [x, y, z] = int_list(block.get("pos"))
+                .ok_or(SchematicError::MalformedSchematic("missing block position"))?;
+            let position = BlockPos::new(x, y, z);
+            let index = schematic
+                .index(position)
+                .ok_or(SchematicError::MalformedSchematic(
+                    "block outside of the size",
+                ))?;
+            let state = block
+                .get("state")
+                .and_then(NbtTag::as_i64)
+                .and_then(|state| states.get(usize::try_from(state).ok()?))
+                .ok_or(SchematicError::MalformedSchematic("invalid palette index"))?;
+            schematic.blocks[index] = *state;
+
+            if let Some(mut data) = block.get("nbt").and_then(NbtTag::as_compound).cloned()
+                && let Some(NbtTag::String(id)) = data.remove("id")
+            {
+                schematic
+                    .block_entities
+                    .push(SchematicBlockEntity { position, id, data });
+            }
+        }
+
+        Ok(schematic)
+    }
+
+    /// Returns the index of the block at the provided coordinates within the
+    /// schematic, if they're inside of it.
+    fn index(&self, position: BlockPos) -> Option<usize> {
+        let x = usize::try_from(position.x)
+            .ok()
+            .filter(|&x| x < self.width)?;
+        let y = usize::try_from(position.y)
+            .ok()
+            .filter(|&y| y < self.height)?;
+        let z = usize::try_from(position.z)
+            .ok()
+            .filter(|&z| z < self.length)?;
+        Some(x + z * self.width + y * self.width * self.length)
+    }
+
+    /// Returns the block at the provided coordinates within the schematic.
+    pub fn get_block(&self, position: BlockPos) -> Option<BlockState> {
+        self.blocks[self.index(position)?]
+    }
+
+    /// Pastes the schematic into the world, with its first corner at the
+    /// origin. The schematic is mirrored and rotated around the origin.
+    /// Returns the amount of the changed blocks.
+    pub fn paste(
+        &self,
+        world: &World,
+        origin: BlockPos,
+        rotation: Rotation,
+        mirror: Mirror,
+    ) -> usize {
+        let transform = |position: BlockPos| {
+            let (x, z) = mirror.mirror(position.x, position.z);
+            let (x, z) = rotation.rotate(x, z);
+            origin.offset(x, position.y, z)
+        };
+
+        let mut blocks = Vec::with_capacity(self.blocks.len());
+        for y in 0..self.height {
+            for z in 0..self.length {
+                for x in 0..self.width {
+                    let position = BlockPos::new(x as i32, y as i32, z as i32);
+                    if let Some(state) = self.get_block(position) {
+                        blocks.push((
+                            transform(position),
+                            transform_state(state, rotation, mirror),
+                        ));
+                    }
+                }
+            }
+        }
+        let changed = world.set_blocks(blocks);
+
+        for block_entity in &self.block_entities {
+            world.set_block_entity(
+                transform(block_entity.position),
+                &block_entity.id,
+                block_entity.data.clone(),
+            );
+        }
+        changed
+    }
+}
+
+/// Reads the list of three integers, i.e. a position in a structure.
+fn int_list(tag: Option<&NbtTag>) -> Option<[i32; 3]> {
+    let values: Vec<i32> = tag?
+        .as_list()?
+        .iter()
+        .map(|value| value.as_i64().map(|value| value as i32))
+        .collect::<Option<_>>()?;
+    values.try_into().ok()
+}
+
+/// Reads the block entity of a Sponge schematic. Version 3 keeps its data in
+/// the `Data` compound, while the older ones keep it next to the position.
+fn sponge_block_entity(compound: &NbtCompound) -> Option<SchematicBlockEntity> {
+    let [x, y, z] = compound.get("Pos")?.as_int_array()?.try_into().ok()?;
+    let id = compound.get("Id")?.as_str()?.to_string();
+    let data = match compound.get("Data").and_then(NbtTag::as_compound) {
+        Some(data) => data.clone(),
+        None => compound
+            .iter()
+            .filter(|(key, _)| !matches!(key.as_str(), "Pos" | "Id"))
+            .map(|(key, tag)| (key.clone(), tag.clone()))
+            .collect(),
+    };
+    Some(SchematicBlockEntity {
+        position: BlockPos::new(x, y, z),
+        id,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(value: &str) -> NbtTag {
+        NbtTag::String(value.to_string())
+    }
+
+    #[test]
+    fn test_sponge_schematic() {
+        // 2x1x2 with stairs in the first corner and a sign in the last one
+        let palette = NbtCompound::from([
+            (String::from("minecraft:air"), NbtTag::Int(0)),
+            (
+                String::from("minecraft:oak_stairs[facing=north,half=bottom]"),
+                NbtTag::Int(1),
+            ),
+            (
+                String::from("minecraft:oak_sign[rotation=4]"),
+                NbtTag::Int(2),
+            ),
+        ]);
+        let sign = NbtCompound::from([
+            (String::from("Pos"), NbtTag::IntArray(vec![1, 0, 1])),
+            (String::from("Id"), string("minecraft:sign")),
+            (
+                String::from("Data"),
+                NbtTag::Compound(NbtCompound::from([(
+                    String::from("is_waxed"),
+                    NbtTag::Byte(1),
+                )])),
+            ),
+        ]);
+        let blocks = NbtCompound::from([
+            (String::from("Palette"), NbtTag::Compound(palette)),
+            (String::from("Data"), NbtTag::ByteArray(vec![1, 0, 0, 2])),
+            (
+                String::from("BlockEntities"),
+                NbtTag::List(vec![NbtTag::Compound(sign)]),
+            ),
+        ]);
+        let schematic = NbtCompound::from([
+            (String::from("Version"), NbtTag::Int(3)),
+            (String::from("Width"), NbtTag::Short(2)),
+            (String::from("Height"), NbtTag::Short(1)),
+            (String::from("Length"), NbtTag::Short(2)),
+            (String::from("Blocks"), NbtTag::Compound(blocks)),
+        ]);
+        let root = NbtCompound::from([(String::from("Schematic"), NbtTag::Compound(schematic))]);
+
+        let schematic = Schematic::from_nbt(&root).unwrap();
+        let stairs = schematic.get_block(BlockPos::new(0, 0, 0)).unwrap();
+        assert_eq!(stairs.name(), "minecraft:oak_stairs");
+        assert_eq!(
+            schematic.get_block(BlockPos::new(1, 0, 0)),
+            Some(BlockState::AIR)
+        );
+        assert_eq!(schematic.block_entities[0].position, BlockPos::new(1, 0, 1));
+        assert_eq!(schematic.block_entities[0].data.len(), 1);
+
+        let rotated = transform_state(stairs, Rotation::Clockwise90, Mirror::None);
+        assert_eq!(rotated.get("facing"), Some("east"));
+        let mirrored = transform_state(stairs, Rotation::Clockwise90, Mirror::LeftRight);
+        assert_eq!(mirrored.get("facing"), Some("west"));
+
+        let sign = schematic.get_block(BlockPos::new(1, 0, 1)).unwrap();
+        let rotated = transform_state(sign, Rotation::Counterclockwise90, Mirror::FrontBack);
+        assert_eq!(rotated.get("rotation"), Some("8"));
+    }
+
+    #[test]
+    fn test_structure() {
+        let state = |name: &str, properties: &[(&str, &str)]| {
+            NbtTag::Compound(NbtCompound::from([
+                (String::from("Name"), string(name)),
+                (
+                    String::from("Properties"),
+                    NbtTag::Compound(
+                        properties
+                            .iter()
+                            .map(|(key, value)| (key.to_string(), string(value)))
+                            .collect(),
+                    ),
+                ),
+            ]))
+        };
+        let block = |pos: [i32; 3], state: i32| {
+            NbtTag::Compound(NbtCompound::from([
+                (
+                    String::from("pos"),
+                    NbtTag::List(pos.into_iter().map(NbtTag::Int).collect()),
+                ),
+                (String::from("state"), NbtTag::Int(state)),
+            ]))
+        };
+        let root = NbtCompound::from([
+            (
+                String::from("size"),
+                NbtTag::List(vec![NbtTag::Int(1), NbtTag::Int(2), NbtTag::Int(1)]),
+            ),
+            (
+                String::from("palette"),
+                NbtTag::List(vec![
+                    state("minecraft:oak_log", &[("axis", "x")]),
+                    state("minecraft:chest", &[("facing", "west"), ("type", "left")]),
+                    state("minecraft:structure_void", &[]),
+                ]),
+            ),
+            (
+                String::from("blocks"),
+                NbtTag::List(vec![block([0, 0, 0], 0), block([0, 1, 0], 2)]),
+            ),
+        ]);
+
+        let schematic = Schematic::from_nbt(&root).unwrap();
+        let log = schematic.get_block(BlockPos::new(0, 0, 0)).unwrap();
+        let rotated = transform_state(log, Rotation::Clockwise90, Mirror::None);
+        assert_eq!(rotated.get("axis"), Some("z"));
+
+        assert_eq!(schematic.get_block(BlockPos::new(0, 1, 0)), None);
+
+        let chest =
+            BlockState::with_properties("chest", &[("facing", "west"), ("type", "left")]).unwrap();
+        let mirrored = transform_state(chest, Rotation::Clockwise180, Mirror::FrontBack);
+        assert_eq!(mirrored.get("facing"), Some("west"));
+        assert_eq!(mirrored.get("type"), Some("right"));
+    }
+}
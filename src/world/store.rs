@@ -0,0 +1,920 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    iter,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        Arc, Condvar, Mutex, MutexGuard,
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
+};
+
+use bytes::Bytes;
+
+use crate::{
+    network::PacketWriter,
+    protocol::{Writeable, packets::play::ClientboundChunkDataAndLightPacket},
+    world::{
+        Chunk,
+        anvil::{AnvilError, StoredChunk},
+        block::BlockState,
+        generator::WorldHeight,
+        light::{LightEngine, update_reach},
+    },
+};
+
+/// The largest amount of the changed blocks in a chunk for which the light is
+/// updated block by block. The whole chunk is lit again for more changes.
+const MAX_LIGHT_UPDATES: usize = 64;
+
+/// Produces the chunks that aren't in the store and keeps the changed ones,
+/// i.e. by loading them from the world files or generating them.
+pub trait ChunkSource: Send + Sync {
+    /// Loads or generates the chunk at the provided chunk coordinates.
+    fn load(&self, chunk_x: i32, chunk_z: i32) -> StoredChunk;
+
+    /// Saves the changed chunk, so it can be loaded again.
+    fn save(&self, chunk: &Chunk) -> Result<(), AnvilError>;
+
+    /// Returns the height of the world the chunks belong to.
+    fn height(&self) -> WorldHeight;
+}
+
+/// A chunk kept in the store.
+struct CachedChunk {
+    /// The chunk, which is locked only by the ones that hold it, so it's
+    /// changed, lit and encoded without locking the store.
+    chunk: Arc<Mutex<Chunk>>,
+    /// Encoded chunk data and light packet of the chunk, which is reused
+    /// until the chunk is changed.
+    packet: Option<Bytes>,
+    /// Clients that have the chunk loaded.
+    viewers: Vec<Arc<PacketWriter>>,
+    /// Amount of the ones that hold the chunk, which keep it in the store
+    /// even if no one views it.
+    holds: usize,
+    /// Changed each time the chunk is changed, so a packet encoded from an
+    /// older version of it isn't used.
+    revision: u64,
+    /// Whether the chunk was released while it was held, so it's dropped
+    /// once no one holds it if no one views it.
+    is_released: bool,
+}
+
+#[derive(Default)]
+struct StoreState {
+    chunks: HashMap<(i32, i32), CachedChunk>,
+    /// Changed chunks that were dropped from the store, but weren't saved
+    /// yet. They're stored again if they're requested before that.
+    unsaved: HashMap<(i32, i32), Arc<Mutex<Chunk>>>,
+    /// Chunks that are being loaded by the workers.
+    loading: HashSet<(i32, i32)>,
+    /// Chunks that are being saved. They aren't loaded again until they're
+    /// saved, and only one copy of each one is saved at the same time.
+    saving: HashSet<(i32, i32)>,
+    /// Chunks that are kept even if no one views them.
+    pinned: HashSet<(i32, i32)>,
+    /// Chunks whose light was changed by the chunks loaded next to them, and
+    /// wasn't sent to their viewers yet.
+    lit: HashSet<(i32, i32)>,
+    /// The last revision given to a changed chunk.
+    revision: u64,
+}
+
+impl StoreState {
+    /// Adds the chunk to the store.
+    fn insert(&mut self, position: (i32, i32), chunk: Arc<Mutex<Chunk>>) {
+        self.revision += 1;
+        self.chunks.insert(
+            position,
+            CachedChunk {
+                chunk,
+                packet: None,
+                viewers: vec![],
+                holds: 0,
+                revision: self.revision,
+                is_released: false,
+            },
+        );
+    }
+
+    /// Adds the chunk produced by a worker to the store.
+    fn insert_loaded(&mut self, chunk: Chunk) {
+        let position = (chunk.x, chunk.z);
+        self.loading.remove(&position);
+        self.insert(position, Arc::new(Mutex::new(chunk)));
+    }
+
+    /// Unholds the chunk, dropping its cached packet if it was changed. The
+    /// chunk is dropped if it was released meanwhile.
+    fn unhold(&mut self, position: (i32, i32), is_changed: bool) {
+        let cached = self.chunks.get_mut(&position).unwrap();
+        cached.holds -= 1;
+        if is_changed {
+            self.revision += 1;
+            cached.revision = self.revision;
+            cached.packet = None;
+        }
+        if cached.holds == 0 && cached.is_released {
+            self.evict_unviewed(position);
+        }
+    }
+
+    /// Drops the chunk if no one views it, or once no one holds it. A changed
+    /// chunk is kept among the unsaved ones until it's saved.
+    fn evict_unviewed(&mut self, position: (i32, i32)) {
+        let Some(cached) = self.chunks.get_mut(&position) else {
+            return;
+        };
+        if !cached.viewers.is_empty() || self.pinned.contains(&position) {
+            return;
+        }
+        if cached.holds > 0 {
+            cached.is_released = true;
+            return;
+        }
+
+        let cached = self.chunks.remove(&position).unwrap();
+        // no one holds the chunk, so no one has it locked, unless a change of
+        // it has panicked, in which case it isn't saved
+        if cached.chunk.lock().is_ok_and(|chunk| chunk.is_dirty) {
+            self.unsaved.insert(position, cached.chunk);
+        }
+    }
+}
+
+/// A chunk held in the store, which keeps it stored until it's dropped. Only
+/// the held chunks are locked.
+struct HeldChunk<'a> {
+    store: &'a Mutex<StoreState>,
+    position: (i32, i32),
+    chunk: Arc<Mutex<Chunk>>,
+    /// Whether the chunk was changed, so its cached packet is dropped.
+    is_changed: bool,
+}
+
+impl<'a> HeldChunk<'a> {
+    /// Holds the chunk, if it's stored. The state is the one of the locked
+    /// store, which mustn't be locked anymore when the chunk is dropped.
+    fn new(
+        store: &'a Mutex<StoreState>,
+        state: &mut StoreState,
+        position: (i32, i32),
+    ) -> Option<Self> {
+        let cached = state.chunks.get_mut(&position)?;
+        cached.holds += 1;
+        Some(Self {
+            store,
+            position,
+            chunk: cached.chunk.clone(),
+            is_changed: false,
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Chunk> {
+        self.chunk.lock().unwrap()
+    }
+}
+
+impl Drop for HeldChunk<'_> {
+    fn drop(&mut self) {
+        // the store is poisoned only if it has panicked while it was locked
+        if let Ok(mut state) = self.store.lock() {
+            state.unhold(self.position, self.is_changed);
+        }
+    }
+}
+
+/// Cache of the chunks of a world, shared between all clients. Missing
+/// chunks are produced by the source on a pool of workers, and each chunk is
+/// produced only once even if several clients request it at the same time.
+/// Chunks are kept while any client views them.
+///
+/// The store is locked only briefly, and each chunk has its own lock for
+/// changing, lighting and encoding it. The chunks are locked before the
+/// store, and several ones in the order of their positions.
+pub struct ChunkStore {
+    source: Arc<dyn ChunkSource>,
+    state: Arc<Mutex<StoreState>>,
+    /// Notified each time a chunk is added to the store or is saved.
+    changed: Arc<Condvar>,
+    jobs: Mutex<Sender<(i32, i32)>>,
+}
+
+impl ChunkStore {
+    /// Creates the store with the provided amount of workers, which produce
+    /// the chunks from the source.
+    pub fn new(source: Arc<dyn ChunkSource>, worker_count: usize) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let state = Arc::new(Mutex::new(StoreState::default()));
+        let changed = Arc::new(Condvar::new());
+
+        for _ in 0..worker_count.max(1) {
+            let source = source.clone();
+            let receiver = receiver.clone();
+            let state = state.clone();
+            let changed = changed.clone();
+            thread::spawn(move || run_worker(source, receiver, state, changed));
+        }
+
+        Self {
+            source,
+            state,
+            changed,
+            jobs: Mutex::new(sender),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, StoreState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Queues the chunks that are neither stored nor being loaded. Unsaved
+    /// chunks are stored again instead, and chunks that are being saved are
+    /// queued once they're saved.
+    fn request(&self, state: &mut StoreState, positions: &[(i32, i32)]) {
+        let jobs = self.jobs.lock().unwrap();
+        for &position in positions {
+            if state.chunks.contains_key(&position) {
+                continue;
+            }
+            if let Some(chunk) = state.unsaved.remove(&position) {
+                state.insert(position, chunk);
+            } else if !state.saving.contains(&position) && state.loading.insert(position) {
+                jobs.send(position).unwrap();
+            }
+        }
+    }
+
+    /// Requests the chunks and waits until all of them are loaded.
+    fn wait_for(&self, positions: &[(i32, i32)]) -> MutexGuard<'_, StoreState> {
+        let mut state = self.lock();
+        loop {
+            self.request(&mut state, positions);
+            if positions
+                .iter()
+                .all(|position| state.chunks.contains_key(position))
+            {
+                return state;
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    /// Waits for the chunk to be loaded and holds it.
+    fn hold(&self, position: (i32, i32)) -> HeldChunk<'_> {
+        let mut state = self.wait_for(&[position]);
+        HeldChunk::new(&self.state, &mut state, position).unwrap()
+    }
+
+    /// Waits for the chunks to be loaded, registers the viewer for each of
+    /// them, so they're kept until it's released, and writes the chunk data
+    /// and light packets of the chunks to it in the same order. The packets
+    /// are encoded without locking the store, but they're queued together
+    /// with registering the viewer, so they always precede the later changes
+    /// of the chunks.
+    pub fn acquire(&self, positions: &[(i32, i32)], viewer: &Arc<PacketWriter>) {
+        let min_y = self.source.height().min_y;
+        let held: Vec<HeldChunk> = {
+            let mut state = self.wait_for(positions);
+            positions
+                .iter()
+                .map(|&position| HeldChunk::new(&self.state, &mut state, position).unwrap())
+                .collect()
+        };
+
+        for held in held {
+            loop {
+                let (packet, revision) = {
+                    let state = self.lock();
+                    let cached = &state.chunks[&held.position];
+                    (cached.packet.clone(), cached.revision)
+                };
+                let packet = packet.unwrap_or_else(|| encode_chunk_packet(&held.lock(), min_y));
+
+                // the chunk is encoded again if it was changed meanwhile
+                let mut state = self.lock();
+                let cached = state.chunks.get_mut(&held.position).unwrap();
+                if cached.revision != revision {
+                    continue;
+                }
+                cached.packet = Some(packet.clone());
+                cached.viewers.push(viewer.clone());
+                // failures close the viewer, which releases the chunks
+                let _ =
+                    viewer.write_raw_packet(ClientboundChunkDataAndLightPacket::PACKET_ID, &packet);
+                break;
+            }
+        }
+    }
+
+    /// Unregisters the viewer of each of the chunks. Chunks that no one
+    /// views anymore are dropped from the store, and the changed ones are
+    /// kept until they're saved with `take_evicted` or `take_dirty`.
+    pub fn release(&self, positions: &[(i32, i32)], viewer: &Arc<PacketWriter>) {
+        let mut state = self.lock();
+        for position in positions {
+            let Some(cached) = state.chunks.get_mut(position) else {
+                continue;
+            };
+            if let Some(index) = cached
+                .viewers
+                .iter()
+                .position(|other| Arc::ptr_eq(other, viewer))
+            {
+                cached.viewers.swap_remove(index);
+            }
+            state.evict_unviewed(*position);
+        }
+    }
+
+    /// Returns the clients that view the chunk.
+    pub fn viewers(&self, chunk_x: i32, chunk_z: i32) -> Vec<Arc<PacketWriter>> {
+        self.lock()
+            .chunks
+            .get(&(chunk_x, chunk_z))
+            .map_or_else(Vec::new, |cached| cached.viewers.clone())
+    }
+
+    /// Returns the clients that view any of the chunks, each one once.
+    pub fn all_viewers(&self) -> Vec<Arc<PacketWriter>> {
+        let state = self.lock();
+        let mut viewers: Vec<Arc<PacketWriter>> = vec![];
+        for viewer in state.chunks.values().flat_map(|cached| &cached.viewers) {
+            if !viewers.iter().any(|other| Arc::ptr_eq(other, viewer)) {
+                viewers.push(viewer.clone());
+            }
+        }
+        viewers
+    }
+
+    /// Changes the blocks of the chunk at the provided coordinates within it,
+    /// with Y counted from the bottom of the world. Heightmaps of the chunk
+    /// are updated, and the light of it and of the neighbours the changed
+    /// light can reach. The chunk is loaded if it isn't, and dropped again
+    /// afterwards if no one views it. Returns the loaded chunks whose light
+    /// was changed.
+    pub fn set_blocks(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        blocks: &[(usize, usize, usize, BlockState)],
+    ) -> Vec<(i32, i32)> {
+        let position = (chunk_x, chunk_z);
+        let mut held = BTreeMap::from([(position, self.hold(position))]);
+        held.get_mut(&position).unwrap().is_changed = true;
+        {
+            let mut chunk = held[&position].lock();
+            for &(x, y, z, block_state) in blocks {
+                chunk.set_block_at(x, y, z, block_state);
+                // the block entity belonged to the replaced block
+                chunk.remove_block_entity(x, y, z);
+            }
+        }
+
+        // the neighbours are held as long as the light turns out to reach
+        // more of them, and they're locked again in the order of positions
+        let mut requested = HashSet::from([position]);
+        let mut lit = loop {
+            let mut chunks: Vec<MutexGuard<Chunk>> = held.values().map(HeldChunk::lock).collect();
+            let chunk = chunks.iter().find(|chunk| (chunk.x, chunk.z) == position);
+            let missing: Vec<(i32, i32)> = reached_neighbours(chunk.unwrap(), blocks)
+                .into_iter()
+                .filter(|neighbour| !requested.contains(neighbour))
+                .collect();
+
+            if missing.is_empty() {
+                let mut engine = LightEngine::new(chunks.iter_mut().map(|chunk| &mut **chunk));
+                if blocks.len() > MAX_LIGHT_UPDATES {
+                    engine.light_chunk(chunk_x, chunk_z);
+                } else {
+                    for &(x, y, z, _) in blocks {
+                        engine.update_block(chunk_x * 16 + x as i32, y, chunk_z * 16 + z as i32);
+                    }
+                }
+                break engine
+                    .changed_chunks()
+                    .iter()
+                    .copied()
+                    .collect::<Vec<(i32, i32)>>();
+            }
+
+            drop(chunks);
+            let mut state = self.lock();
+            for neighbour in missing {
+                requested.insert(neighbour);
+                if let Some(chunk) = HeldChunk::new(&self.state, &mut state, neighbour) {
+                    held.insert(neighbour, chunk);
+                }
+            }
+        };
+
+        for position in &lit {
+            held.get_mut(position).unwrap().is_changed = true;
+        }
+        drop(held);
+        let mut state = self.lock();
+        state.evict_unviewed(position);
+        lit.retain(|position| state.chunks.contains_key(position));
+        lit.sort();
+        lit
+    }
+
+    /// Keeps the chunks loaded even if no one views them, i.e. the ones that
+    /// were changed in a world whose chunks can't be saved.
+    pub fn pin(&self, positions: &[(i32, i32)]) {
+        self.lock().pinned.extend(positions);
+    }
+
+    /// Calls the function with the chunk, loading it first if it isn't. The
+    /// cached packet of the chunk is dropped, and the chunk is dropped from
+    /// the store afterwards if no one views it.
+    pub fn update_chunk<R>(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        function: impl FnOnce(&mut Chunk) -> R,
+    ) -> R {
+        let mut held = self.hold((chunk_x, chunk_z));
+        held.is_changed = true;
+        let result = function(&mut held.lock());
+        drop(held);
+        self.lock().evict_unviewed((chunk_x, chunk_z));
+        result
+    }
+
+    /// Calls the function with the stored chunk, if it's loaded. The cached
+    /// packet of the chunk is dropped, as the chunk may be changed.
+    pub fn with_chunk_mut<R>(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        function: impl FnOnce(&mut Chunk) -> R,
+    ) -> Option<R> {
+        let mut held = HeldChunk::new(&self.state, &mut self.lock(), (chunk_x, chunk_z))?;
+        held.is_changed = true;
+        let result = function(&mut held.lock());
+        Some(result)
+    }
+
+    /// Calls the function with the stored chunk, if it's loaded.
+    pub fn with_chunk<R>(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        function: impl FnOnce(&Chunk) -> R,
+    ) -> Option<R> {
+        let held = HeldChunk::new(&self.state, &mut self.lock(), (chunk_x, chunk_z))?;
+        let result = function(&held.lock());
+        Some(result)
+    }
+
+    /// Returns whether the chunk is loaded.
+    pub fn is_loaded(&self, chunk_x: i32, chunk_z: i32) -> bool {
+        self.lock().chunks.contains_key(&(chunk_x, chunk_z))
+    }
+
+    /// Returns the amount of loaded chunks.
+    pub fn len(&self) -> usize {
+        self.lock().chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the chunks whose light was changed by the chunks loaded next
+    /// to them since the previous call, so the light can be sent to their
+    /// viewers.
+    pub fn take_lit(&self) -> Vec<(i32, i32)> {
+        let mut state = self.lock();
+        let state = &mut *state;
+        let mut lit: Vec<(i32, i32)> = state
+            .lit
+            .drain()
+            .filter(|position| state.chunks.contains_key(position))
+            .collect();
+        lit.sort();
+        lit
+    }
+
+    /// Copies the changed chunks that were dropped from the store and aren't
+    /// being saved already, and marks them as saved. The copies have to be
+    /// passed to `save_chunks`, which can be done without blocking the store.
+    pub fn take_evicted(&self) -> Vec<Chunk> {
+        let evicted: Vec<Arc<Mutex<Chunk>>> = {
+            let mut state = self.lock();
+            let state = &mut *state;
+            let positions: Vec<(i32, i32)> = state
+                .unsaved
+                .keys()
+                .copied()
+                .filter(|position| !state.saving.contains(position))
+                .collect();
+            positions
+                .into_iter()
+                .map(|position| {
+                    state.saving.insert(position);
+                    state.unsaved.remove(&position).unwrap()
+                })
+                .collect()
+        };
+        evicted
+            .iter()
+            .map(|chunk| chunk.lock().unwrap().clone())
+            .collect()
+    }
+
+    /// Copies the changed chunks that aren't being saved already, including
+    /// the ones that were dropped from the store, and marks them as saved.
+    /// The copies have to be passed to `save_chunks`, which can be done
+    /// without blocking the store.
+    pub fn take_dirty(&self) -> Vec<Chunk> {
+        let mut dirty = self.take_evicted();
+        let held: Vec<HeldChunk> = {
+            let mut state = self.lock();
+            let positions: Vec<(i32, i32)> = state.chunks.keys().copied().collect();
+            positions
+                .into_iter()
+                .map(|position| HeldChunk::new(&self.state, &mut state, position).unwrap())
+                .collect()
+        };
+
+        for held in &held {
+            let mut chunk = held.lock();
+            if chunk.is_dirty && self.lock().saving.insert(held.position) {
+                dirty.push(chunk.clone());
+                chunk.is_dirty = false;
+            }
+        }
+        dirty
+    }
+
+    /// Saves the chunks copied by `take_dirty` or `take_evicted`. The ones
+    /// that couldn't be saved are marked as changed again, or kept among the
+    /// unsaved ones if they were dropped. Returns the amount of saved chunks,
+    /// or the first error.
+    pub fn save_chunks(&self, chunks: Vec<Chunk>) -> Result<usize, AnvilError> {
+        let mut saved = 0;
+        let mut error = None;
+        for chunk in chunks {
+            let position = (chunk.x, chunk.z);
+            let result = self.source.save(&chunk);
+
+            let mut state = self.lock();
+            state.saving.remove(&position);
+            let held = match result {
+                Ok(()) => {
+                    saved += 1;
+                    None
+                }
+                Err(e) => {
+                    error.get_or_insert(e);
+                    let held = HeldChunk::new(&self.state, &mut state, position);
+                    if held.is_none() {
+                        // a newer dropped copy is saved instead
+                        state
+                            .unsaved
+                            .entry(position)
+                            .or_insert_with(|| Arc::new(Mutex::new(chunk)));
+                    }
+                    held
+                }
+            };
+            drop(state);
+            if let Some(held) = held {
+                held.lock().is_dirty = true;
+            }
+            self.changed.notify_all();
+        }
+        error.map_or(Ok(saved), Err)
+    }
+
+    /// Saves all changed chunks, after the ones that are being saved
+    /// already. Returns the amount of saved chunks.
+    pub fn save_all(&self) -> Result<usize, AnvilError> {
+        let mut state = self.lock();
+        while !state.saving.is_empty() {
+            state = self.changed.wait(state).unwrap();
+        }
+        drop(state);
+        self.save_chunks(self.take_dirty())
+    }
+}
+
+/// Returns whether the chunk is within one chunk from the other one.
+fn is_neighbour((x, z): (i32, i32), chunk_x: i32, chunk_z: i32) -> bool {
+    (x - chunk_x).abs() <= 1 && (z - chunk_z).abs() <= 1
+}
+
+/// Returns the neighbours of the chunk that the light changed by the changed
+/// blocks of it can reach. All of them are reached if the whole chunk is lit
+/// again.
+fn reached_neighbours(
+    chunk: &Chunk,
+    blocks: &[(usize, usize, usize, BlockState)],
+) -> Vec<(i32, i32)> {
+    let reaches: Vec<(usize, usize, usize)> = blocks
+        .iter()
+        .map(|&(x, y, z, _)| (x, z, update_reach(chunk, x, y, z) as usize))
+        .collect();
+
+    let mut neighbours = vec![];
+    for dx in -1..=1 {
+        for dz in -1..=1 {
+            let is_reached = blocks.len() > MAX_LIGHT_UPDATES
+                || reaches
+                    .iter()
+                    .any(|&(x, z, reach)| border_distance(dx, x) + border_distance(dz, z) <= reach);
+            if (dx, dz) != (0, 0) && is_reached {
+                neighbours.push((chunk.x + dx, chunk.z + dz));
+            }
+        }
+    }
+    neighbours
+}
+
+/// Returns the distance from the coordinate within the chunk to the first
+/// block of the neighbour in the provided direction, or zero for the chunk
+/// itself.
+fn border_distance(direction: i32, coordinate: usize) -> usize {
+    match direction {
+        -1 => coordinate + 1,
+        1 => 16 - coordinate,
+        _ => 0,
+    }
+}
+
+/// Encodes the chunk data and light packet of the chunk without its ID.
+fn encode_chunk_packet(chunk: &Chunk, min_y: i32) -> Bytes {
+    ClientboundChunkDataAndLightPacket::from_chunk(chunk, min_y)
+        .write()
+        .expect("chunk packets are always encodable")
+}
+
+/// Produces the requested chunks until the store is dropped. A chunk whose
+/// production panicked is replaced with an empty one that is never saved, so
+/// no one waits for it forever.
+fn run_worker(
+    source: Arc<dyn ChunkSource>,
+    receiver: Arc<Mutex<Receiver<(i32, i32)>>>,
+    state: Arc<Mutex<StoreState>>,
+    changed: Arc<Condvar>,
+) {
+    loop {
+        let job = receiver.lock().unwrap().recv();
+        let Ok((chunk_x, chunk_z)) = job else {
+            return;
+        };
+
+        let produced = panic::catch_unwind(AssertUnwindSafe(|| {
+            produce_chunk(&*source, &state, chunk_x, chunk_z)
+        }));
+        if produced.is_err() {
+            eprintln!("Failed to produce chunk ({chunk_x}, {chunk_z}), it's left empty");
+            let mut chunk = source.height().empty_chunk(chunk_x, chunk_z);
+            chunk.is_lossy = true;
+            state.lock().unwrap().insert_loaded(chunk);
+        }
+        changed.notify_all();
+    }
+}
+
+/// Loads the chunk from the source and adds it to the store. The chunk is
+/// lit together with the stored neighbours, so the light crosses the borders
+/// between them, and the neighbours whose light was changed are marked as
+/// lit. The store is unlocked while the chunks are lit.
+fn produce_chunk(source: &dyn ChunkSource, store: &Mutex<StoreState>, chunk_x: i32, chunk_z: i32) {
+    let stored = source.load(chunk_x, chunk_z);
+    let mut chunk = stored.chunk;
+    if stored.is_lit {
+        store.lock().unwrap().insert_loaded(chunk);
+        return;
+    }
+
+    let mut held = BTreeMap::new();
+    let mut state = store.lock().unwrap();
+    loop {
+        let neighbours: Vec<(i32, i32)> = state
+            .chunks
+            .keys()
+            .copied()
+            .filter(|&position| is_neighbour(position, chunk_x, chunk_z))
+            .collect();
+        for neighbour in neighbours {
+            held.entry(neighbour)
+                .or_insert_with(|| HeldChunk::new(store, &mut state, neighbour).unwrap());
+        }
+        drop(state);
+
+        let mut neighbours: Vec<MutexGuard<Chunk>> = held.values().map(HeldChunk::lock).collect();
+        let chunks = iter::once(&mut chunk).chain(neighbours.iter_mut().map(|chunk| &mut **chunk));
+        let mut engine = LightEngine::new(chunks);
+        engine.light_chunk(chunk_x, chunk_z);
+        let lit: Vec<(i32, i32)> = engine
+            .changed_chunks()
+            .iter()
+            .copied()
+            .filter(|&position| position != (chunk_x, chunk_z))
+            .collect();
+
+        // the chunks loaded meanwhile are lit with it too, and the neighbours
+        // are locked until it's added, so they're lit with it from then on
+        state = store.lock().unwrap();
+        let is_complete = state.chunks.keys().all(|&position| {
+            !is_neighbour(position, chunk_x, chunk_z) || held.contains_key(&position)
+        });
+        if is_complete {
+            state.lit.extend(&lit);
+            state.insert_loaded(chunk);
+            drop(state);
+            drop(neighbours);
+            for position in &lit {
+                held.get_mut(position).unwrap().is_changed = true;
+            }
+            return;
+        }
+        drop(neighbours);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+    use crate::world::light::{ChunkLight, LightKind};
+
+    #[derive(Default)]
+    struct CountingSource {
+        loads: AtomicUsize,
+        saves: AtomicUsize,
+        /// Chunk whose loading panics.
+        broken: Option<(i32, i32)>,
+        /// Chunk that has a light source next to its border.
+        lamp: Option<(i32, i32)>,
+    }
+
+    impl ChunkSource for CountingSource {
+        fn load(&self, chunk_x: i32, chunk_z: i32) -> StoredChunk {
+            self.loads.fetch_add(1, Ordering::SeqCst);
+            if self.broken == Some((chunk_x, chunk_z)) {
+                panic!("chunk ({chunk_x}, {chunk_z}) is broken");
+            }
+            let mut chunk = Chunk::with_height(chunk_x, chunk_z, 64);
+            chunk.set_block_at(0, 0, 0, BlockState::new("stone").unwrap());
+            if self.lamp == Some((chunk_x, chunk_z)) {
+                chunk.set_block_at(0, 10, 3, BlockState::new("glowstone").unwrap());
+            }
+            chunk.light = ChunkLight::new(4, true);
+            StoredChunk {
+                chunk,
+                is_lit: false,
+            }
+        }
+
+        fn save(&self, _: &Chunk) -> Result<(), AnvilError> {
+            self.saves.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn height(&self) -> WorldHeight {
+            WorldHeight {
+                min_y: 0,
+                height: 64,
+            }
+        }
+    }
+
+    #[test]
+    fn test_concurrent_requests_load_once() {
+        let source = Arc::new(CountingSource::default());
+        let store = Arc::new(ChunkStore::new(source.clone(), 4));
+        let positions: Vec<(i32, i32)> = (0..4).flat_map(|x| (0..4).map(move |z| (x, z))).collect();
+        let viewers: Vec<Arc<PacketWriter>> = (0..8)
+            .map(|_| Arc::new(PacketWriter::new(io::sink())))
+            .collect();
+
+        let handles: Vec<_> = viewers
+            .iter()
+            .map(|viewer| {
+                let store = store.clone();
+                let positions = positions.clone();
+                let viewer = viewer.clone();
+                thread::spawn(move || store.acquire(&positions, &viewer))
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(source.loads.load(Ordering::SeqCst), 16);
+        assert_eq!(store.len(), 16);
+        assert_eq!(store.viewers(0, 0).len(), 8);
+
+        // all chunks were changed by the source, so they're kept until
+        // they're saved after eviction
+        for viewer in &viewers[1..] {
+            store.release(&positions, viewer);
+        }
+        assert_eq!(store.len(), 16);
+        store.release(&positions[..4], &viewers[0]);
+        assert_eq!(store.len(), 12);
+        assert_eq!(source.saves.load(Ordering::SeqCst), 0);
+        let evicted = store.take_evicted();
+        assert_eq!(evicted.len(), 4);
+        assert_eq!(store.save_chunks(evicted).unwrap(), 4);
+        assert_eq!(store.save_all().unwrap(), 12);
+        assert_eq!(store.save_all().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_set_block_in_unviewed_chunk() {
+        let source = Arc::new(CountingSource::default());
+        let store = ChunkStore::new(source.clone(), 1);
+        let viewer = Arc::new(PacketWriter::new(io::sink()));
+        store.acquire(&[(0, 0)], &viewer);
+
+        // the light of the viewed neighbour changes too
+        let glowstone = BlockState::new("glowstone").unwrap();
+        let lit = store.set_blocks(1, 0, &[(0, 10, 3, glowstone)]);
+        assert_eq!(lit, vec![(0, 0)]);
+        assert!(!store.is_loaded(1, 0));
+        assert_eq!(source.saves.load(Ordering::SeqCst), 0);
+        assert_eq!(
+            store.with_chunk(0, 0, |chunk| chunk.light.get(LightKind::Block, 15, 10, 3)),
+            Some(14)
+        );
+
+        // the unsaved chunk is stored again instead of being loaded
+        store.acquire(&[(1, 0)], &viewer);
+        assert_eq!(source.loads.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            store.with_chunk(1, 0, |chunk| chunk.get_block_at(0, 10, 3)),
+            Some(glowstone)
+        );
+        store.release(&[(1, 0)], &viewer);
+
+        store.pin(&[(2, 0)]);
+        store.set_blocks(2, 0, &[(0, 10, 3, glowstone)]);
+        assert!(store.is_loaded(2, 0));
+        assert_eq!(store.take_evicted().len(), 1);
+    }
+
+    #[test]
+    fn test_set_blocks_returns_changed_light() {
+        let source = Arc::new(CountingSource::default());
+        let store = ChunkStore::new(source.clone(), 1);
+        let viewer = Arc::new(PacketWriter::new(io::sink()));
+        store.acquire(&[(0, 0), (1, 0)], &viewer);
+
+        // the shadow of the block doesn't reach the neighbour
+        let lit = store.set_blocks(1, 0, &[(8, 0, 8, BlockState::new("stone").unwrap())]);
+        assert_eq!(lit, vec![(1, 0)]);
+        let lit = store.set_blocks(1, 0, &[(1, 10, 8, BlockState::new("glowstone").unwrap())]);
+        assert_eq!(lit, vec![(0, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn test_loaded_neighbour_is_lit() {
+        let source = Arc::new(CountingSource {
+            lamp: Some((1, 0)),
+            ..Default::default()
+        });
+        let store = ChunkStore::new(source.clone(), 1);
+        let viewer = Arc::new(PacketWriter::new(io::sink()));
+        store.acquire(&[(0, 0)], &viewer);
+        assert_eq!(store.take_lit(), vec![]);
+
+        store.acquire(&[(1, 0)], &viewer);
+        assert_eq!(store.take_lit(), vec![(0, 0)]);
+        assert_eq!(store.take_lit(), vec![]);
+        assert_eq!(
+            store.with_chunk(0, 0, |chunk| chunk.light.get(LightKind::Block, 15, 10, 3)),
+            Some(14)
+        );
+    }
+
+    #[test]
+    fn test_panicking_load_gives_empty_chunk() {
+        let source = Arc::new(CountingSource {
+            broken: Some((1, 0)),
+            ..Default::default()
+        });
+        let store = ChunkStore::new(source.clone(), 1);
+        let viewer = Arc::new(PacketWriter::new(io::sink()));
+        store.acquire(&[(0, 0), (1, 0)], &viewer);
+
+        // the worker survives the panic
+        assert!(store.with_chunk(1, 0, |chunk| chunk.is_lossy).unwrap());
+        store.acquire(&[(2, 0)], &viewer);
+        assert_eq!(
+            store.with_chunk(2, 0, |chunk| chunk.get_block_at(0, 0, 0)),
+            Some(BlockState::new("stone").unwrap())
+        );
+    }
+}
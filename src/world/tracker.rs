@@ -0,0 +1,215 @@
+use std::collections::{HashSet, VecDeque};
+
+/// The rate of the chunks the client is assumed to handle until it reports
+/// its own, the same as vanilla uses.
+const INITIAL_CHUNKS_PER_TICK: f32 = 9.0;
+
+/// Bounds of the chunk rate the client may request.
+const MIN_CHUNKS_PER_TICK: f32 = 0.01;
+const MAX_CHUNKS_PER_TICK: f32 = 64.0;
+
+/// Returns the chunks of the square with the provided center and radius in
+/// the spiral order: the center first, and then each ring around it.
+pub fn spiral(center: (i32, i32), radius: i32) -> impl Iterator<Item = (i32, i32)> {
+    let (center_x, center_z) = center;
+    std::iter::once(center).chain((1..=radius).flat_map(move |ring| {
+        let top = (-ring..=ring).map(move |dx| (dx, -ring));
+        let right = (-ring + 1..=ring).map(move |dz| (ring, dz));
+        let bottom = (-ring..ring).rev().map(move |dx| (dx, ring));
+        let left = (-ring + 1..ring).rev().map(move |dz| (-ring, dz));
+        top.chain(right)
+            .chain(bottom)
+            .chain(left)
+            .map(move |(dx, dz)| (center_x + dx, center_z + dz))
+    }))
+}
+
+/// Keeps track of the chunks a single client has loaded. Chunks around the
+/// center are sent in batches: the next batch is sent only after the client
+/// has acknowledged the previous one, and its size follows the rate the
+/// client reported.
+#[derive(Debug, Clone)]
+pub struct ChunkTracker {
+    /// The chunk the client is in, if it's known yet.
+    center: Option<(i32, i32)>,
+    /// Radius of the square of chunks around the center that are sent.
+    view_distance: i32,
+    /// Chunks that were sent to the client.
+    sent: HashSet<(i32, i32)>,
+    /// Chunks in the view distance which weren't sent yet, in the order
+    /// they should be sent.
+    pending: VecDeque<(i32, i32)>,
+    /// The rate of the chunks the client has requested.
+    chunks_per_tick: f32,
+    /// Whether the last sent batch wasn't acknowledged yet.
+    awaiting_batch: bool,
+}
+
+impl ChunkTracker {
+    pub fn new(view_distance: i32) -> Self {
+        Self {
+            center: None,
+            view_distance,
+            sent: HashSet::new(),
+            pending: VecDeque::new(),
+            chunks_per_tick: INITIAL_CHUNKS_PER_TICK,
+            awaiting_batch: false,
+        }
+    }
+
+    pub fn center(&self) -> Option<(i32, i32)> {
+        self.center
+    }
+
+    pub fn view_distance(&self) -> i32 {
+        self.view_distance
+    }
+
+    /// Returns whether the chunk was sent to the client.
+    pub fn is_sent(&self, chunk_x: i32, chunk_z: i32) -> bool {
+        self.sent.contains(&(chunk_x, chunk_z))
+    }
+
+    /// Returns all chunks that were sent to the client.
+    pub fn sent_chunks(&self) -> Vec<(i32, i32)> {
+        self.sent.iter().copied().collect()
+    }
+
+    /// Returns whether the chunk is within the view distance of the center.
+    pub fn is_in_view(&self, chunk_x: i32, chunk_z: i32) -> bool {
+        self.center.is_some_and(|(center_x, center_z)| {
+            (chunk_x - center_x).abs() <= self.view_distance
+                && (chunk_z - center_z).abs() <= self.view_distance
+        })
+    }
+
+    /// Moves the center to the provided chunk. Returns the sent chunks that
+    /// went out of the view distance and have to be unloaded by the client.
+    pub fn move_to(&mut self, chunk_x: i32, chunk_z: i32) -> Vec<(i32, i32)> {
+        if self.center == Some((chunk_x, chunk_z)) {
+            return vec![];
+        }
+        self.center = Some((chunk_x, chunk_z));
+        self.refresh()
+    }
+
+    /// Changes the view distance. Returns the sent chunks that went out of
+    /// it and have to be unloaded by the client.
+    pub fn set_view_distance(&mut self, view_distance: i32) -> Vec<(i32, i32)> {
+        if self.view_distance == view_distance {
+            return vec![];
+        }
+        self.view_distance = view_distance;
+        self.refresh()
+    }
+
+    /// Forgets all sent chunks, i.e. after the client has changed the world.
+    pub fn reset(&mut self) {
+        self.center = None;
+        self.sent.clear();
+        self.pending.clear();
+        self.awaiting_batch = false;
+    }
+
+    /// Drops the chunks that went out of the view and queues the new ones in
+    /// the spiral order.
+    fn refresh(&mut self) -> Vec<(i32, i32)> {
+        let mut unloaded: Vec<(i32, i32)> = self
+            .sent
+            .iter()
+            .copied()
+            .filter(|&(x, z)| !self.is_in_view(x, z))
+            .collect();
+        unloaded.sort_unstable();
+        for position in &unloaded {
+            self.sent.remove(position);
+        }
+
+        self.pending = match self.center {
+            Some(center) => spiral(center, self.view_distance)
+                .filter(|position| !self.sent.contains(position))
+                .collect(),
+            None => VecDeque::new(),
+        };
+        unloaded
+    }
+
+    /// Takes the chunks of the next batch and marks them as sent. Returns
+    /// nothing if the previous batch wasn't acknowledged yet or all chunks
+    /// were already sent.
+    pub fn next_batch(&mut self) -> Vec<(i32, i32)> {
+        if self.awaiting_batch || self.pending.is_empty() {
+            return vec![];
+        }
+
+        let size = (self.chunks_per_tick as usize)
+            .max(1)
+            .min(self.pending.len());
+        let batch: Vec<(i32, i32)> = self.pending.drain(..size).collect();
+        self.sent.extend(&batch);
+        self.awaiting_batch = true;
+        batch
+    }
+
+    /// Handles the acknowledgement of the batch with the rate the client
+    /// wants to receive the chunks at.
+    pub fn batch_received(&mut self, chunks_per_tick: f32) {
+        self.awaiting_batch = false;
+        self.chunks_per_tick = if chunks_per_tick.is_nan() {
+            MIN_CHUNKS_PER_TICK
+        } else {
+            chunks_per_tick.clamp(MIN_CHUNKS_PER_TICK, MAX_CHUNKS_PER_TICK)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spiral() {
+        let positions: Vec<(i32, i32)> = spiral((10, -3), 2).collect();
+        assert_eq!(positions.len(), 25);
+        assert_eq!(positions[0], (10, -3));
+        assert_eq!(positions[1], (9, -4));
+
+        let unique: HashSet<_> = positions.iter().collect();
+        assert_eq!(unique.len(), 25);
+
+        // each ring is further away than the previous one
+        let distances: Vec<i32> = positions
+            .iter()
+            .map(|(x, z)| (x - 10).abs().max((z + 3).abs()))
+            .collect();
+        assert!(distances.is_sorted());
+    }
+
+    #[test]
+    fn test_chunk_tracker() {
+        let mut tracker = ChunkTracker::new(2);
+        assert!(tracker.move_to(0, 0).is_empty());
+
+        let first = tracker.next_batch();
+        assert_eq!(first.len(), 9);
+        assert_eq!(first[0], (0, 0));
+        assert!(tracker.next_batch().is_empty());
+
+        tracker.batch_received(100.0);
+        assert_eq!(tracker.next_batch().len(), 16);
+        tracker.batch_received(20.0);
+        assert!(tracker.next_batch().is_empty());
+
+        let unloaded = tracker.move_to(1, 0);
+        assert_eq!(unloaded, (-2..=2).map(|z| (-2, z)).collect::<Vec<_>>());
+        assert!(!tracker.is_sent(-2, 0));
+
+        let batch = tracker.next_batch();
+        assert_eq!(batch.len(), 5);
+        assert!(batch.iter().all(|&(x, _)| x == 3));
+
+        tracker.batch_received(f32::NAN);
+        assert_eq!(tracker.set_view_distance(1).len(), 16);
+        assert_eq!(tracker.next_batch(), vec![]);
+    }
+}